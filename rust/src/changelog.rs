@@ -0,0 +1,258 @@
+// Markdown changelog for a release, based on the semantic diff of two versions
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use itertools::Itertools;
+
+use crate::common::format_word_def;
+use crate::db_diff::{Change, ChangeKind, Entity};
+use crate::db_model::{DictCounts, WordEntry};
+
+const DELETED_TAG: &str = "deleted";
+
+const ENTITIES: [(Entity, &str); 6] = [
+    (Entity::Word, "words"),
+    (Entity::Definition, "definitions"),
+    (Entity::Pronunciation, "pronunciations"),
+    (Entity::Tag, "tags"),
+    (Entity::Reference, "references"),
+    (Entity::Note, "notes"),
+];
+
+/// Escape characters with a special meaning in Markdown text
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn count_row(writer: &mut dyn Write, name: &str, old: u32, new: u32) -> io::Result<()> {
+    let delta = i64::from(new) - i64::from(old);
+    writeln!(writer, "| {name} | {old} | {new} | {delta:+} |")
+}
+
+fn write_counts(writer: &mut dyn Write, old: &DictCounts, new: &DictCounts) -> io::Result<()> {
+    writeln!(writer, "| | previous | current | difference |")?;
+    writeln!(writer, "|---|---:|---:|---:|")?;
+    count_row(writer, "words", old.num_words, new.num_words)?;
+    count_row(
+        writer,
+        "definitions",
+        old.num_definitions,
+        new.num_definitions,
+    )?;
+    count_row(writer, "references", old.num_references, new.num_references)?;
+    count_row(writer, "notes", old.num_notes, new.num_notes)?;
+    writeln!(writer)
+}
+
+fn write_change_counts(writer: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+    writeln!(writer, "| | added | removed | changed |")?;
+    writeln!(writer, "|---|---:|---:|---:|")?;
+    for (entity, name) in ENTITIES {
+        let count = |kind| {
+            changes
+                .iter()
+                .filter(|c| c.entity == entity && c.kind == kind)
+                .count()
+        };
+        writeln!(
+            writer,
+            "| {name} | {} | {} | {} |",
+            count(ChangeKind::Added),
+            count(ChangeKind::Removed),
+            count(ChangeKind::Changed)
+        )?;
+    }
+    writeln!(writer)
+}
+
+fn word_summary(word: &WordEntry) -> String {
+    let prons = word
+        .definitions
+        .iter()
+        .flat_map(|d| &d.prons)
+        .map(|p| p.pinyin_mark.as_str())
+        .unique()
+        .join(", ");
+    let definitions = word.definitions.iter().map(|d| &d.definition).join("; ");
+    format!(
+        "{} ({}): {}",
+        escape(&format_word_def(&word.trad, &word.simp, None)),
+        escape(&prons),
+        escape(&definitions)
+    )
+}
+
+fn write_new_words(
+    writer: &mut dyn Write,
+    changes: &[Change],
+    new_entries: &[WordEntry],
+) -> io::Result<()> {
+    let words: HashMap<(&str, &str), &WordEntry> = new_entries
+        .iter()
+        .map(|w| ((w.trad.as_str(), w.simp.as_str()), w))
+        .collect();
+    let mut by_tag: BTreeMap<&str, Vec<&WordEntry>> = BTreeMap::new();
+    let mut untagged = vec![];
+    for change in changes
+        .iter()
+        .filter(|c| c.entity == Entity::Word && c.kind == ChangeKind::Added)
+    {
+        let Some(word) = words.get(&(change.trad.as_str(), change.simp.as_str())) else {
+            continue;
+        };
+        if word.tags.is_empty() {
+            untagged.push(*word);
+        }
+        for tag in &word.tags {
+            by_tag.entry(&tag.tag).or_default().push(word);
+        }
+    }
+    if by_tag.is_empty() && untagged.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer, "## New words\n")?;
+    for (tag, words) in by_tag
+        .into_iter()
+        .chain((!untagged.is_empty()).then_some(("without tags", untagged)))
+    {
+        writeln!(writer, "### {} ({})\n", escape(tag), words.len())?;
+        for word in words {
+            writeln!(writer, "- {}", word_summary(word))?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_list(writer: &mut dyn Write, title: &str, items: &[String]) -> io::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "## {title}\n")?;
+    for item in items {
+        writeln!(writer, "- {item}")?;
+    }
+    writeln!(writer)
+}
+
+fn change_location(change: &Change) -> String {
+    escape(&format_word_def(
+        &change.trad,
+        &change.simp,
+        change.ext_def_id,
+    ))
+}
+
+/// Definitions which are removed completely, either on their own or with their word
+fn removed_definitions(changes: &[Change]) -> Vec<String> {
+    changes
+        .iter()
+        .filter(|c| c.kind == ChangeKind::Removed)
+        .filter(|c| c.entity == Entity::Definition || c.entity == Entity::Word)
+        .map(|c| {
+            format!(
+                "{}: {}",
+                change_location(c),
+                escape(c.old.as_deref().unwrap_or_default())
+            )
+        })
+        .collect()
+}
+
+/// Definitions and words which got the deleted tag, with the texts of the definitions
+fn deleted_definitions(changes: &[Change], new_entries: &[WordEntry]) -> Vec<String> {
+    changes
+        .iter()
+        .filter(|c| {
+            c.entity == Entity::Tag
+                && c.kind == ChangeKind::Added
+                && c.key == DELETED_TAG
+                && c.pinyin.is_none()
+                && c.reference.is_none()
+        })
+        .map(|c| {
+            // the tag on a word deletes all of its definitions
+            let definitions = new_entries
+                .iter()
+                .find(|w| w.trad == c.trad && w.simp == c.simp)
+                .map(|w| {
+                    w.definitions
+                        .iter()
+                        .filter(|d| c.ext_def_id.is_none_or(|id| d.ext_def_id == id))
+                        .map(|d| &d.definition)
+                        .join("; ")
+                })
+                .unwrap_or_default();
+            format!("{}: {}", change_location(c), escape(&definitions))
+        })
+        .collect()
+}
+
+/// Added and removed pronunciations per definition
+fn changed_pronunciations(changes: &[Change]) -> Vec<String> {
+    changes
+        .iter()
+        .filter(|c| c.entity == Entity::Pronunciation)
+        .chunk_by(|c| change_location(c))
+        .into_iter()
+        .map(|(location, group)| {
+            let prons = group
+                .map(|c| {
+                    let symbol = if c.kind == ChangeKind::Added {
+                        '+'
+                    } else {
+                        '-'
+                    };
+                    format!("{symbol}{}", escape(&c.key))
+                })
+                .join(", ");
+            format!("{location}: {prons}")
+        })
+        .collect()
+}
+
+/// Write the changelog in Markdown
+///
+/// The counts are the same as in the meta data of the dictionary, the other sections are based on the
+/// changes between the two versions.
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_changelog(
+    writer: &mut dyn Write,
+    title: &str,
+    counts: (&DictCounts, &DictCounts),
+    changes: &[Change],
+    new_entries: &[WordEntry],
+) -> io::Result<()> {
+    writeln!(writer, "# {}\n", escape(title))?;
+    writeln!(writer, "## Summary\n")?;
+    write_counts(writer, counts.0, counts.1)?;
+    write_change_counts(writer, changes)?;
+    write_new_words(writer, changes, new_entries)?;
+    write_list(writer, "Removed definitions", &removed_definitions(changes))?;
+    write_list(
+        writer,
+        "Definitions tagged as deleted",
+        &deleted_definitions(changes, new_entries),
+    )?;
+    write_list(
+        writer,
+        "Changed pronunciations",
+        &changed_pronunciations(changes),
+    )?;
+    Ok(())
+}
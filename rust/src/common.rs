@@ -2,6 +2,9 @@ use crate::config::WORD_SEP;
 
 pub type SqliteId = i64;
 
+/// Temporary note ids of new notes, which are not finalized yet
+pub const MAX_TEMPORARY_NOTE_ID: u32 = 99;
+
 pub fn format_word_def(trad: &str, simp: &str, ext_def_id: Option<u32>) -> String {
     #[allow(clippy::collapsible_else_if, reason = "maintain symmetry")]
     #[allow(clippy::option_if_let_else, reason = "readability")]
@@ -19,3 +22,45 @@ pub fn format_word_def(trad: &str, simp: &str, ext_def_id: Option<u32>) -> Strin
         }
     }
 }
+
+/// Levenshtein distance between two strings, counted in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Similarity of two texts between 0.0 (completely different) and 1.0 (identical), ignoring case
+#[allow(clippy::cast_precision_loss, reason = "texts are short")]
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    let max_len = a.chars().count().max(b.chars().count());
+    if max_len == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(&a, &b) as f64 / max_len as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("你好", "你們好"), 1);
+        assert!((similarity("Hello", "hello") - 1.0).abs() < f64::EPSILON);
+        assert!((similarity("abcd", "abxd") - 0.75).abs() < f64::EPSILON);
+        assert!(similarity("to eat", "a kind of bird") < 0.5);
+    }
+}
@@ -3,9 +3,12 @@ pub const ITEMS_SEP: &str = ";";
 
 pub const APPROX_TXT_FILE_SIZE: usize = 16_000_000;
 
+/// Version of `DB_SCHEMA`, databases of older versions are upgraded by `db_migrate`
+pub const DB_SCHEMA_VERSION: u32 = 4;
+
 pub const DB_SCHEMA: &str = r#"
 
-PRAGMA user_version = 1;
+PRAGMA user_version = 4;
 
 /* Schema of a dictionary for Mandarin Chinese. The same data can also be represented as a text file. Some fields in this table exist mainly in order to preserve information of the text representation or make the conversions more convenient.
 
@@ -17,7 +20,9 @@ The order of the text file is preserved using the rank field (dict_shared). New
 
 
 
-ext_def_id is a constant unique id within the scope of all definitions for the same word. It is used for references or internal and external links, similar to ext_note_id */
+ext_def_id is a constant unique id within the scope of all definitions for the same word. It is used for references or internal and external links, similar to ext_note_id
+
+A definition can have sub-definitions (parent_id), which have the same word, class and pronunciations and follow their parent in the order of rank */
 CREATE TABLE IF NOT EXISTS "dict_definition" (
 	"id" INTEGER NOT NULL UNIQUE,
 	"shared_id" INTEGER NOT NULL,
@@ -26,9 +31,13 @@ CREATE TABLE IF NOT EXISTS "dict_definition" (
 	-- constant id, used for referencing definitions in the text representation of from external sources
 	"ext_def_id" INTEGER NOT NULL,
 	"class_id" INTEGER NOT NULL,
+	-- parent definition of a sub-definition, NULL for top-level definitions
+	"parent_id" INTEGER,
 	PRIMARY KEY("id"),
 	FOREIGN KEY ("word_id") REFERENCES "dict_word"("id")
 	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("parent_id") REFERENCES "dict_definition"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
 	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
 	ON UPDATE NO ACTION ON DELETE NO ACTION,
 	FOREIGN KEY ("class_id") REFERENCES "dict_class"("id")
@@ -55,6 +64,8 @@ CREATE TABLE IF NOT EXISTS "dict_word" (
 	"trad" TEXT NOT NULL,
 	-- word in simplified characters
 	"simp" TEXT NOT NULL,
+	-- rank in a word frequency list (1 = most frequent), NULL if the word is not in the list or none was used
+	"frequency_rank" INTEGER,
 	PRIMARY KEY("id"),
 	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
 	ON UPDATE NO ACTION ON DELETE NO ACTION
@@ -110,6 +121,27 @@ CREATE TABLE IF NOT EXISTS "dict_reference" (
 
 CREATE INDEX IF NOT EXISTS "dict_reference_index_0"
 ON "dict_reference" ("word_id_src", "definition_id_src");
+/* ext_def_id of a word which is no longer used by a definition. Either the definition moved to another definition
+(word_id_dst, ext_def_id_dst), which can be a redirect again, or it was deleted on date_deleted (YYYY-MM-DD). */
+CREATE TABLE IF NOT EXISTS "dict_redirect" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_id" INTEGER NOT NULL,
+	"word_id" INTEGER NOT NULL,
+	"ext_def_id" INTEGER NOT NULL,
+	"word_id_dst" INTEGER,
+	"ext_def_id_dst" INTEGER,
+	"date_deleted" TEXT,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("word_id") REFERENCES "dict_word"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("word_id_dst") REFERENCES "dict_word"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS "dict_redirect_index_0"
+ON "dict_redirect" ("word_id", "ext_def_id");
 /* dict_shared enables linking tags, notes or references to different entries in other tables
 rank indicates the order of the element, it is a continuous counter
 rank_relative can be used to add new elements with a certain order between two successive ranks */
@@ -229,6 +261,32 @@ pub const fn get_ref_type(ref_type_char: char) -> Option<(&'static str, bool)> {
     })
 }
 
+/// What the target of a reference must carry, checked on the target definition or any definition of the target word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefTargetConstraint {
+    Class(&'static str),
+    #[allow(dead_code, reason = "no reference type requires a tag yet")]
+    Tag(char),
+}
+
+/// Constraint on the target of the given reference type, e.g. a measure word must be a classifier
+pub const fn get_ref_target_constraint(ref_type_char: char) -> Option<RefTargetConstraint> {
+    match ref_type_char {
+        'M' => Some(RefTargetConstraint::Class("classifier")),
+        _ => None,
+    }
+}
+
+/// Default patterns (case-insensitive regex for the whole text in parentheses) of usage labels in definitions and
+/// the ascii tags they are moved to, e.g. "(chiefly Taiwan)" --> t
+pub const USAGE_LABEL_TAGS: &[(&str, char)] = &[
+    ("chiefly Taiwan(ese)?", 't'),
+    ("Taiwan(ese)?", 't'),
+    ("chiefly (mainland )?China", 'c'),
+    ("mainland China", 'c'),
+    ("obsolete", 'x'),
+];
+
 /// Get (name, category, rank) of a tag, there shall not be several tags with the same rank applied to the same item
 pub const fn tag_to_txt_ascii_common(ascii_tag: char) -> Option<(&'static str, &'static str, u8)> {
     Some(match ascii_tag {
@@ -242,6 +300,9 @@ pub const fn tag_to_txt_ascii_common(ascii_tag: char) -> Option<(&'static str, &
         'a' => ("ai-human", "ai", 6),
         'w' => ("wiktionary", "source", 3),
         'm' => ("mdbg", "source", 2),
+        'u' => ("unihan", "source", 4), // generated from the Unihan database
+        's' => ("segmentation", "source", 5), // generated from the words contained in a word
+        'f' => ("frequency-list", "source", 9), // relevance assigned from a word frequency list
         '+' => ("high-relevance", "relevance", 1),
         '-' => ("low-relevance", "relevance", 1),
         'x' => ("lowest-relevance", "relevance", 1),
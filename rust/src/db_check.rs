@@ -1,219 +1,463 @@
 // LLM generated:
-// - regex check if character is Chinese character (translated from python, which was based on stackoverflow answer)
 // - SQL to check for conflicts and add missing things
 
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
 use crate::common;
+use crate::common::SqliteId;
 pub use crate::config::APPROX_TXT_FILE_SIZE;
-use crate::pinyin;
-use regex::Regex;
-use rusqlite::{Connection, Error as SqliteError, Transaction};
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError, OptionalExtension, Transaction};
+use serde::Deserialize;
 
 use crate::db_to_txt;
 use crate::txt_to_db;
 
-// Represents either a single Unicode code point or a range of code points.
-enum HanChar {
-    Single(u32),
-    Range(u32, u32),
-}
-
-// A static slice holding the Unicode ranges for Han characters.
-static LHAN: &[HanChar] = &[
-    HanChar::Range(0x2E80, 0x2E99), // CJK RADICAL REPEAT, CJK RADICAL RAP
-    HanChar::Range(0x2E9B, 0x2EF3), // CJK RADICAL CHOKE, CJK RADICAL C-SIMPLIFIED TURTLE
-    HanChar::Range(0x2F00, 0x2FD5), // KANGXI RADICAL ONE, KANGXI RADICAL FLUTE
-    HanChar::Single(0x3005),        // IDEOGRAPHIC ITERATION MARK
-    HanChar::Single(0x3007),        // IDEOGRAPHIC NUMBER ZERO
-    HanChar::Range(0x3021, 0x3029), // HANGZHOU NUMERAL ONE, HANGZHOU NUMERAL NINE
-    HanChar::Range(0x3038, 0x303A), // HANGZHOU NUMERAL TEN, HANGZHOU NUMERAL THIRTY
-    HanChar::Single(0x303B),        // VERTICAL IDEOGRAPHIC ITERATION MARK
-    HanChar::Range(0x3400, 0x4DB5), // CJK UNIFIED IDEOGRAPH-3400, CJK UNIFIED IDEOGRAPH-4DB5
-    HanChar::Range(0x4E00, 0x9FC3), // CJK UNIFIED IDEOGRAPH-4E00, CJK UNIFIED IDEOGRAPH-9FC3
-    HanChar::Range(0xF900, 0xFA2D), // CJK COMPATIBILITY IDEOGRAPH-F900, CJK COMPATIBILITY IDEOGRAPH-FA2D
-    HanChar::Range(0xFA30, 0xFA6A), // CJK COMPATIBILITY IDEOGRAPH-FA30, CJK COMPATIBILITY IDEOGRAPH-FA6A
-    HanChar::Range(0xFA70, 0xFAD9), // CJK COMPATIBILITY IDEOGRAPH-FA70, CJK COMPATIBILITY IDEOGRAPH-FAD9
-    HanChar::Range(0x20000, 0x2A6D6), // CJK UNIFIED IDEOGRAPH-20000, CJK UNIFIED IDEOGRAPH-2A6D6
-    HanChar::Range(0x2F800, 0x2FA1D), // CJK COMPATIBILITY IDEOGRAPH-2F800, CJK COMPATIBILITY IDEOGRAPH-2FA1D
-];
-
-/// Compiles and returns a regex that matches only Hanzi characters.
-fn get_hanzi_only_regex_pattern() -> Regex {
-    let mut pattern_list = String::new();
-
-    for han_char in LHAN {
-        match *han_char {
-            HanChar::Range(from, to) => {
-                pattern_list.push_str(&format!(
-                    "{}-{}",
-                    char::from_u32(from).unwrap(),
-                    char::from_u32(to).unwrap()
-                ));
-            }
-            HanChar::Single(val) => {
-                pattern_list.push(char::from_u32(val).unwrap());
-            }
+mod baseline;
+mod definitions;
+mod entries;
+mod graph;
+mod references;
+mod stable_ids;
+mod tags;
+
+pub use baseline::Baseline;
+pub use entries::TradSimpTable;
+pub use stable_ids::{StableIds, StableIdsReference};
+
+/// How a finding affects the result of a run, only errors let the run fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("Invalid severity {s}, expected warning or error")),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "Warning"),
+            Self::Error => write!(f, "Error"),
         }
     }
-    let pattern = format!("[{pattern_list}]");
+}
 
-    Regex::new(&pattern).unwrap()
+/// Problem found by a check, the location is the word (and definition) as written in the text format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check_id: &'static str,
+    pub severity: Severity,
+    pub location: String,
+    pub message: String,
+    /// Lines in the text file, empty if the DB was not created from a text file
+    pub lines: Vec<u32>,
+    /// Items (`dict_shared`) the finding is about, used for the inline suppressions
+    pub shared_ids: Vec<SqliteId>,
 }
 
-#[allow(clippy::similar_names, reason = "a vs b")]
-pub fn check_conflicting_notes_on_symmetric_references(
-    conn: &Transaction,
-) -> Result<Vec<String>, SqliteError> {
-    let mut errors = vec![];
-    let mut stmt = conn.prepare(
+impl Finding {
+    /// Attach the items the finding is about, together with their lines in the text file
+    fn set_items(&mut self, conn: &Connection, shared_ids: &[SqliteId]) -> Result<(), SqliteError> {
+        self.lines = source_lines(conn, shared_ids)?;
+        self.shared_ids = shared_ids.to_vec();
+        Ok(())
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Validation {}: {}", self.severity, self.location)?;
+        match self.lines.as_slice() {
+            [] => {}
+            [line] => write!(f, " (line {line})")?,
+            lines => write!(f, " (lines {})", lines.iter().join(", "))?,
+        }
+        write!(f, ": {} ({})", self.message, self.check_id)
+    }
+}
+
+/// Source lines of the shared entries, if the DB was created from a text file
+fn source_lines(conn: &Connection, shared_ids: &[SqliteId]) -> Result<Vec<u32>, SqliteError> {
+    let has_lines: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM temp.sqlite_master WHERE name = 'dict_source_line')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_lines {
+        return Ok(vec![]);
+    }
+    let mut stmt =
+        conn.prepare_cached("SELECT line FROM temp.dict_source_line WHERE shared_id = ?1")?;
+    let mut lines = vec![];
+    for shared_id in shared_ids {
+        if let Some(line) = stmt.query_row([shared_id], |row| row.get(0)).optional()? {
+            lines.push(line);
+        }
+    }
+    Ok(lines)
+}
+
+/// Word (and definition) of an item as written in the text format, e.g. "你好#D1" for a definition, with the
+/// kind of item unless it is the word itself
+fn item_location(conn: &Connection, shared_id: SqliteId) -> Result<String, SqliteError> {
+    let mut stmt = conn.prepare_cached(
         r"
-        SELECT
-            -- Information about the first side of the relationship (Word A)
-            word_A.trad AS word_A_trad,
-            word_A.simp AS word_A_simp,
-            def_A.ext_def_id AS word_A_ext_def_id, -- This will be NULL if the reference is not from a specific definition
-
-            -- Information about the second side of the relationship (Word B)
-            word_B.trad AS word_B_trad,
-            word_B.simp AS word_B_simp,
-            def_B.ext_def_id AS word_B_ext_def_id, -- This will be NULL if the reference is not to a specific definition
-
-            -- Conflicting information from the two symmetric references
-            ref1.id AS reference_A_to_B_id,
-            shared1.note_id AS reference_A_to_B_note_id,
-            ref2.id AS reference_B_to_A_id,
-            shared2.note_id AS reference_B_to_A_note_id
-        FROM
-            dict_reference AS ref1
-        JOIN
-            dict_ref_type AS ref_type ON ref1.ref_type_id = ref_type.id
-        -- This self-join finds the symmetric pair
-        JOIN
-            dict_reference AS ref2 ON ref1.word_id_src = ref2.word_id_dst
-                                AND ref1.word_id_dst = ref2.word_id_src
-                                AND ref1.ref_type_id = ref2.ref_type_id
-                                AND (ref1.definition_id_src = ref2.definition_id_dst OR (ref1.definition_id_src IS NULL AND ref2.definition_id_dst IS NULL))
-                                AND (ref1.definition_id_dst = ref2.definition_id_src OR (ref1.definition_id_dst IS NULL AND ref2.definition_id_src IS NULL))
-        -- Joins to get note information
-        JOIN
-            dict_shared AS shared1 ON ref1.shared_id = shared1.id
-        JOIN
-            dict_shared AS shared2 ON ref2.shared_id = shared2.id
-        -- New joins to get user-friendly identifiers
-        JOIN
-            dict_word AS word_A ON ref1.word_id_src = word_A.id
-        JOIN
-            dict_word AS word_B ON ref1.word_id_dst = word_B.id
-        LEFT JOIN
-            dict_definition AS def_A ON ref1.definition_id_src = def_A.id
-        LEFT JOIN
-            dict_definition AS def_B ON ref1.definition_id_dst = def_B.id
-        WHERE
-            ref_type.is_symmetric = 1
-            -- This condition ensures we process each pair only once
-            AND ref1.id < ref2.id
-            -- The actual conflict condition: both have different, non-null notes
-            AND shared1.note_id IS NOT NULL
-            AND shared2.note_id IS NOT NULL
-            AND shared1.note_id <> shared2.note_id;
-        "
+        SELECT w.trad, w.simp, NULL AS ext_def_id, NULL AS item, NULL AS trad_dst, NULL AS simp_dst,
+            NULL AS ext_def_id_dst
+        FROM dict_word w WHERE w.shared_id = ?1
+        UNION ALL
+        SELECT w.trad, w.simp, d.ext_def_id, NULL, NULL, NULL, NULL
+        FROM dict_definition d JOIN dict_word w ON d.word_id = w.id WHERE d.shared_id = ?1
+        UNION ALL
+        SELECT w.trad, w.simp, NULL, 'pronunciation ' || p.pinyin_num, NULL, NULL, NULL
+        FROM dict_shared_pron sp
+        JOIN dict_pron p ON sp.pron_id = p.id
+        JOIN dict_pron_definition pdp ON sp.id = pdp.shared_pron_id
+        JOIN dict_definition d ON pdp.definition_id = d.id
+        JOIN dict_word w ON d.word_id = w.id
+        WHERE sp.shared_id = ?1
+        UNION ALL
+        SELECT w.trad, w.simp, d.ext_def_id, 'reference to', w_dst.trad, w_dst.simp, d_dst.ext_def_id
+        FROM dict_reference r
+        JOIN dict_word w ON r.word_id_src = w.id
+        LEFT JOIN dict_definition d ON r.definition_id_src = d.id
+        JOIN dict_word w_dst ON r.word_id_dst = w_dst.id
+        LEFT JOIN dict_definition d_dst ON r.definition_id_dst = d_dst.id
+        WHERE r.shared_id = ?1
+        UNION ALL
+        SELECT w.trad, w.simp, NULL, 'redirect R' || rd.ext_def_id, NULL, NULL, NULL
+        FROM dict_redirect rd JOIN dict_word w ON rd.word_id = w.id WHERE rd.shared_id = ?1
+        LIMIT 1
+        ",
     )?;
-    let mut rows = stmt.query([])?;
+    let location = stmt
+        .query_row([shared_id], |row| {
+            let mut location = common::format_word_def(
+                &row.get::<_, String>("trad")?,
+                &row.get::<_, String>("simp")?,
+                row.get("ext_def_id")?,
+            );
+            if let Some(item) = row.get::<_, Option<String>>("item")? {
+                location = format!("{location} {item}");
+            }
+            if let (Some(trad_dst), Some(simp_dst)) = (
+                row.get::<_, Option<String>>("trad_dst")?,
+                row.get::<_, Option<String>>("simp_dst")?,
+            ) {
+                let word_dst =
+                    common::format_word_def(&trad_dst, &simp_dst, row.get("ext_def_id_dst")?);
+                location = format!("{location} {word_dst}");
+            }
+            Ok(location)
+        })
+        .optional()?;
+    Ok(location.unwrap_or_default())
+}
 
-    while let Some(row) = rows.next()? {
-        let word_a_trad: String = row.get("word_A_trad")?;
-        let word_a_simp: String = row.get("word_A_simp")?;
-        let word_a_ext_def_id: Option<u32> = row.get("word_A_ext_def_id")?;
-        let word_a = common::format_word_def(&word_a_trad, &word_a_simp, word_a_ext_def_id);
+/// Validation of the entries in the DB
+///
+/// New checks are added to `registry()`, they can be enabled or disabled per run with a `CheckConfig`.
+pub trait Check {
+    /// Stable id used in the CLI, config files and the output, e.g. `trad-simp-length`
+    fn id(&self) -> &'static str;
 
-        let word_b_trad: String = row.get("word_B_trad")?;
-        let word_b_simp: String = row.get("word_B_simp")?;
-        let word_b_ext_def_id: Option<u32> = row.get("word_B_ext_def_id")?;
-        let word_b = common::format_word_def(&word_b_trad, &word_b_simp, word_b_ext_def_id);
+    fn description(&self) -> &'static str;
 
-        errors.push(format!("Validation Error: Different notes on symmetric references between {word_a} and {word_b}"));
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    /// Checks which are expensive or report many findings in the current data can be opt-in
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// Run the check, the findings have the default severity of the check
+    ///
+    /// # Errors
+    /// Returns an error if the database can't be queried
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError>;
+
+    /// Checks with an obvious fix for their findings, see `fix`
+    fn is_fixable(&self) -> bool {
+        false
+    }
+
+    /// Fix the findings of this check in the DB, returns a description of every change
+    ///
+    /// # Errors
+    /// Returns an error if the database can't be queried or updated
+    fn fix(&self, _tx: &Transaction, _findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        Ok(vec![])
+    }
+
+    fn finding(&self, location: String, message: String) -> Finding {
+        Finding {
+            check_id: self.id(),
+            severity: self.default_severity(),
+            location,
+            message,
+            lines: vec![],
+            shared_ids: vec![],
+        }
     }
-    todo!(); // TODO test this
-    Ok(errors)
 }
 
-// TODO take list of stuff to check, e.g. if the source is a parsed text file some things might be ensured by the parser, SQL ensures other stuff
-pub fn check_entries(conn: &Connection) -> Result<Vec<String>, SqliteError> {
-    let mut errors = vec![];
+/// Data for the checks which doesn't come from the DB
+#[derive(Debug, Default)]
+pub struct CheckOptions {
+    /// Mapping of traditional to simplified characters, the bundled table of common characters if not set
+    pub trad_simp_table: Option<TradSimpTable>,
+    /// Previous release for the stable ids check, the check finds nothing if not set
+    pub stable_ids: Option<StableIdsReference>,
+}
+
+/// All available checks with the default options
+#[must_use]
+pub fn registry() -> Vec<Box<dyn Check>> {
+    registry_with_options(CheckOptions::default())
+}
+
+/// All available checks, in the order in which they are run
+#[must_use]
+pub fn registry_with_options(options: CheckOptions) -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(entries::TradSimpLength),
+        Box::new(entries::TradSimpMapping {
+            table: options
+                .trad_simp_table
+                .unwrap_or_else(TradSimpTable::bundled),
+        }),
+        Box::new(entries::NonHanCharacter),
+        Box::new(entries::CompatibilityIdeograph),
+        Box::new(entries::WordPunctuation),
+        Box::new(entries::PinyinSyllableCount),
+        Box::new(entries::PinyinSpelling),
+        Box::new(entries::PinyinSyllable),
+        Box::new(tags::DuplicateTag),
+        Box::new(tags::TagRankConflict),
+        Box::new(definitions::DefinitionWhitespace),
+        Box::new(definitions::DuplicateDefinition),
+        Box::new(definitions::DuplicateDefinitionVariant),
+        Box::new(references::SymmetricReferenceMissing),
+        Box::new(references::SymmetricReferenceConflict),
+        Box::new(graph::SelfReference),
+        Box::new(graph::VariantChain),
+        Box::new(graph::ReferenceCycle),
+        Box::new(graph::ReferenceTarget),
+        Box::new(
+            options
+                .stable_ids
+                .map_or_else(StableIds::default, StableIds::new),
+        ),
+    ]
+}
+
+/// Selection of the checks for a run and the severity of their findings, e.g. loaded from a JSON config file:
+/// `{"enable": ["some-check"], "disable": ["other-check"], "severity": {"some-check": "warning"}}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckConfig {
+    #[serde(default)]
+    pub enable: Vec<String>,
+    #[serde(default)]
+    pub disable: Vec<String>,
+    /// Overrides the severity of all findings of a check
+    #[serde(default)]
+    pub severity: BTreeMap<String, Severity>,
+}
+
+impl CheckConfig {
+    /// Enable and disable checks on top of the current selection, e.g. from the command line
+    pub fn apply(&mut self, enable: &[String], disable: &[String]) {
+        for id in enable {
+            self.disable.retain(|d| d != id);
+            self.enable.push(id.clone());
+        }
+        for id in disable {
+            self.enable.retain(|e| e != id);
+            self.disable.push(id.clone());
+        }
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self, check: &dyn Check) -> bool {
+        let id = check.id();
+        if self.disable.iter().any(|d| d == id) {
+            false
+        } else {
+            self.enable.iter().any(|e| e == id) || check.enabled_by_default()
+        }
+    }
+
+    /// Ids in the config which don't belong to any of the checks
+    #[must_use]
+    pub fn unknown_ids(&self, checks: &[Box<dyn Check>]) -> Vec<&str> {
+        self.enable
+            .iter()
+            .chain(&self.disable)
+            .chain(self.severity.keys())
+            .filter(|id| !checks.iter().any(|c| c.id() == id.as_str()))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Comment directive to suppress findings of some checks for an item and everything below it, e.g.
+/// `# ignore-check: trad-simp-length, pinyin-syllable`
+pub const SUPPRESSION_DIRECTIVE: &str = "ignore-check:";
+
+/// Check ids suppressed per item by the comment directive
+fn load_suppressions(conn: &Connection) -> Result<HashMap<SqliteId, Vec<String>>, SqliteError> {
     let mut stmt = conn.prepare(
         r"
-        SELECT
-            w.trad,
-            w.simp,
-            c.name AS class_name,
-		GROUP_CONCAT(t_w.ascii_symbol, ';') AS word_tags,
-        GROUP_CONCAT(p.pinyin_num, ';') AS pinyin_nums,
-		GROUP_CONCAT(t_p.ascii_symbol, ';') AS pinyin_tags
-        FROM dict_definition def
-        JOIN dict_shared s ON def.shared_id = s.id
-        JOIN dict_word w ON def.word_id = w.id
-		JOIN dict_shared sw ON w.shared_id = sw.id
-		LEFT JOIN dict_shared_tag st_w ON w.shared_id = st_w.for_shared_id
-		LEFT JOIN dict_tag t_w ON st_w.tag_id = t_w.id
-        JOIN dict_class c ON def.class_id = c.id
-        LEFT JOIN dict_pron_definition pdp ON def.id = pdp.definition_id
-        LEFT JOIN dict_shared_pron sp ON pdp.shared_pron_id = sp.id
-        LEFT JOIN dict_pron p ON sp.pron_id = p.id
-        LEFT JOIN dict_shared p_s ON sp.shared_id = p_s.id
-		LEFT JOIN dict_shared_tag st_p ON p_s.id = st_p.for_shared_id
-		LEFT JOIN dict_tag t_p ON st_p.tag_id = t_p.id
-        GROUP BY def.id
-        ORDER BY s.rank, s.rank_relative;
+        SELECT s.id, c.comment
+        FROM dict_shared s
+        JOIN dict_comment c ON s.comment_id = c.id
+        WHERE c.comment LIKE ?1 || '%'
         ",
     )?;
-
-    let hanzi_pattern = get_hanzi_only_regex_pattern();
-    let mut rows = stmt.query([])?;
-
+    let mut suppressions = HashMap::new();
+    let mut rows = stmt.query([SUPPRESSION_DIRECTIVE])?;
     while let Some(row) = rows.next()? {
-        let trad: String = row.get("trad")?;
-        let simp: String = row.get("simp")?;
-        let word_tags: Option<String> = row.get("word_tags")?;
-        let pinyin_tags: Option<String> = row.get("pinyin_tags")?;
-        let _class_name: String = row.get("class_name")?;
-        let pinyin_nums: Vec<String> = row
-            .get::<_, String>("pinyin_nums")?
-            .split(';')
-            .map(std::borrow::ToOwned::to_owned)
+        let comment: String = row.get(1)?;
+        let ids = comment
+            .strip_prefix(SUPPRESSION_DIRECTIVE)
+            .unwrap_or_default()
+            .split(',')
+            .map(|id| id.trim().to_owned())
+            .filter(|id| !id.is_empty())
             .collect();
+        suppressions.insert(row.get(0)?, ids);
+    }
+    Ok(suppressions)
+}
 
-        // check if number of characters is the same in trad and simp
-        if trad.chars().count() != simp.chars().count() {
-            if let Some(w_t) = word_tags {
-                if !w_t.contains(['i', 'X']) {
-                    errors.push(format!("Validation Error: Different numbers of characters, traditional: {trad} simplified: {simp}"));
-                    continue;
+/// The item itself, its definition (for pronunciations and references of a definition) and its word
+fn item_and_parents(conn: &Connection, shared_id: SqliteId) -> Result<Vec<SqliteId>, SqliteError> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        SELECT w.shared_id, d.shared_id
+        FROM dict_definition d JOIN dict_word w ON d.word_id = w.id WHERE d.shared_id = ?1
+        UNION ALL
+        SELECT w.shared_id, d.shared_id
+        FROM dict_shared_pron sp
+        JOIN dict_pron_definition pdp ON sp.id = pdp.shared_pron_id
+        JOIN dict_definition d ON pdp.definition_id = d.id
+        JOIN dict_word w ON d.word_id = w.id
+        WHERE sp.shared_id = ?1
+        UNION ALL
+        SELECT w.shared_id, d.shared_id
+        FROM dict_reference r
+        JOIN dict_word w ON r.word_id_src = w.id
+        LEFT JOIN dict_definition d ON r.definition_id_src = d.id
+        WHERE r.shared_id = ?1
+        UNION ALL
+        SELECT w.shared_id, NULL
+        FROM dict_redirect rd JOIN dict_word w ON rd.word_id = w.id WHERE rd.shared_id = ?1
+        ",
+    )?;
+    let mut ids = vec![shared_id];
+    let mut rows = stmt.query([shared_id])?;
+    while let Some(row) = rows.next()? {
+        ids.extend([row.get::<_, SqliteId>(0)?]);
+        ids.extend(row.get::<_, Option<SqliteId>>(1)?);
+    }
+    Ok(ids)
+}
+
+/// Run all enabled checks, findings suppressed by a comment directive are left out
+///
+/// # Errors
+/// Returns an error if the database can't be queried
+pub fn run_checks(
+    conn: &Connection,
+    checks: &[Box<dyn Check>],
+    config: &CheckConfig,
+) -> Result<Vec<Finding>, SqliteError> {
+    let suppressions = load_suppressions(conn)?;
+    let mut findings = vec![];
+    for check in checks.iter().filter(|c| config.is_enabled(c.as_ref())) {
+        for mut finding in check.run(conn)? {
+            let mut is_suppressed = false;
+            if !suppressions.is_empty() {
+                for shared_id in &finding.shared_ids {
+                    is_suppressed |= item_and_parents(conn, *shared_id)?.iter().any(|id| {
+                        suppressions
+                            .get(id)
+                            .is_some_and(|ids| ids.iter().any(|i| i == finding.check_id))
+                    });
                 }
             }
+            if is_suppressed {
+                continue;
+            }
+            if let Some(severity) = config.severity.get(check.id()) {
+                finding.severity = *severity;
+            }
+            findings.push(finding);
         }
+    }
+    Ok(findings)
+}
+
+/// Change made by the fix of a check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub check_id: &'static str,
+    pub change: String,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fixed: {} ({})", self.change, self.check_id)
+    }
+}
 
-        // check if the number of pinyin syllables matches the number of Chinese characters
-        let trad_hanzi_only: String = hanzi_pattern
-            .find_iter(&trad)
-            .map(|mat| mat.as_str())
+/// Apply the fixes of the checks to their findings, e.g. from `run_checks`
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn apply_fixes(
+    tx: &Transaction,
+    checks: &[Box<dyn Check>],
+    findings: &[Finding],
+) -> Result<Vec<Fix>, SqliteError> {
+    let mut fixes = vec![];
+    for check in checks.iter().filter(|c| c.is_fixable()) {
+        let check_findings: Vec<Finding> = findings
+            .iter()
+            .filter(|f| f.check_id == check.id())
+            .cloned()
             .collect();
-        if trad_hanzi_only.len() == trad.len() {
-            let possible_erhuas = trad.chars().filter(|c| *c == '兒').count();
-            let num_trad_chars = trad.chars().count();
-            let expected_syllables = (num_trad_chars - possible_erhuas)..=num_trad_chars;
-            for pinyin_num in pinyin_nums {
-                if let Some(p_t) = &pinyin_tags {
-                    if p_t.contains(['i', 'X']) {
-                        continue;
-                    }
-                }
-                let num_pinyin_syllables = pinyin::count_syllables(&pinyin_num);
-                if !expected_syllables.contains(&num_pinyin_syllables) {
-                    errors.push(format!("Validation Error: pinyin syllables don't match number of characters, traditional: {trad} pinyin: {pinyin_num}"));
-                }
-            }
+        if check_findings.is_empty() {
+            continue;
         }
+        fixes.extend(
+            check
+                .fix(tx, &check_findings)?
+                .into_iter()
+                .map(|change| Fix {
+                    check_id: check.id(),
+                    change,
+                }),
+        );
     }
-    Ok(errors)
+    Ok(fixes)
 }
 
 pub fn round_trip_check(conn: &Connection) -> Result<Vec<u8>, SqliteError> {
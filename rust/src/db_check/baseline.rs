@@ -0,0 +1,65 @@
+// Known findings which don't let a run fail, so that new checks can be added before the existing problems
+// in the data are fixed
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::Finding;
+
+/// A finding without its lines, which change whenever lines are added above it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BaselineEntry {
+    check: String,
+    location: String,
+    message: String,
+}
+
+impl From<&Finding> for BaselineEntry {
+    fn from(finding: &Finding) -> Self {
+        Self {
+            check: finding.check_id.to_owned(),
+            location: finding.location.clone(),
+            message: finding.message.clone(),
+        }
+    }
+}
+
+/// Known findings, stored as a JSON array of `{"check": ..., "location": ..., "message": ...}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Baseline {
+    entries: BTreeSet<BaselineEntry>,
+}
+
+impl Baseline {
+    #[must_use]
+    pub fn from_findings(findings: &[Finding]) -> Self {
+        Self {
+            entries: findings.iter().map(BaselineEntry::from).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, finding: &Finding) -> bool {
+        self.entries.contains(&BaselineEntry::from(finding))
+    }
+
+    /// Number of entries which are not found anymore, i.e. the baseline can be updated
+    #[must_use]
+    pub fn num_fixed(&self, findings: &[Finding]) -> usize {
+        let current = Self::from_findings(findings);
+        self.entries.difference(&current.entries).count()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
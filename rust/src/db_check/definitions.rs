@@ -0,0 +1,311 @@
+// Checks of the definitions of a word, e.g. duplicates from the Wiktionary import
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::{Connection, Error as SqliteError, Transaction};
+
+use super::{Check, Finding, Severity, item_location};
+use crate::common;
+use crate::common::SqliteId;
+
+/// Definitions which are at least this similar (after normalization) are reported as near-duplicates
+const MIN_NEAR_DUPLICATE_SIMILARITY: f64 = 0.8;
+
+#[derive(Debug)]
+struct Definition {
+    shared_id: SqliteId,
+    trad: String,
+    simp: String,
+    ext_def_id: u32,
+    // parent of a sub-definition (id of the definition)
+    parent_id: Option<SqliteId>,
+    text: String,
+    normalized: String,
+}
+
+impl Definition {
+    fn location(&self) -> String {
+        common::format_word_def(&self.trad, &self.simp, Some(self.ext_def_id))
+    }
+}
+
+/// Text of a definition without case, punctuation and parentheticals, parts concatenated with `---` are sorted
+pub(super) fn normalize_definition(definition: &str) -> String {
+    let mut without_parentheticals = String::new();
+    let mut depth = 0_usize;
+    for c in definition.chars() {
+        match c {
+            '(' | '（' => depth += 1,
+            ')' | '）' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => without_parentheticals.push(c),
+            _ => {}
+        }
+    }
+    let mut parts: Vec<String> = without_parentheticals
+        .split("---")
+        .map(|part| {
+            part.to_lowercase()
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|part| !part.is_empty())
+        .collect();
+    parts.sort();
+    parts.dedup();
+    parts.join(" ")
+}
+
+/// All definitions per word, except definitions which are exempt (i or X tag)
+fn load_definitions(conn: &Connection) -> Result<HashMap<SqliteId, Vec<Definition>>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT
+            def.word_id,
+            def.shared_id,
+            w.trad,
+            w.simp,
+            def.ext_def_id,
+            def.parent_id,
+            def.definition
+        FROM dict_definition def
+        JOIN dict_word w ON def.word_id = w.id
+        JOIN dict_shared s ON def.shared_id = s.id
+        WHERE NOT EXISTS (
+            SELECT 1 FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+            WHERE st.for_shared_id = def.shared_id AND t.ascii_symbol IN ('i', 'X'))
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    let mut definitions: HashMap<SqliteId, Vec<Definition>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let definition: String = row.get("definition")?;
+        definitions
+            .entry(row.get("word_id")?)
+            .or_default()
+            .push(Definition {
+                shared_id: row.get("shared_id")?,
+                trad: row.get("trad")?,
+                simp: row.get("simp")?,
+                ext_def_id: row.get("ext_def_id")?,
+                parent_id: row.get("parent_id")?,
+                normalized: normalize_definition(&definition),
+                text: definition,
+            });
+    }
+    Ok(definitions)
+}
+
+/// Finding for two definitions if they are (nearly) the same, only definitions with the same text are errors
+fn compare_definitions(
+    check: &dyn Check,
+    conn: &Connection,
+    location: String,
+    a: &Definition,
+    b: &Definition,
+) -> Result<Option<Finding>, SqliteError> {
+    if a.normalized.is_empty() || b.normalized.is_empty() {
+        return Ok(None);
+    }
+    let similarity = common::similarity(&a.normalized, &b.normalized);
+    if similarity < MIN_NEAR_DUPLICATE_SIMILARITY {
+        return Ok(None);
+    }
+    let kind = if a.normalized == b.normalized {
+        "duplicate"
+    } else {
+        "near-duplicate"
+    };
+    let mut finding = check.finding(
+        location,
+        format!(
+            "{kind} definitions {} and {} (similarity {similarity:.2})",
+            a.location(),
+            b.location()
+        ),
+    );
+    if a.text == b.text {
+        finding.severity = Severity::Error;
+    }
+    finding.set_items(conn, &[a.shared_id, b.shared_id])?;
+    Ok(Some(finding))
+}
+
+/// Definition with single spaces between words and no whitespace at the beginning or end
+fn normalize_whitespace(definition: &str) -> String {
+    definition.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Definitions with whitespace which is not visible in most outputs, e.g. copied from other sources
+fn definitions_with_whitespace(conn: &Connection) -> Result<Vec<(SqliteId, String)>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT def.shared_id, def.definition
+        FROM dict_definition def
+        JOIN dict_shared s ON def.shared_id = s.id
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    let definitions = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, SqliteId>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(definitions
+        .into_iter()
+        .filter(|(_, definition)| normalize_whitespace(definition) != *definition)
+        .collect())
+}
+
+/// Leading, trailing or repeated whitespace in a definition
+#[derive(Debug)]
+pub struct DefinitionWhitespace;
+
+impl Check for DefinitionWhitespace {
+    fn id(&self) -> &'static str {
+        "definition-whitespace"
+    }
+
+    fn description(&self) -> &'static str {
+        "definitions have no leading, trailing or repeated whitespace"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn is_fixable(&self) -> bool {
+        true
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for (shared_id, definition) in definitions_with_whitespace(conn)? {
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                format!("leading, trailing or repeated whitespace in \"{definition}\""),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    fn fix(&self, tx: &Transaction, findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        let mut stmt_update =
+            tx.prepare("UPDATE dict_definition SET definition = ?2 WHERE shared_id = ?1")?;
+        let mut changes = vec![];
+        for (shared_id, definition) in definitions_with_whitespace(tx)? {
+            if !findings.iter().any(|f| f.shared_ids == [shared_id]) {
+                continue;
+            }
+            let normalized = normalize_whitespace(&definition);
+            stmt_update.execute((shared_id, &normalized))?;
+            changes.push(format!(
+                "{}: \"{definition}\" -> \"{normalized}\"",
+                item_location(tx, shared_id)?
+            ));
+        }
+        Ok(changes)
+    }
+}
+
+/// Same or nearly the same definition twice within a word, e.g. with different classes. Sub-definitions are only
+/// compared with their siblings, e.g. "old" below two different parents is not a duplicate
+#[derive(Debug)]
+pub struct DuplicateDefinition;
+
+impl Check for DuplicateDefinition {
+    fn id(&self) -> &'static str {
+        "duplicate-definition"
+    }
+
+    fn description(&self) -> &'static str {
+        "definitions of a word are not the same after normalization (definitions with the same text are errors, others warnings)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let definitions = load_definitions(conn)?;
+        let mut words: Vec<&Vec<Definition>> = definitions.values().collect();
+        words.sort_by_key(|defs| defs[0].shared_id);
+        let mut findings = vec![];
+        for defs in words {
+            for (i, a) in defs.iter().enumerate() {
+                for b in defs[i + 1..].iter().filter(|b| b.parent_id == a.parent_id) {
+                    let location = common::format_word_def(&a.trad, &a.simp, None);
+                    findings.extend(compare_definitions(self, conn, location, a, b)?);
+                }
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Same or nearly the same definition in a word and its variant (references V and v), only top-level definitions
+/// are compared
+#[derive(Debug)]
+pub struct DuplicateDefinitionVariant;
+
+impl Check for DuplicateDefinitionVariant {
+    fn id(&self) -> &'static str {
+        "duplicate-definition-variant"
+    }
+
+    fn description(&self) -> &'static str {
+        "definitions of a word and its variants (V/v references) are not the same after normalization"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let definitions = load_definitions(conn)?;
+        let mut stmt = conn.prepare(
+            r"
+            SELECT DISTINCT r.word_id_src, r.word_id_dst
+            FROM dict_reference r
+            JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+            JOIN dict_shared s ON r.shared_id = s.id
+            WHERE rt.ascii_symbol IN ('V', 'v') AND r.word_id_src != r.word_id_dst
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let variants = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, SqliteId>(0)?, row.get::<_, SqliteId>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut findings = vec![];
+        let mut compared = HashSet::new();
+        for (word_id, variant_word_id) in variants {
+            // references in both directions are compared only once
+            if !compared.insert((word_id.min(variant_word_id), word_id.max(variant_word_id))) {
+                continue;
+            }
+            let (Some(defs), Some(variant_defs)) =
+                (definitions.get(&word_id), definitions.get(&variant_word_id))
+            else {
+                continue;
+            };
+            let top_level = |def: &&Definition| def.parent_id.is_none();
+            for a in defs.iter().filter(top_level) {
+                for b in variant_defs.iter().filter(top_level) {
+                    let location = common::format_word_def(&a.trad, &a.simp, None);
+                    findings.extend(compare_definitions(self, conn, location, a, b)?);
+                }
+            }
+        }
+        Ok(findings)
+    }
+}
@@ -0,0 +1,615 @@
+// Checks of single entries: characters of the word and the pronunciations
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead};
+
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError, Transaction};
+
+use super::{Check, Finding, Severity, item_location};
+use crate::common;
+use crate::common::SqliteId;
+use crate::han;
+use crate::pinyin;
+
+/// Tags which exempt an item from the checks: irregular (i) and deleted (X)
+fn is_exempt(tags: Option<&str>) -> bool {
+    tags.is_some_and(|t| t.contains(['i', 'X']))
+}
+
+#[derive(Debug)]
+pub struct TradSimpLength;
+
+impl Check for TradSimpLength {
+    fn id(&self) -> &'static str {
+        "trad-simp-length"
+    }
+
+    fn description(&self) -> &'static str {
+        "traditional and simplified word have the same number of characters"
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                w.trad,
+                w.simp,
+                w.shared_id,
+                GROUP_CONCAT(t.ascii_symbol, ';') AS word_tags
+            FROM dict_word w
+            JOIN dict_shared s ON w.shared_id = s.id
+            LEFT JOIN dict_shared_tag st ON w.shared_id = st.for_shared_id
+            LEFT JOIN dict_tag t ON st.tag_id = t.id
+            GROUP BY w.id
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            let shared_id: SqliteId = row.get("shared_id")?;
+            let word_tags: Option<String> = row.get("word_tags")?;
+            if trad.chars().count() != simp.chars().count() && !is_exempt(word_tags.as_deref()) {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    "different numbers of characters in the traditional and simplified word"
+                        .to_owned(),
+                );
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+#[derive(Debug)]
+pub struct PinyinSyllableCount;
+
+impl Check for PinyinSyllableCount {
+    fn id(&self) -> &'static str {
+        "pinyin-syllable-count"
+    }
+
+    fn description(&self) -> &'static str {
+        "number of pinyin syllables matches the number of characters (erhua is optional)"
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                w.trad,
+                w.simp,
+                p.pinyin_num,
+                sp.shared_id,
+                (SELECT GROUP_CONCAT(t.ascii_symbol, ';')
+                    FROM dict_shared_tag st
+                    JOIN dict_tag t ON st.tag_id = t.id
+                    WHERE st.for_shared_id = p_s.id) AS pinyin_tags
+            FROM dict_shared_pron sp
+            JOIN dict_shared p_s ON sp.shared_id = p_s.id
+            JOIN dict_pron p ON sp.pron_id = p.id
+            JOIN dict_pron_definition pdp ON sp.id = pdp.shared_pron_id
+            JOIN dict_definition def ON pdp.definition_id = def.id
+            JOIN dict_word w ON def.word_id = w.id
+            GROUP BY sp.id
+            ORDER BY p_s.rank, p_s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            let pinyin_num: String = row.get("pinyin_num")?;
+            let pinyin_tags: Option<String> = row.get("pinyin_tags")?;
+            if is_exempt(pinyin_tags.as_deref()) {
+                continue;
+            }
+            if !han::is_han_only(&trad) {
+                continue;
+            }
+            let possible_erhuas = trad.chars().filter(|c| *c == '兒').count();
+            let num_trad_chars = trad.chars().count();
+            let expected_syllables = (num_trad_chars - possible_erhuas)..=num_trad_chars;
+            if !expected_syllables.contains(&pinyin::count_syllables(&pinyin_num)) {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    format!("pinyin syllables don't match the number of characters: {pinyin_num}"),
+                );
+                finding.set_items(conn, &[row.get("shared_id")?])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Pronunciations which are not spelled like in the rest of the dictionary, e.g. ü instead of v
+fn pronunciations_to_normalize(
+    conn: &Connection,
+) -> Result<Vec<(SqliteId, String, String)>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT sp.shared_id, p.pinyin_num
+        FROM dict_shared_pron sp
+        JOIN dict_shared p_s ON sp.shared_id = p_s.id
+        JOIN dict_pron p ON sp.pron_id = p.id
+        ORDER BY p_s.rank, p_s.rank_relative;
+        ",
+    )?;
+    let prons = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, SqliteId>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(prons
+        .into_iter()
+        .filter_map(|(shared_id, pinyin_num)| {
+            let normalized = pinyin::normalize_spelling(&pinyin_num);
+            (normalized != pinyin_num).then_some((shared_id, pinyin_num, normalized))
+        })
+        .collect())
+}
+
+/// Spelling of the pinyin with tone numbers: v for ü and no lookalikes like the IPA ɡ
+#[derive(Debug)]
+pub struct PinyinSpelling;
+
+impl Check for PinyinSpelling {
+    fn id(&self) -> &'static str {
+        "pinyin-spelling"
+    }
+
+    fn description(&self) -> &'static str {
+        "pinyin is written with v for ü (shown as ü with tone marks) and without lookalike letters like the IPA ɡ"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn is_fixable(&self) -> bool {
+        true
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for (shared_id, pinyin_num, normalized) in pronunciations_to_normalize(conn)? {
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                format!("pinyin {pinyin_num} should be written as {normalized}"),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    fn fix(&self, tx: &Transaction, findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        let mut stmt_insert =
+            tx.prepare("INSERT OR IGNORE INTO dict_pron (pinyin_num, pinyin_mark) VALUES (?1,?2)")?;
+        let mut stmt_update = tx.prepare(
+            r"
+            UPDATE dict_shared_pron
+            SET pron_id = (SELECT id FROM dict_pron WHERE pinyin_num = ?2)
+            WHERE shared_id = ?1
+            ",
+        )?;
+        let mut changes = vec![];
+        for (shared_id, pinyin_num, normalized) in pronunciations_to_normalize(tx)? {
+            if !findings.iter().any(|f| f.shared_ids == [shared_id]) {
+                continue;
+            }
+            stmt_insert.execute((&normalized, pinyin::pinyin_mark_from_num(&normalized)))?;
+            stmt_update.execute((shared_id, &normalized))?;
+            changes.push(format!(
+                "{}: {pinyin_num} -> {normalized}",
+                item_location(tx, shared_id)?
+            ));
+        }
+        // pronunciations which were only used with the old spelling
+        tx.execute(
+            "DELETE FROM dict_pron WHERE id NOT IN (SELECT pron_id FROM dict_shared_pron)",
+            [],
+        )?;
+        Ok(changes)
+    }
+}
+
+#[derive(Debug)]
+pub struct PinyinSyllable;
+
+impl Check for PinyinSyllable {
+    fn id(&self) -> &'static str {
+        "pinyin-syllable"
+    }
+
+    fn description(&self) -> &'static str {
+        "pinyin syllables are in the Mandarin syllable inventory (including erhua, ê and interjections)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                w.trad,
+                w.simp,
+                p.pinyin_num,
+                sp.shared_id,
+                (SELECT GROUP_CONCAT(t.ascii_symbol, ';')
+                    FROM dict_shared_tag st
+                    JOIN dict_tag t ON st.tag_id = t.id
+                    WHERE st.for_shared_id = p_s.id) AS pinyin_tags
+            FROM dict_shared_pron sp
+            JOIN dict_shared p_s ON sp.shared_id = p_s.id
+            JOIN dict_pron p ON sp.pron_id = p.id
+            JOIN dict_pron_definition pdp ON sp.id = pdp.shared_pron_id
+            JOIN dict_definition def ON pdp.definition_id = def.id
+            JOIN dict_word w ON def.word_id = w.id
+            GROUP BY sp.id
+            ORDER BY p_s.rank, p_s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            let pinyin_num: String = row.get("pinyin_num")?;
+            let shared_id: SqliteId = row.get("shared_id")?;
+            let pinyin_tags: Option<String> = row.get("pinyin_tags")?;
+            // letters in the word are usually also in the pinyin, e.g. "A A zhi4"
+            if is_exempt(pinyin_tags.as_deref()) || !han::is_han_only(&trad) {
+                continue;
+            }
+            // other spellings are reported by the fixable check pinyin-spelling
+            for invalid in pinyin::invalid_syllables(&pinyin::normalize_spelling(&pinyin_num)) {
+                let suggestion = invalid
+                    .suggestion
+                    .map(|s| format!(", did you mean {s}?"))
+                    .unwrap_or_default();
+                let message = format!(
+                    "invalid pinyin syllable {} at position {} of {pinyin_num}{suggestion}",
+                    invalid.syllable, invalid.position
+                );
+                let mut finding =
+                    self.finding(common::format_word_def(&trad, &simp, None), message);
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Mapping of traditional characters to their simplified forms
+#[derive(Debug, Clone, Default)]
+pub struct TradSimpTable {
+    simplified: HashMap<char, Vec<char>>,
+    known: HashSet<char>,
+}
+
+/// Common characters in the format of the `OpenCC` table
+static BUNDLED_TRAD_SIMP_TABLE: &str = include_str!("../../data/trad_simp_chars.txt");
+
+impl TradSimpTable {
+    /// Table of common characters which is part of the tool
+    #[must_use]
+    pub fn bundled() -> Self {
+        let mut table = Self::default();
+        for line in BUNDLED_TRAD_SIMP_TABLE.lines() {
+            table.add_line(line);
+        }
+        table
+    }
+
+    /// Read either the `OpenCC` table (`TSCharacters.txt`, e.g. "乾\t乾 干") or the variants of the Unihan
+    /// database (`Unihan_Variants.txt`, `kSimplifiedVariant` and `kTraditionalVariant`), other lines are ignored
+    ///
+    /// # Errors
+    /// Returns an error if reading fails
+    pub fn read(reader: &mut dyn BufRead) -> io::Result<Self> {
+        let mut table = Self::default();
+        for line in reader.lines() {
+            table.add_line(&line?);
+        }
+        Ok(table)
+    }
+
+    fn add_line(&mut self, line: &str) {
+        if line.starts_with('#') {
+            return;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            [code_point, "kSimplifiedVariant", simplified] => {
+                if let Some(trad) = han::parse_code_point(code_point) {
+                    for simp in simplified.split(' ').filter_map(han::parse_code_point) {
+                        self.insert(trad, simp);
+                    }
+                }
+            }
+            [code_point, "kTraditionalVariant", traditional] => {
+                if let Some(simp) = han::parse_code_point(code_point) {
+                    for trad in traditional.split(' ').filter_map(han::parse_code_point) {
+                        self.insert(trad, simp);
+                    }
+                }
+            }
+            [trad, simplified] => {
+                let mut trad_chars = trad.chars();
+                if let (Some(trad), None) = (trad_chars.next(), trad_chars.next()) {
+                    for simp in simplified.split(' ').filter_map(|s| s.chars().next()) {
+                        self.insert(trad, simp);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, trad: char, simp: char) {
+        self.known.extend([trad, simp]);
+        let simplified = self.simplified.entry(trad).or_default();
+        if !simplified.contains(&simp) {
+            simplified.push(simp);
+        }
+    }
+
+    /// Characters which are only used in traditional writing, e.g. 國 but not 乾 (simplified 干 or 乾)
+    fn is_traditional_only(&self, c: char) -> bool {
+        self.simplified.get(&c).is_some_and(|s| !s.contains(&c))
+    }
+
+    /// Characters which are not in the table can't be judged, e.g. if the table only contains common characters
+    fn is_known(&self, c: char) -> bool {
+        self.known.contains(&c)
+    }
+
+    fn is_mapping(&self, trad: char, simp: char) -> bool {
+        trad == simp && !self.is_traditional_only(trad)
+            || self
+                .simplified
+                .get(&trad)
+                .is_some_and(|s| s.contains(&simp))
+    }
+}
+
+#[derive(Debug)]
+pub struct TradSimpMapping {
+    pub table: TradSimpTable,
+}
+
+impl Check for TradSimpMapping {
+    fn id(&self) -> &'static str {
+        "trad-simp-mapping"
+    }
+
+    fn description(&self) -> &'static str {
+        "each simplified character is a simplified form of the traditional character at the same position"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                w.trad,
+                w.simp,
+                w.shared_id,
+                GROUP_CONCAT(t.ascii_symbol, ';') AS word_tags
+            FROM dict_word w
+            JOIN dict_shared s ON w.shared_id = s.id
+            LEFT JOIN dict_shared_tag st ON w.shared_id = st.for_shared_id
+            LEFT JOIN dict_tag t ON st.tag_id = t.id
+            GROUP BY w.id
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            let shared_id: SqliteId = row.get("shared_id")?;
+            let word_tags: Option<String> = row.get("word_tags")?;
+            // different lengths are reported by trad-simp-length
+            if is_exempt(word_tags.as_deref()) || trad.chars().count() != simp.chars().count() {
+                continue;
+            }
+            let mut messages = vec![];
+            for (trad_char, simp_char) in trad.chars().zip(simp.chars()) {
+                if self.table.is_traditional_only(simp_char) {
+                    messages.push(format!(
+                        "traditional character {simp_char} in the simplified word (simplified: {})",
+                        self.table.simplified[&simp_char].iter().join(" / ")
+                    ));
+                } else if (self.table.is_known(trad_char) || self.table.is_known(simp_char))
+                    && !self.table.is_mapping(trad_char, simp_char)
+                {
+                    messages.push(format!("{trad_char} is not simplified as {simp_char}"));
+                }
+            }
+            if !messages.is_empty() {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    messages.join(", "),
+                );
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// All words in file order with their characters, traditional first
+fn load_words(conn: &Connection) -> Result<Vec<(SqliteId, String, String)>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT w.shared_id, w.trad, w.simp
+        FROM dict_word w
+        JOIN dict_shared s ON w.shared_id = s.id
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect()
+}
+
+/// Characters of the traditional and simplified word, each only once
+fn word_chars<'a>(trad: &'a str, simp: &'a str) -> impl Iterator<Item = char> + 'a {
+    trad.chars().chain(simp.chars()).unique()
+}
+
+/// Characters besides Han characters which are common in words: Latin and Greek letters and digits (e.g.
+/// 卡拉OK, α粒子), spaces, punctuation and the middle dot of foreign names
+fn is_common_in_words(c: char) -> bool {
+    han::is_han(c)
+        || c.is_ascii_alphanumeric()
+        || c.is_ascii_punctuation()
+        || c == ' '
+        || ('α'..='ω').contains(&c)
+        || ('Α'..='Ω').contains(&c)
+        || ('\u{3000}'..='\u{303F}').contains(&c) // CJK symbols and punctuation
+        || ('\u{FF01}'..='\u{FF65}').contains(&c) // full-width forms
+        || ['·', '・'].contains(&c)
+}
+
+/// Characters of other scripts in a word, e.g. Japanese kana
+#[derive(Debug)]
+pub struct NonHanCharacter;
+
+impl Check for NonHanCharacter {
+    fn id(&self) -> &'static str {
+        "non-han-character"
+    }
+
+    fn description(&self) -> &'static str {
+        "words consist of Han characters, Latin letters, digits and punctuation (no characters of other scripts)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for (shared_id, trad, simp) in load_words(conn)? {
+            let messages: Vec<String> = word_chars(&trad, &simp)
+                .filter(|c| !is_common_in_words(*c))
+                .map(|c| format!("non-Han character {c} (U+{:04X})", u32::from(c)))
+                .collect();
+            if !messages.is_empty() {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    messages.join(", "),
+                );
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Compatibility ideographs look like unified ideographs, but aren't found when searching for the unified one
+#[derive(Debug)]
+pub struct CompatibilityIdeograph;
+
+impl Check for CompatibilityIdeograph {
+    fn id(&self) -> &'static str {
+        "compatibility-ideograph"
+    }
+
+    fn description(&self) -> &'static str {
+        "words use unified ideographs instead of the equivalent compatibility ideographs (e.g. U+8C48 instead of U+F900)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let compatibility_ideographs = han::compatibility_ideographs();
+        let mut findings = vec![];
+        for (shared_id, trad, simp) in load_words(conn)? {
+            let messages: Vec<String> = word_chars(&trad, &simp)
+                .filter_map(|c| {
+                    let unified = compatibility_ideographs.get(&c)?;
+                    Some(format!(
+                        "compatibility ideograph {c} (U+{:04X}) instead of {unified} (U+{:04X})",
+                        u32::from(c),
+                        u32::from(*unified)
+                    ))
+                })
+                .collect();
+            if !messages.is_empty() {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    messages.join(", "),
+                );
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Punctuation in Chinese words is full-width (e.g. ， instead of ,), letters and digits are half-width
+#[derive(Debug)]
+pub struct WordPunctuation;
+
+impl Check for WordPunctuation {
+    fn id(&self) -> &'static str {
+        "word-punctuation"
+    }
+
+    fn description(&self) -> &'static str {
+        "words with Han characters use full-width punctuation, letters and digits in words are half-width"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for (shared_id, trad, simp) in load_words(conn)? {
+            let has_han = trad.chars().any(han::is_han);
+            let messages: Vec<String> = word_chars(&trad, &simp)
+                .filter_map(|c| {
+                    if let Some(half_width) = han::half_width_alphanumeric(c) {
+                        Some(format!("full-width {c} instead of {half_width}"))
+                    } else if has_han && let Some(full_width) = han::full_width_punctuation(c) {
+                        Some(format!("half-width {c} instead of {full_width}"))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            if !messages.is_empty() {
+                let mut finding = self.finding(
+                    common::format_word_def(&trad, &simp, None),
+                    messages.join(", "),
+                );
+                finding.set_items(conn, &[shared_id])?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
@@ -0,0 +1,337 @@
+// Checks of the graph formed by the references: self-references, variant chains, cycles and the targets of
+// the reference types
+
+use std::collections::{BTreeMap, HashMap};
+
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError};
+
+use super::{Check, Finding, Severity, item_location};
+use crate::common;
+use crate::common::SqliteId;
+use crate::config::{self, RefTargetConstraint};
+
+/// Reference from a word or definition to itself, e.g. a synonym of itself
+#[derive(Debug)]
+pub struct SelfReference;
+
+impl Check for SelfReference {
+    fn id(&self) -> &'static str {
+        "self-reference"
+    }
+
+    fn description(&self) -> &'static str {
+        "references don't point to the word or definition they belong to"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT r.shared_id
+            FROM dict_reference r
+            JOIN dict_shared s ON r.shared_id = s.id
+            WHERE r.word_id_src = r.word_id_dst
+                AND (r.definition_id_src IS NULL
+                    OR r.definition_id_dst IS NULL
+                    OR r.definition_id_src = r.definition_id_dst)
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let shared_ids = stmt
+            .query_map([], |row| row.get::<_, SqliteId>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut findings = vec![];
+        for shared_id in shared_ids {
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                "reference to itself".to_owned(),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+}
+
+/// A variant should refer to the main form directly, not to another variant (A v→ B v→ C)
+#[derive(Debug)]
+pub struct VariantChain;
+
+impl Check for VariantChain {
+    fn id(&self) -> &'static str {
+        "variant-chain"
+    }
+
+    fn description(&self) -> &'static str {
+        "targets of variant-of references (V/v) are not variants themselves"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                r1.shared_id,
+                w.trad AS trad_dst,
+                w.simp AS simp_dst,
+                w2.trad AS trad_dst2,
+                w2.simp AS simp_dst2,
+                d2.ext_def_id AS ext_def_id_dst2
+            FROM dict_reference r1
+            JOIN dict_ref_type t1 ON r1.ref_type_id = t1.id
+            -- variant references of the target word, or of the target definition
+            JOIN dict_reference r2 ON r2.word_id_src = r1.word_id_dst
+                AND (r2.definition_id_src IS NULL
+                    OR r1.definition_id_dst IS NULL
+                    OR r2.definition_id_src = r1.definition_id_dst)
+            JOIN dict_ref_type t2 ON r2.ref_type_id = t2.id
+            JOIN dict_word w ON r1.word_id_dst = w.id
+            JOIN dict_word w2 ON r2.word_id_dst = w2.id
+            LEFT JOIN dict_definition d2 ON r2.definition_id_dst = d2.id
+            JOIN dict_shared s ON r1.shared_id = s.id
+            WHERE t1.ascii_symbol IN ('V', 'v') AND t2.ascii_symbol IN ('V', 'v')
+                -- cycles and self-references are reported by other checks
+                AND r2.word_id_dst != r1.word_id_src
+                AND r1.word_id_dst != r1.word_id_src
+                AND r2.word_id_dst != r2.word_id_src
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let shared_id: SqliteId = row.get("shared_id")?;
+            let variant = common::format_word_def(
+                &row.get::<_, String>("trad_dst")?,
+                &row.get::<_, String>("simp_dst")?,
+                None,
+            );
+            let main_form = common::format_word_def(
+                &row.get::<_, String>("trad_dst2")?,
+                &row.get::<_, String>("simp_dst2")?,
+                row.get("ext_def_id_dst2")?,
+            );
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                format!(
+                    "{variant} is itself a variant of {main_form}, refer to {main_form} instead"
+                ),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+}
+
+/// Directed cycles of the words connected by references of one non-symmetric type, e.g. A part-of B part-of A
+#[derive(Debug)]
+pub struct ReferenceCycle;
+
+/// Depth-first search which reports one cycle for each edge back to a word on the current path
+fn find_cycles(
+    graph: &BTreeMap<SqliteId, Vec<(SqliteId, SqliteId)>>,
+) -> Vec<Vec<(SqliteId, SqliteId)>> {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum State {
+        OnPath,
+        Done,
+    }
+    let mut states: HashMap<SqliteId, State> = HashMap::new();
+    let mut cycles = vec![];
+    for &start in graph.keys() {
+        if states.contains_key(&start) {
+            continue;
+        }
+        // current path of (word, index of the next edge to visit), the edge before that index leads to the
+        // next word on the path
+        let mut path: Vec<(SqliteId, usize)> = vec![(start, 0)];
+        states.insert(start, State::OnPath);
+        while let Some(top) = path.len().checked_sub(1) {
+            let (word, next_edge) = path[top];
+            let Some(&(dst, _)) = graph.get(&word).and_then(|edges| edges.get(next_edge)) else {
+                states.insert(word, State::Done);
+                path.pop();
+                continue;
+            };
+            path[top].1 += 1;
+            match states.get(&dst) {
+                Some(State::OnPath) => {
+                    let start_index = path.iter().position(|p| p.0 == dst).unwrap_or(top);
+                    let cycle = path[start_index..]
+                        .iter()
+                        .map(|&(word, next_edge)| (word, graph[&word][next_edge - 1].1))
+                        .collect();
+                    cycles.push(cycle);
+                }
+                Some(State::Done) => {}
+                None => {
+                    states.insert(dst, State::OnPath);
+                    path.push((dst, 0));
+                }
+            }
+        }
+    }
+    cycles
+}
+
+impl Check for ReferenceCycle {
+    fn id(&self) -> &'static str {
+        "reference-cycle"
+    }
+
+    fn description(&self) -> &'static str {
+        "references of non-symmetric types (e.g. part-of, variant-of) don't form cycles"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT rt.type, r.word_id_src, r.word_id_dst, r.shared_id, w.trad, w.simp
+            FROM dict_reference r
+            JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+            JOIN dict_word w ON r.word_id_src = w.id
+            JOIN dict_shared s ON r.shared_id = s.id
+            WHERE rt.is_symmetric = 0 AND r.word_id_src != r.word_id_dst
+            ORDER BY rt.type, s.rank, s.rank_relative;
+            ",
+        )?;
+        let mut graphs: BTreeMap<String, BTreeMap<SqliteId, Vec<(SqliteId, SqliteId)>>> =
+            BTreeMap::new();
+        let mut words: HashMap<SqliteId, String> = HashMap::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let word_id_src: SqliteId = row.get("word_id_src")?;
+            graphs
+                .entry(row.get("type")?)
+                .or_default()
+                .entry(word_id_src)
+                .or_default()
+                .push((row.get("word_id_dst")?, row.get("shared_id")?));
+            words.insert(
+                word_id_src,
+                common::format_word_def(
+                    &row.get::<_, String>("trad")?,
+                    &row.get::<_, String>("simp")?,
+                    None,
+                ),
+            );
+        }
+
+        let mut findings = vec![];
+        for (ref_type, graph) in &graphs {
+            for cycle in find_cycles(graph) {
+                let word_names = cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|(word_id, _)| words.get(word_id).map_or("", String::as_str))
+                    .join(" → ");
+                let shared_ids: Vec<SqliteId> = cycle.iter().map(|c| c.1).collect();
+                let mut finding = self.finding(
+                    words.get(&cycle[0].0).cloned().unwrap_or_default(),
+                    format!("cycle of {ref_type} references: {word_names}"),
+                );
+                finding.set_items(conn, &shared_ids)?;
+                findings.push(finding);
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// Targets of reference types with a constraint (see `config::get_ref_target_constraint`), e.g. the target of
+/// used-with-measure-word (M) must be a classifier
+#[derive(Debug)]
+pub struct ReferenceTarget;
+
+impl Check for ReferenceTarget {
+    fn id(&self) -> &'static str {
+        "reference-target"
+    }
+
+    fn description(&self) -> &'static str {
+        "targets of reference types with a constraint have the required class or tag, e.g. measure words (M)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                r.shared_id,
+                rt.ascii_symbol,
+                w.trad AS trad_dst,
+                w.simp AS simp_dst,
+                -- classes and tags of the target definition, or of all definitions of the target word
+                (SELECT GROUP_CONCAT(c.name, ';')
+                    FROM dict_definition d JOIN dict_class c ON d.class_id = c.id
+                    WHERE d.word_id = r.word_id_dst
+                        AND (r.definition_id_dst IS NULL OR d.id = r.definition_id_dst)) AS classes,
+                (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                    FROM dict_definition d
+                    JOIN dict_shared_tag st ON d.shared_id = st.for_shared_id
+                    JOIN dict_tag t ON st.tag_id = t.id
+                    WHERE d.word_id = r.word_id_dst
+                        AND (r.definition_id_dst IS NULL OR d.id = r.definition_id_dst)) AS tags
+            FROM dict_reference r
+            JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+            JOIN dict_word w ON r.word_id_dst = w.id
+            JOIN dict_shared s ON r.shared_id = s.id
+            ORDER BY s.rank, s.rank_relative;
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let ascii_symbol: String = row.get("ascii_symbol")?;
+            let Some(constraint) = ascii_symbol
+                .chars()
+                .next()
+                .and_then(config::get_ref_target_constraint)
+            else {
+                continue;
+            };
+            let classes: Option<String> = row.get("classes")?;
+            let tags: Option<String> = row.get("tags")?;
+            let (fulfilled, required) = match constraint {
+                RefTargetConstraint::Class(class) => (
+                    classes.is_some_and(|c| c.split(';').any(|c| c == class)),
+                    format!("class {class}"),
+                ),
+                RefTargetConstraint::Tag(tag) => {
+                    (tags.is_some_and(|t| t.contains(tag)), format!("tag {tag}"))
+                }
+            };
+            if fulfilled {
+                continue;
+            }
+            let shared_id: SqliteId = row.get("shared_id")?;
+            let target = common::format_word_def(
+                &row.get::<_, String>("trad_dst")?,
+                &row.get::<_, String>("simp_dst")?,
+                None,
+            );
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                format!("target {target} of the reference {ascii_symbol} has no definition with the {required}"),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+}
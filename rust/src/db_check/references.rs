@@ -0,0 +1,211 @@
+// Checks of the references between words and definitions
+
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError, Transaction};
+
+use super::tags::tag_rank;
+use super::{Check, Finding, Severity, item_location};
+use crate::common;
+use crate::common::SqliteId;
+use crate::db_edit;
+
+/// Pairs of tags with the same rank, which are only on one side each
+fn conflicting_tags(tags_a: &str, tags_b: &str) -> Vec<(char, char)> {
+    let only_a = tags_a.chars().filter(|t| !tags_b.contains(*t));
+    only_a
+        .cartesian_product(
+            tags_b
+                .chars()
+                .filter(|t| !tags_a.contains(*t))
+                .collect_vec(),
+        )
+        .filter(|(a, b)| tag_rank(*a).is_some() && tag_rank(*a) == tag_rank(*b))
+        .collect()
+}
+
+/// Symmetric references which are only written in one direction, the other direction is added before the DB
+/// is written anyway, so the check is only useful to see these changes with --fix
+#[derive(Debug)]
+pub struct SymmetricReferenceMissing;
+
+impl Check for SymmetricReferenceMissing {
+    fn id(&self) -> &'static str {
+        "symmetric-reference-missing"
+    }
+
+    fn description(&self) -> &'static str {
+        "symmetric references (e.g. synonyms) are written in both directions"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn is_fixable(&self) -> bool {
+        true
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT ref1.shared_id
+            FROM dict_reference AS ref1
+            JOIN dict_ref_type AS ref_type ON ref1.ref_type_id = ref_type.id
+            JOIN dict_shared AS s ON ref1.shared_id = s.id
+            WHERE ref_type.is_symmetric = 1
+                AND NOT EXISTS (
+                    SELECT 1 FROM dict_reference AS ref2
+                    WHERE ref1.word_id_src = ref2.word_id_dst
+                        AND ref1.word_id_dst = ref2.word_id_src
+                        AND ref1.ref_type_id = ref2.ref_type_id
+                        AND (ref1.definition_id_src = ref2.definition_id_dst
+                            OR (ref1.definition_id_src IS NULL AND ref2.definition_id_dst IS NULL))
+                        AND (ref1.definition_id_dst = ref2.definition_id_src
+                            OR (ref1.definition_id_dst IS NULL AND ref2.definition_id_src IS NULL)))
+            ORDER BY s.rank, s.rank_relative
+            ",
+        )?;
+        let shared_ids = stmt
+            .query_map([], |row| row.get::<_, SqliteId>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut findings = vec![];
+        for shared_id in shared_ids {
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                "symmetric reference without the other direction".to_owned(),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    /// Adds the other direction of all symmetric references, with the notes and tags of the existing one
+    fn fix(&self, tx: &Transaction, _findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        let added = db_edit::add_missing_symmetric_references(tx)?;
+        db_edit::add_missing_notes_and_tags_for_symmetric_references(tx)?;
+        added
+            .into_iter()
+            .map(|shared_id| Ok(format!("{}: added", item_location(tx, shared_id)?)))
+            .collect()
+    }
+}
+
+/// Both directions of a symmetric reference are merged before the DB is written (notes and tags are copied
+/// to the other side), which is only possible if they don't contradict each other
+#[derive(Debug)]
+pub struct SymmetricReferenceConflict;
+
+impl Check for SymmetricReferenceConflict {
+    fn id(&self) -> &'static str {
+        "symmetric-reference-conflict"
+    }
+
+    fn description(&self) -> &'static str {
+        "both directions of a symmetric reference don't have different notes or tags of the same rank"
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut stmt = conn.prepare(
+            r"
+            SELECT
+                word_a.trad AS word_a_trad,
+                word_a.simp AS word_a_simp,
+                def_a.ext_def_id AS word_a_ext_def_id, -- NULL if the reference is not from a specific definition
+                word_b.trad AS word_b_trad,
+                word_b.simp AS word_b_simp,
+                def_b.ext_def_id AS word_b_ext_def_id, -- NULL if the reference is not to a specific definition
+                ref1.shared_id AS shared_id_a,
+                ref2.shared_id AS shared_id_b,
+                note_a.ext_note_id AS note_a,
+                note_b.ext_note_id AS note_b,
+                (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                    FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                    WHERE st.for_shared_id = ref1.shared_id) AS tags_a,
+                (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                    FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                    WHERE st.for_shared_id = ref2.shared_id) AS tags_b
+            FROM dict_reference AS ref1
+            JOIN dict_ref_type AS ref_type ON ref1.ref_type_id = ref_type.id
+            -- This self-join finds the symmetric pair
+            JOIN dict_reference AS ref2 ON ref1.word_id_src = ref2.word_id_dst
+                AND ref1.word_id_dst = ref2.word_id_src
+                AND ref1.ref_type_id = ref2.ref_type_id
+                AND (ref1.definition_id_src = ref2.definition_id_dst
+                    OR (ref1.definition_id_src IS NULL AND ref2.definition_id_dst IS NULL))
+                AND (ref1.definition_id_dst = ref2.definition_id_src
+                    OR (ref1.definition_id_dst IS NULL AND ref2.definition_id_src IS NULL))
+            JOIN dict_shared AS shared_a ON ref1.shared_id = shared_a.id
+            JOIN dict_shared AS shared_b ON ref2.shared_id = shared_b.id
+            LEFT JOIN dict_note AS note_a ON shared_a.note_id = note_a.id
+            LEFT JOIN dict_note AS note_b ON shared_b.note_id = note_b.id
+            JOIN dict_word AS word_a ON ref1.word_id_src = word_a.id
+            JOIN dict_word AS word_b ON ref1.word_id_dst = word_b.id
+            LEFT JOIN dict_definition AS def_a ON ref1.definition_id_src = def_a.id
+            LEFT JOIN dict_definition AS def_b ON ref1.definition_id_dst = def_b.id
+            WHERE
+                ref_type.is_symmetric = 1
+                -- This condition ensures we process each pair only once
+                AND ref1.id < ref2.id
+            ORDER BY shared_a.rank, shared_a.rank_relative
+            ",
+        )?;
+        let mut findings = vec![];
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let note_a: Option<u32> = row.get("note_a")?;
+            let note_b: Option<u32> = row.get("note_b")?;
+            let tags_a: Option<String> = row.get("tags_a")?;
+            let tags_b: Option<String> = row.get("tags_b")?;
+
+            let mut messages = vec![];
+            if let (Some(note_a), Some(note_b)) = (note_a, note_b)
+                && note_a != note_b
+            {
+                messages.push(format!("different notes N{note_a} and N{note_b}"));
+            }
+            let tag_conflicts = conflicting_tags(
+                tags_a.as_deref().unwrap_or_default(),
+                tags_b.as_deref().unwrap_or_default(),
+            );
+            if !tag_conflicts.is_empty() {
+                messages.push(format!(
+                    "conflicting tags {}",
+                    tag_conflicts
+                        .iter()
+                        .map(|(a, b)| format!("{a} and {b}"))
+                        .join(", ")
+                ));
+            }
+            if messages.is_empty() {
+                continue;
+            }
+
+            let word_a = common::format_word_def(
+                &row.get::<_, String>("word_a_trad")?,
+                &row.get::<_, String>("word_a_simp")?,
+                row.get("word_a_ext_def_id")?,
+            );
+            let word_b = common::format_word_def(
+                &row.get::<_, String>("word_b_trad")?,
+                &row.get::<_, String>("word_b_simp")?,
+                row.get("word_b_ext_def_id")?,
+            );
+            let shared_ids: [SqliteId; 2] = [row.get("shared_id_a")?, row.get("shared_id_b")?];
+            let mut finding = self.finding(
+                word_a,
+                format!(
+                    "{} on the symmetric references with {word_b}",
+                    messages.join(" and ")
+                ),
+            );
+            finding.set_items(conn, &shared_ids)?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+}
@@ -0,0 +1,259 @@
+// Check that the external ids of a previous release still point to the same meaning, the ids are used for
+// links from outside of the dictionary
+
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError, OptionalExtension};
+
+use super::definitions::normalize_definition;
+use super::{Check, Finding};
+use crate::common;
+use crate::common::{MAX_TEMPORARY_NOTE_ID, SqliteId};
+use crate::db_model::{self, DefinitionEntry, NoteInfo, WordEntry};
+
+/// Entries of a previous release to compare the ids with
+#[derive(Debug, Clone)]
+pub struct StableIdsReference {
+    pub entries: Vec<WordEntry>,
+    /// Minimum similarity (0.0 to 1.0) of the texts of definitions and notes with the same id
+    pub min_similarity: f64,
+}
+
+/// All notes by `ext_note_id` with the words they are attached to
+fn collect_notes(entries: &[WordEntry]) -> HashMap<u32, (&NoteInfo, HashSet<String>)> {
+    let mut notes: HashMap<u32, (&NoteInfo, HashSet<String>)> = HashMap::new();
+    for word in entries {
+        let word_str = common::format_word_def(&word.trad, &word.simp, None);
+        let word_notes = word
+            .note
+            .iter()
+            .chain(word.references.iter().filter_map(|r| r.note.as_ref()));
+        let def_notes = word.definitions.iter().flat_map(|d| {
+            d.note
+                .iter()
+                .chain(d.prons.iter().filter_map(|p| p.note.as_ref()))
+                .chain(d.references.iter().filter_map(|r| r.note.as_ref()))
+        });
+        for note in word_notes.chain(def_notes) {
+            notes
+                .entry(note.ext_note_id)
+                .or_insert_with(|| (note, HashSet::new()))
+                .1
+                .insert(word_str.clone());
+        }
+    }
+    notes
+}
+
+/// Item of a definition in the DB, or of the word if the definition is not set
+fn definition_item(
+    conn: &Connection,
+    trad: &str,
+    simp: &str,
+    ext_def_id: Option<u32>,
+) -> Result<Option<SqliteId>, SqliteError> {
+    conn.prepare_cached(
+        r"
+        SELECT coalesce(d.shared_id, w.shared_id)
+        FROM dict_word w
+        LEFT JOIN dict_definition d ON d.word_id = w.id AND d.ext_def_id = ?3
+        WHERE w.trad = ?1 AND w.simp = ?2
+        ",
+    )?
+    .query_row((trad, simp, ext_def_id), |row| row.get(0))
+    .optional()
+}
+
+/// Items in the DB with a note
+fn note_items(conn: &Connection, ext_note_id: u32) -> Result<Vec<SqliteId>, SqliteError> {
+    conn.prepare_cached(
+        r"
+        SELECT s.id
+        FROM dict_shared s JOIN dict_note n ON s.note_id = n.id
+        WHERE n.ext_note_id = ?1
+        ORDER BY s.rank, s.rank_relative
+        ",
+    )?
+    .query_map([ext_note_id], |row| row.get(0))?
+    .collect()
+}
+
+/// Findings of `StableIds` for the notes
+fn note_findings(
+    check: &dyn Check,
+    conn: &Connection,
+    reference: &StableIdsReference,
+    current: &[WordEntry],
+) -> Result<Vec<Finding>, SqliteError> {
+    let mut findings = vec![];
+    let current_notes = collect_notes(current);
+    for (ext_note_id, (note, words)) in collect_notes(&reference.entries)
+        .into_iter()
+        .sorted_by_key(|(id, _)| *id)
+    {
+        if ext_note_id <= MAX_TEMPORARY_NOTE_ID {
+            continue;
+        }
+        let location = format!("note N{ext_note_id}");
+        let Some((current_note, current_words)) = current_notes.get(&ext_note_id) else {
+            findings.push(check.finding(location, "disappeared".to_owned()));
+            continue;
+        };
+        let items = note_items(conn, ext_note_id)?;
+        if common::similarity(&note.note, &current_note.note) < reference.min_similarity {
+            let mut finding = check.finding(
+                location.clone(),
+                format!(
+                    "changed its meaning: \"{}\" -> \"{}\"",
+                    note.note, current_note.note
+                ),
+            );
+            finding.set_items(conn, &items)?;
+            findings.push(finding);
+        }
+        if words.is_disjoint(current_words) {
+            let mut finding = check.finding(
+                location,
+                format!(
+                    "moved to a different word: {}",
+                    current_words.iter().sorted().join(", ")
+                ),
+            );
+            finding.set_items(conn, &items)?;
+            findings.push(finding);
+        }
+    }
+    Ok(findings)
+}
+
+/// Findings of `StableIds` for the entries in the DB
+fn stable_id_findings(
+    check: &dyn Check,
+    conn: &Connection,
+    reference: &StableIdsReference,
+) -> Result<Vec<Finding>, SqliteError> {
+    let min_similarity = reference.min_similarity;
+    let current = db_model::load_word_entries(conn)?;
+    let redirects = db_model::load_redirects(conn)?;
+    let mut findings = vec![];
+    let redirected: HashSet<(&str, &str, u32)> = redirects
+        .iter()
+        .map(|r| (r.trad.as_str(), r.simp.as_str(), r.ext_def_id))
+        .collect();
+    let current_words: HashMap<(&str, &str), &WordEntry> = current
+        .iter()
+        .map(|w| ((w.trad.as_str(), w.simp.as_str()), w))
+        .collect();
+    // ids are only unique within a word, so moved definitions are found by their text
+    let mut by_text: HashMap<String, Vec<(&WordEntry, &DefinitionEntry)>> = HashMap::new();
+    for w in &current {
+        for d in &w.definitions {
+            let normalized = normalize_definition(&w.full_definition(d));
+            if !normalized.is_empty() {
+                by_text.entry(normalized).or_default().push((w, d));
+            }
+        }
+    }
+
+    for word in &reference.entries {
+        let current_word = current_words.get(&(word.trad.as_str(), word.simp.as_str()));
+        for def in &word.definitions {
+            let def_str = common::format_word_def(&word.trad, &word.simp, Some(def.ext_def_id));
+            let current_def = current_word.and_then(|w| {
+                w.definitions
+                    .iter()
+                    .find(|d| d.ext_def_id == def.ext_def_id)
+            });
+            // sub-definitions are compared with the texts of their parents, so that splitting a definition into
+            // sub-definitions keeps the meaning
+            let full_definition = word.full_definition(def);
+            let (message, (trad, simp, ext_def_id)) = if let Some((current_word, current_def)) =
+                current_word.zip(current_def)
+            {
+                let current_full_definition = current_word.full_definition(current_def);
+                if common::similarity(&full_definition, &current_full_definition) >= min_similarity
+                {
+                    continue;
+                }
+                (
+                    format!(
+                        "changed its meaning: \"{full_definition}\" -> \"{current_full_definition}\""
+                    ),
+                    (&word.trad, &word.simp, Some(def.ext_def_id)),
+                )
+            } else if redirected.contains(&(word.trad.as_str(), word.simp.as_str(), def.ext_def_id))
+            {
+                continue;
+            } else if let Some((w, d)) = by_text
+                .get(normalize_definition(&full_definition).as_str())
+                .and_then(|defs| {
+                    // the definition might have moved together with its text, e.g. if the characters of the
+                    // word changed
+                    defs.iter()
+                        .find(|(w, _)| w.trad != word.trad || w.simp != word.simp)
+                })
+            {
+                (
+                    format!(
+                        "moved to a different word: {}",
+                        common::format_word_def(&w.trad, &w.simp, Some(d.ext_def_id))
+                    ),
+                    (&w.trad, &w.simp, Some(d.ext_def_id)),
+                )
+            } else {
+                (
+                    "disappeared, keep it with the deleted tag (X) or add a redirect (R) instead"
+                        .to_owned(),
+                    (&word.trad, &word.simp, None),
+                )
+            };
+            let mut finding = check.finding(def_str, message);
+            let items: Vec<SqliteId> = definition_item(conn, trad, simp, ext_def_id)?
+                .into_iter()
+                .collect();
+            finding.set_items(conn, &items)?;
+            findings.push(finding);
+        }
+    }
+
+    findings.extend(note_findings(check, conn, reference, &current)?);
+    Ok(findings)
+}
+
+/// Definition and note ids of a previous release which don't point to the same meaning anymore
+///
+/// Definitions must not disappear, a definition which is not valid anymore has to be kept with the deleted tag
+/// (X) as tombstone or be replaced by a redirect (R) of the word. The text of a definition or note with the same
+/// id must be similar (`min_similarity`) and notes must stay attached to at least one of their words.
+#[derive(Debug, Default)]
+pub struct StableIds {
+    // the check finds nothing without a previous release
+    reference: Option<StableIdsReference>,
+}
+
+impl StableIds {
+    #[must_use]
+    pub fn new(reference: StableIdsReference) -> Self {
+        Self {
+            reference: Some(reference),
+        }
+    }
+}
+
+impl Check for StableIds {
+    fn id(&self) -> &'static str {
+        "stable-ids"
+    }
+
+    fn description(&self) -> &'static str {
+        "definition and note ids point to the same meaning as in the previous release (--stable-ids)"
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        match &self.reference {
+            Some(reference) => stable_id_findings(self, conn, reference),
+            None => Ok(vec![]),
+        }
+    }
+}
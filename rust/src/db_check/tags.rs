@@ -0,0 +1,220 @@
+// Checks of the tags of words, definitions, pronunciations and references
+
+use itertools::Itertools;
+use rusqlite::{Connection, Error as SqliteError, Transaction};
+
+use super::{Check, Finding, Severity, item_location};
+use crate::common::SqliteId;
+use crate::config;
+
+/// Rank of an ascii tag, tags with the same rank exclude each other
+pub(super) fn tag_rank(ascii_tag: char) -> Option<u8> {
+    config::tag_to_txt_ascii_common(ascii_tag).map(|t| t.2)
+}
+
+/// Tags with the same rank on the same item, in file order
+#[derive(Debug)]
+struct RankConflict {
+    shared_id: SqliteId,
+    category: &'static str,
+    tags: Vec<(SqliteId, char)>,
+}
+
+/// All items with several tags of the same rank, the tags are in the order of the text file
+fn rank_conflicts(conn: &Connection) -> Result<Vec<RankConflict>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT st.for_shared_id, t.id, t.ascii_symbol
+        FROM dict_shared_tag st
+        JOIN dict_tag t ON st.tag_id = t.id
+        JOIN dict_shared s ON st.for_shared_id = s.id
+        WHERE t.ascii_symbol IS NOT NULL
+        ORDER BY s.rank, s.rank_relative, st.rowid
+        ",
+    )?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, SqliteId>(0)?,
+                row.get::<_, SqliteId>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut conflicts = vec![];
+    for (shared_id, item_tags) in &tags.iter().chunk_by(|t| t.0) {
+        let item_tags: Vec<(SqliteId, char)> = item_tags
+            .filter_map(|(_, tag_id, ascii)| ascii.chars().next().map(|c| (*tag_id, c)))
+            .collect();
+        for (i, (_, ascii_tag)) in item_tags.iter().enumerate() {
+            let Some((_, category, rank)) = config::tag_to_txt_ascii_common(*ascii_tag) else {
+                continue;
+            };
+            // report each rank only once, at its first tag
+            if item_tags[..i].iter().any(|t| tag_rank(t.1) == Some(rank)) {
+                continue;
+            }
+            let same_rank: Vec<(SqliteId, char)> = item_tags[i..]
+                .iter()
+                .filter(|t| tag_rank(t.1) == Some(rank))
+                .copied()
+                .collect();
+            if same_rank.len() > 1 {
+                conflicts.push(RankConflict {
+                    shared_id,
+                    category,
+                    tags: same_rank,
+                });
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+/// The tags of one rank exclude each other, e.g. taiwan-only (T) and china-only (C)
+#[derive(Debug)]
+pub struct TagRankConflict;
+
+impl Check for TagRankConflict {
+    fn id(&self) -> &'static str {
+        "tag-rank-conflict"
+    }
+
+    fn description(&self) -> &'static str {
+        "an item has at most one tag of each rank (see tag_to_txt_ascii_common)"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn is_fixable(&self) -> bool {
+        true
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for conflict in rank_conflicts(conn)? {
+            let mut finding = self.finding(
+                item_location(conn, conflict.shared_id)?,
+                format!(
+                    "several tags of the same rank ({}): {}",
+                    conflict.category,
+                    conflict.tags.iter().map(|t| t.1).join(", ")
+                ),
+            );
+            finding.set_items(conn, &[conflict.shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    /// The first tag in file order is kept, the others are removed
+    fn fix(&self, tx: &Transaction, findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        let mut stmt_delete =
+            tx.prepare("DELETE FROM dict_shared_tag WHERE for_shared_id = ?1 AND tag_id = ?2")?;
+        let mut changes = vec![];
+        for conflict in rank_conflicts(tx)? {
+            if !findings
+                .iter()
+                .any(|f| f.shared_ids == [conflict.shared_id])
+            {
+                continue;
+            }
+            let Some(((_, kept), removed)) = conflict.tags.split_first() else {
+                continue;
+            };
+            for (tag_id, _) in removed {
+                stmt_delete.execute((conflict.shared_id, tag_id))?;
+            }
+            changes.push(format!(
+                "{}: removed {} {}, kept {kept}",
+                item_location(tx, conflict.shared_id)?,
+                if removed.len() > 1 { "tags" } else { "tag" },
+                removed.iter().map(|t| t.1).join(", ")
+            ));
+        }
+        Ok(changes)
+    }
+}
+
+/// The same tag written twice for an item, which is only kept once in the DB (see `txt_to_db`)
+#[derive(Debug)]
+pub struct DuplicateTag;
+
+/// Tags which were written more than once per item, if the DB was created from a text file
+fn duplicate_tags(conn: &Connection) -> Result<Vec<(SqliteId, String)>, SqliteError> {
+    let has_duplicates: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM temp.sqlite_master WHERE name = 'dict_duplicate_tag')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_duplicates {
+        return Ok(vec![]);
+    }
+    let mut stmt = conn.prepare(
+        r"
+        SELECT dt.shared_id, COALESCE(t.ascii_symbol, '#' || t.tag)
+        FROM temp.dict_duplicate_tag dt
+        JOIN dict_tag t ON dt.tag_id = t.id
+        JOIN dict_shared s ON dt.shared_id = s.id
+        ORDER BY s.rank, s.rank_relative, dt.rowid
+        ",
+    )?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+impl Check for DuplicateTag {
+    fn id(&self) -> &'static str {
+        "duplicate-tag"
+    }
+
+    fn description(&self) -> &'static str {
+        "an item has each tag only once"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn is_fixable(&self) -> bool {
+        true
+    }
+
+    fn run(&self, conn: &Connection) -> Result<Vec<Finding>, SqliteError> {
+        let mut findings = vec![];
+        for (shared_id, tag) in duplicate_tags(conn)? {
+            let mut finding = self.finding(
+                item_location(conn, shared_id)?,
+                format!("duplicate tag {tag}"),
+            );
+            finding.set_items(conn, &[shared_id])?;
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    /// The DB has each tag only once anyway, the fix just drops the record of the duplicates
+    fn fix(&self, tx: &Transaction, findings: &[Finding]) -> Result<Vec<String>, SqliteError> {
+        let mut changes = vec![];
+        for (shared_id, tag) in duplicate_tags(tx)? {
+            if findings.iter().any(|f| f.shared_ids == [shared_id]) {
+                changes.push(format!(
+                    "{}: removed duplicate tag {tag}",
+                    item_location(tx, shared_id)?
+                ));
+            }
+        }
+        for finding in findings {
+            for shared_id in &finding.shared_ids {
+                tx.execute(
+                    "DELETE FROM temp.dict_duplicate_tag WHERE shared_id = ?1",
+                    [shared_id],
+                )?;
+            }
+        }
+        Ok(changes)
+    }
+}
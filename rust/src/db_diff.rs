@@ -0,0 +1,457 @@
+// Semantic diff between two versions of the dictionary
+//
+// Entities are compared by their keys instead of their position in the text file: words by trad/simp,
+// definitions by ext_def_id (per word), pronunciations by pinyin (per definition), references by their
+// endpoints and type, notes by ext_note_id (temporary ids of new notes by their text). Ranks are ignored, so
+// moving an entry is not a change.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::common::{MAX_TEMPORARY_NOTE_ID, format_word_def};
+use crate::db_model::{DefinitionEntry, NoteInfo, ReferenceEntry, TagInfo, WordEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Entity {
+    Word,
+    Definition,
+    Pronunciation,
+    Tag,
+    Reference,
+    Note,
+}
+
+/// A single change, located by the word and optionally the definition, pronunciation or reference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub entity: Entity,
+    pub trad: String,
+    pub simp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext_def_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinyin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// Identifies the changed item: tag name, pinyin, reference, note id or the changed field
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self.kind {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+        };
+        let entity = match self.entity {
+            Entity::Word => "word",
+            Entity::Definition => "definition",
+            Entity::Pronunciation => "pronunciation",
+            Entity::Tag => "tag",
+            Entity::Reference => "reference",
+            Entity::Note => "note",
+        };
+        write!(
+            f,
+            "{symbol} {entity} {}",
+            format_word_def(&self.trad, &self.simp, self.ext_def_id)
+        )?;
+        if let Some(pinyin) = &self.pinyin {
+            write!(f, " {pinyin}")?;
+        }
+        if let Some(reference) = &self.reference {
+            write!(f, " [{reference}]")?;
+        }
+        // words and whole definitions are already identified by the location
+        if self.entity != Entity::Word
+            && !(self.entity == Entity::Definition && self.kind != ChangeKind::Changed)
+        {
+            write!(f, ": {}", self.key)?;
+        }
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, ": {old:?} -> {new:?}"),
+            (Some(value), None) | (None, Some(value)) => write!(f, ": {value:?}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Location of the compared items, used to fill in the context of a change
+#[derive(Clone, Default)]
+struct Location<'a> {
+    trad: &'a str,
+    simp: &'a str,
+    ext_def_id: Option<u32>,
+    pinyin: Option<&'a str>,
+    reference: Option<String>,
+}
+
+impl Location<'_> {
+    fn change(
+        &self,
+        kind: ChangeKind,
+        entity: Entity,
+        key: String,
+        old: Option<String>,
+        new: Option<String>,
+    ) -> Change {
+        Change {
+            kind,
+            entity,
+            trad: self.trad.to_owned(),
+            simp: self.simp.to_owned(),
+            ext_def_id: self.ext_def_id,
+            pinyin: self.pinyin.map(str::to_owned),
+            reference: self.reference.clone(),
+            key,
+            old,
+            new,
+        }
+    }
+}
+
+/// Description of a reference as it is used as key, e.g. `synonym-equal 你好#D3`
+#[must_use]
+pub fn reference_key(reference: &ReferenceEntry) -> String {
+    format!(
+        "{} {}",
+        reference.ref_type,
+        format_word_def(
+            &reference.dst_trad,
+            &reference.dst_simp,
+            reference.dst_ext_def_id
+        )
+    )
+}
+
+fn diff_tags(changes: &mut Vec<Change>, loc: &Location, old: &[TagInfo], new: &[TagInfo]) {
+    for tag in old {
+        if !new.iter().any(|t| t.tag == tag.tag) {
+            changes.push(loc.change(
+                ChangeKind::Removed,
+                Entity::Tag,
+                tag.tag.clone(),
+                None,
+                None,
+            ));
+        }
+    }
+    for tag in new {
+        if !old.iter().any(|t| t.tag == tag.tag) {
+            changes.push(loc.change(ChangeKind::Added, Entity::Tag, tag.tag.clone(), None, None));
+        }
+    }
+}
+
+fn diff_references(
+    changes: &mut Vec<Change>,
+    loc: &Location,
+    old: &[ReferenceEntry],
+    new: &[ReferenceEntry],
+) {
+    let old_by_key: HashMap<_, _> = old.iter().map(|r| (reference_key(r), r)).collect();
+    let new_by_key: HashMap<_, _> = new.iter().map(|r| (reference_key(r), r)).collect();
+    for r in old {
+        let key = reference_key(r);
+        if let Some(r_new) = new_by_key.get(&key) {
+            let ref_loc = Location {
+                reference: Some(key),
+                ..loc.clone()
+            };
+            diff_tags(changes, &ref_loc, &r.tags, &r_new.tags);
+        } else {
+            changes.push(loc.change(ChangeKind::Removed, Entity::Reference, key, None, None));
+        }
+    }
+    for r in new {
+        let key = reference_key(r);
+        if !old_by_key.contains_key(&key) {
+            changes.push(loc.change(ChangeKind::Added, Entity::Reference, key, None, None));
+        }
+    }
+}
+
+fn diff_definition(
+    changes: &mut Vec<Change>,
+    loc: &Location,
+    old: &DefinitionEntry,
+    new: &DefinitionEntry,
+) {
+    if old.definition != new.definition {
+        changes.push(loc.change(
+            ChangeKind::Changed,
+            Entity::Definition,
+            "definition".to_owned(),
+            Some(old.definition.clone()),
+            Some(new.definition.clone()),
+        ));
+    }
+    if old.parent_ext_def_id != new.parent_ext_def_id {
+        let parent = |id: Option<u32>| id.map(|id| format!("D{id}"));
+        changes.push(loc.change(
+            ChangeKind::Changed,
+            Entity::Definition,
+            "parent".to_owned(),
+            parent(old.parent_ext_def_id),
+            parent(new.parent_ext_def_id),
+        ));
+    }
+    if old.class_name != new.class_name {
+        changes.push(loc.change(
+            ChangeKind::Changed,
+            Entity::Definition,
+            "class".to_owned(),
+            Some(old.class_name.clone()),
+            Some(new.class_name.clone()),
+        ));
+    }
+    for pron in &old.prons {
+        if let Some(pron_new) = new.prons.iter().find(|p| p.pinyin_num == pron.pinyin_num) {
+            let pron_loc = Location {
+                pinyin: Some(&pron.pinyin_num),
+                ..loc.clone()
+            };
+            diff_tags(changes, &pron_loc, &pron.tags, &pron_new.tags);
+        } else {
+            changes.push(loc.change(
+                ChangeKind::Removed,
+                Entity::Pronunciation,
+                pron.pinyin_num.clone(),
+                None,
+                None,
+            ));
+        }
+    }
+    for pron in &new.prons {
+        if !old.prons.iter().any(|p| p.pinyin_num == pron.pinyin_num) {
+            changes.push(loc.change(
+                ChangeKind::Added,
+                Entity::Pronunciation,
+                pron.pinyin_num.clone(),
+                None,
+                None,
+            ));
+        }
+    }
+    diff_tags(changes, loc, &old.tags, &new.tags);
+    diff_references(changes, loc, &old.references, &new.references);
+}
+
+fn diff_word(changes: &mut Vec<Change>, old: &WordEntry, new: &WordEntry) {
+    let loc = Location {
+        trad: &old.trad,
+        simp: &old.simp,
+        ..Location::default()
+    };
+    diff_tags(changes, &loc, &old.tags, &new.tags);
+    diff_references(changes, &loc, &old.references, &new.references);
+    for def in &old.definitions {
+        let def_loc = Location {
+            ext_def_id: Some(def.ext_def_id),
+            ..loc.clone()
+        };
+        if let Some(def_new) = new
+            .definitions
+            .iter()
+            .find(|d| d.ext_def_id == def.ext_def_id)
+        {
+            diff_definition(changes, &def_loc, def, def_new);
+        } else {
+            changes.push(def_loc.change(
+                ChangeKind::Removed,
+                Entity::Definition,
+                format!("D{}", def.ext_def_id),
+                Some(def.definition.clone()),
+                None,
+            ));
+        }
+    }
+    for def in &new.definitions {
+        if !old
+            .definitions
+            .iter()
+            .any(|d| d.ext_def_id == def.ext_def_id)
+        {
+            let def_loc = Location {
+                ext_def_id: Some(def.ext_def_id),
+                ..loc.clone()
+            };
+            changes.push(def_loc.change(
+                ChangeKind::Added,
+                Entity::Definition,
+                format!("D{}", def.ext_def_id),
+                None,
+                Some(def.definition.clone()),
+            ));
+        }
+    }
+}
+
+/// Notes with the location of the first item they are attached to
+type NoteLocations<'a> = BTreeMap<u32, (&'a NoteInfo, Location<'a>)>;
+
+/// All notes by `ext_note_id`, notes with a temporary id separately
+fn collect_notes<'a>(entries: &'a [WordEntry]) -> (NoteLocations<'a>, NoteLocations<'a>) {
+    let mut notes = BTreeMap::new();
+    let mut temporary_notes = BTreeMap::new();
+    let mut add = |note: Option<&'a NoteInfo>, loc: Location<'a>| {
+        if let Some(n) = note {
+            if n.ext_note_id <= MAX_TEMPORARY_NOTE_ID {
+                temporary_notes.entry(n.ext_note_id).or_insert((n, loc));
+            } else {
+                notes.entry(n.ext_note_id).or_insert((n, loc));
+            }
+        }
+    };
+    for word in entries {
+        let loc = Location {
+            trad: &word.trad,
+            simp: &word.simp,
+            ..Location::default()
+        };
+        add(word.note.as_ref(), loc.clone());
+        for r in &word.references {
+            add(r.note.as_ref(), loc.clone());
+        }
+        for def in &word.definitions {
+            let def_loc = Location {
+                ext_def_id: Some(def.ext_def_id),
+                ..loc.clone()
+            };
+            add(def.note.as_ref(), def_loc.clone());
+            for pron in &def.prons {
+                add(pron.note.as_ref(), def_loc.clone());
+            }
+            for r in &def.references {
+                add(r.note.as_ref(), def_loc.clone());
+            }
+        }
+    }
+    (notes, temporary_notes)
+}
+
+fn diff_notes(changes: &mut Vec<Change>, old: &[WordEntry], new: &[WordEntry]) {
+    let (old_notes, old_temporary) = collect_notes(old);
+    let (new_notes, new_temporary) = collect_notes(new);
+    for (id, (note, loc)) in &old_notes {
+        match new_notes.get(id) {
+            Some((note_new, _)) if note_new.note != note.note => changes.push(loc.change(
+                ChangeKind::Changed,
+                Entity::Note,
+                format!("N{id}"),
+                Some(note.note.clone()),
+                Some(note_new.note.clone()),
+            )),
+            Some(_) => {}
+            None => changes.push(loc.change(
+                ChangeKind::Removed,
+                Entity::Note,
+                format!("N{id}"),
+                Some(note.note.clone()),
+                None,
+            )),
+        }
+    }
+    for (id, (note, loc)) in &new_notes {
+        if !old_notes.contains_key(id) {
+            changes.push(loc.change(
+                ChangeKind::Added,
+                Entity::Note,
+                format!("N{id}"),
+                None,
+                Some(note.note.clone()),
+            ));
+        }
+    }
+    // temporary ids are assigned again on every import, so these notes are only matched by their text
+    let has_text = |notes: &NoteLocations, text: &str| notes.values().any(|(n, _)| n.note == text);
+    for (id, (note, loc)) in &old_temporary {
+        if !has_text(&new_temporary, &note.note) {
+            changes.push(loc.change(
+                ChangeKind::Removed,
+                Entity::Note,
+                format!("N{id}"),
+                Some(note.note.clone()),
+                None,
+            ));
+        }
+    }
+    for (id, (note, loc)) in &new_temporary {
+        if !has_text(&old_temporary, &note.note) {
+            changes.push(loc.change(
+                ChangeKind::Added,
+                Entity::Note,
+                format!("N{id}"),
+                None,
+                Some(note.note.clone()),
+            ));
+        }
+    }
+}
+
+/// Compare two versions of the dictionary
+///
+/// Changes are listed per word in the order of the new version, followed by the removed words and the
+/// note changes. Added or removed words are reported as a whole, without their definitions.
+#[must_use]
+pub fn diff_entries(old: &[WordEntry], new: &[WordEntry]) -> Vec<Change> {
+    let word_key = |w: &WordEntry| (w.trad.clone(), w.simp.clone());
+    let old_by_key: HashMap<_, _> = old.iter().map(|w| (word_key(w), w)).collect();
+    let new_by_key: HashMap<_, _> = new.iter().map(|w| (word_key(w), w)).collect();
+    let mut changes = vec![];
+
+    for word in new {
+        if let Some(word_old) = old_by_key.get(&word_key(word)) {
+            diff_word(&mut changes, word_old, word);
+        } else {
+            let loc = Location {
+                trad: &word.trad,
+                simp: &word.simp,
+                ..Location::default()
+            };
+            changes.push(loc.change(
+                ChangeKind::Added,
+                Entity::Word,
+                format_word_def(&word.trad, &word.simp, None),
+                None,
+                Some(word.definitions.iter().map(|d| &d.definition).join("; ")),
+            ));
+        }
+    }
+    for word in old {
+        if !new_by_key.contains_key(&word_key(word)) {
+            let loc = Location {
+                trad: &word.trad,
+                simp: &word.simp,
+                ..Location::default()
+            };
+            changes.push(loc.change(
+                ChangeKind::Removed,
+                Entity::Word,
+                format_word_def(&word.trad, &word.simp, None),
+                Some(word.definitions.iter().map(|d| &d.definition).join("; ")),
+                None,
+            ));
+        }
+    }
+    diff_notes(&mut changes, old, new);
+    changes
+}
@@ -1,8 +1,18 @@
-use std::cmp::max;
+use std::cmp::{Reverse, max};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead};
+use std::mem;
+use std::ops::Range;
 
+use itertools::Itertools;
+use regex::Regex;
 use rusqlite::{Error as SqliteError, Transaction};
 
+use crate::common;
 use crate::common::SqliteId;
+use crate::config;
+use crate::han;
+use crate::pinyin;
 
 pub fn finalize_note_ids(conn: &Transaction, max_ext_note_id: u32) -> Result<u32, SqliteError> {
     let mut stmt_max_ext_note_id = conn.prepare(
@@ -47,7 +57,46 @@ pub fn finalize_note_ids(conn: &Transaction, max_ext_note_id: u32) -> Result<u32
     Ok(base_ext_note_id)
 }
 
-pub fn add_missing_symmetric_references(conn: &Transaction) -> Result<(), SqliteError> {
+/// Adds the shared item of a new reference from a word or definition, it is ranked after the last reference of
+/// the word or definition or else directly after the word or definition itself. Returns the shared id.
+fn add_reference_shared(
+    conn: &Transaction,
+    word_id_src: SqliteId,
+    definition_id_src: Option<SqliteId>,
+) -> Result<SqliteId, SqliteError> {
+    let (rank, rank_relative): (i64, i64) = conn
+        .prepare_cached(
+            r"
+            SELECT rank, COALESCE(rank_relative, 0) FROM (
+                SELECT s.rank, s.rank_relative, 0 AS priority
+                FROM dict_reference r
+                JOIN dict_shared s ON r.shared_id = s.id
+                WHERE r.word_id_src = ?1 AND r.definition_id_src IS ?2
+                UNION ALL
+                SELECT s.rank, s.rank_relative, 1
+                FROM dict_definition d
+                JOIN dict_shared s ON d.shared_id = s.id
+                WHERE d.id = ?2
+                UNION ALL
+                SELECT s.rank, s.rank_relative, 2
+                FROM dict_word w
+                JOIN dict_shared s ON w.shared_id = s.id
+                WHERE w.id = ?1
+            )
+            ORDER BY priority, rank DESC, rank_relative DESC
+            LIMIT 1
+            ",
+        )?
+        .query_row((word_id_src, definition_id_src), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    conn.prepare_cached("INSERT INTO dict_shared (rank, rank_relative) VALUES (?1,?2)")?
+        .execute((rank, rank_relative + 1))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Adds the other direction of symmetric references, returns the shared ids of the added references
+pub fn add_missing_symmetric_references(conn: &Transaction) -> Result<Vec<SqliteId>, SqliteError> {
     // find all references with missing symmetric counterpart
     let mut stmt_missing_references = conn.prepare(
         r"
@@ -73,92 +122,18 @@ pub fn add_missing_symmetric_references(conn: &Transaction) -> Result<(), Sqlite
             AND symmetric_ref.id IS NULL;
         "
     )?;
-    let mut stmt_insert_at_shared_id = conn.prepare_cached(
-        r"
-        SELECT
-            CASE
-                /*
-                * First, check if the original reference points to a specific definition (definition_id_dst is not NULL).
-                */
-                WHEN original_ref.definition_id_dst IS NOT NULL THEN
-                    COALESCE(
-                        /*
-                        * Priority 1: Find the rank of the last outgoing reference from the destination definition.
-                        * The subquery looks for all references originating from that specific definition and picks the highest rank.
-                        * If no such references exist, this subquery will return NULL.
-                        */
-                        (
-                            SELECT MAX(shared.rank)
-                            FROM dict_reference AS outgoing_ref
-                            JOIN dict_shared AS shared ON outgoing_ref.shared_id = shared.id
-                            WHERE outgoing_ref.word_id_src = original_ref.word_id_dst
-                            AND outgoing_ref.definition_id_src = original_ref.definition_id_dst
-                        ),
-                        /*
-                        * Priority 2: If the first subquery was NULL (no outgoing references), COALESCE falls back to this one.
-                        * This finds the rank of the destination definition itself.
-                        */
-                        (
-                            SELECT shared.rank
-                            FROM dict_definition AS def
-                            JOIN dict_shared AS shared ON def.shared_id = shared.id
-                            WHERE def.id = original_ref.definition_id_dst
-                        )
-                    )
-
-                /*
-                * If definition_id_dst is NULL, the original reference points to a word in general.
-                * This corresponds to your third and fourth priority rules.
-                */
-                ELSE
-                    COALESCE(
-                        /*
-                        * Priority 3: Find the rank of the last outgoing reference from the destination word.
-                        * This subquery looks for references originating from the word itself (not tied to a specific definition).
-                        * It will return NULL if no such references exist.
-                        */
-                        (
-                            SELECT MAX(shared.rank)
-                            FROM dict_reference AS outgoing_ref
-                            JOIN dict_shared AS shared ON outgoing_ref.shared_id = shared.id
-                            WHERE outgoing_ref.word_id_src = original_ref.word_id_dst
-                            AND outgoing_ref.definition_id_src IS NULL
-                        ),
-                        /*
-                        * Priority 4: If the third subquery was NULL, COALESCE falls back to this one.
-                        * This finds the rank of the destination word itself.
-                        */
-                        (
-                            SELECT shared.rank
-                            FROM dict_word AS word
-                            JOIN dict_shared AS shared ON word.shared_id = shared.id
-                            WHERE word.id = original_ref.word_id_dst
-                        )
-                    )
-            END AS correct_rank
-        FROM
-            dict_reference AS original_ref
-        WHERE
-            original_ref.id = ?1;
-        "
-    )?;
 
     let mut rows = stmt_missing_references.query([])?;
 
-    // TODO log which lines have been added
+    let mut added = vec![];
     while let Some(row) = rows.next()? {
-        let ref_id: SqliteId = row.get("id")?;
         let ref_type_id: SqliteId = row.get("ref_type_id")?;
         let word_id_src: SqliteId = row.get("word_id_src")?;
         let definition_id_src: Option<SqliteId> = row.get("definition_id_src")?;
         let word_id_dst: SqliteId = row.get("word_id_dst")?;
         let definition_id_dst: Option<SqliteId> = row.get("definition_id_dst")?;
-        let rank_to_insert_at: SqliteId =
-            stmt_insert_at_shared_id.query_one((ref_id,), |row| row.get(0))?;
-        let mut stmt =
-            conn.prepare_cached("INSERT INTO dict_shared (rank, rank_relative) VALUES (?1,?2)")?;
-        stmt.execute((rank_to_insert_at, 1))?;
-        let shared_id = conn.last_insert_rowid();
+        // the other direction starts at the destination
+        let shared_id = add_reference_shared(conn, word_id_dst, definition_id_dst)?;
         let mut stmt = conn
             .prepare_cached("INSERT INTO dict_reference (shared_id, ref_type_id, word_id_src, definition_id_src, word_id_dst, definition_id_dst) VALUES (?1,?2,?3,?4,?5,?6)")?;
         stmt.execute((
@@ -170,8 +145,9 @@ pub fn add_missing_symmetric_references(conn: &Transaction) -> Result<(), Sqlite
             word_id_src,
             definition_id_src,
         ))?;
+        added.push(shared_id);
     }
-    Ok(())
+    Ok(added)
 }
 
 #[allow(clippy::too_many_lines, reason = "SQL")]
@@ -316,3 +292,1288 @@ pub fn add_missing_notes_and_tags_for_symmetric_references(
     )?;
     Ok(())
 }
+
+/// Adds an ascii tag to an item, unknown tags are ignored
+fn add_tag(conn: &Transaction, shared_id: SqliteId, tag: char) -> Result<(), SqliteError> {
+    let Some((name, category, _)) = config::tag_to_txt_ascii_common(tag) else {
+        return Ok(());
+    };
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO dict_tag (tag, type, ascii_symbol) VALUES (?1,?2,?3)",
+    )?
+    .execute((name, category, tag.to_string()))?;
+    conn.prepare_cached(
+        r"
+        INSERT OR IGNORE INTO dict_shared_tag (for_shared_id, tag_id)
+        SELECT ?1, id FROM dict_tag WHERE tag = ?2 AND type = ?3
+        ",
+    )?
+    .execute((shared_id, name, category))?;
+    Ok(())
+}
+
+/// Removes an ascii tag from an item
+fn remove_tag(conn: &Transaction, shared_id: SqliteId, tag: char) -> Result<(), SqliteError> {
+    conn.prepare_cached(
+        r"
+        DELETE FROM dict_shared_tag
+        WHERE for_shared_id = ?1 AND tag_id IN (SELECT id FROM dict_tag WHERE ascii_symbol = ?2)
+        ",
+    )?
+    .execute((shared_id, tag.to_string()))?;
+    Ok(())
+}
+
+/// Usage label kept in a definition, with the tag of the label and the existing tag of the same rank
+type LabelConflict<'a> = (&'a str, char, char);
+
+/// Usage labels in parentheses which are moved from the definitions to tags, e.g. "(chiefly Taiwan)" --> t
+#[derive(Debug, Clone)]
+pub struct UsageLabels {
+    labels: Vec<(Regex, char)>,
+}
+
+impl Default for UsageLabels {
+    fn default() -> Self {
+        Self::new(config::USAGE_LABEL_TAGS.iter().map(|(p, t)| (*p, *t)))
+            .expect("valid default usage labels")
+    }
+}
+
+impl UsageLabels {
+    /// Patterns are case-insensitive regular expressions, which have to match the whole label
+    ///
+    /// # Errors
+    /// Returns an error for invalid patterns and unknown ascii tags
+    pub fn new<'a>(labels: impl IntoIterator<Item = (&'a str, char)>) -> Result<Self, String> {
+        let labels = labels
+            .into_iter()
+            .map(|(pattern, tag)| {
+                if config::tag_to_txt_ascii_common(tag).is_none() {
+                    return Err(format!("Unknown tag {tag} for the usage label {pattern}"));
+                }
+                let regex = Regex::new(&format!("(?i)^(?:{pattern})$"))
+                    .map_err(|e| format!("Invalid usage label {pattern}: {e}"))?;
+                Ok((regex, tag))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { labels })
+    }
+
+    fn tag(&self, label: &str) -> Option<char> {
+        self.labels
+            .iter()
+            .find(|(regex, _)| regex.is_match(label.trim()))
+            .map(|l| l.1)
+    }
+
+    /// Definition without the usage labels and the tags to add, labels whose tag conflicts with an existing tag of
+    /// the same rank are kept
+    fn strip<'a>(
+        &self,
+        definition: &'a str,
+        tags: &[char],
+    ) -> (String, Vec<char>, Vec<LabelConflict<'a>>) {
+        let rank = |tag: char| config::tag_to_txt_ascii_common(tag).map(|t| t.2);
+        let mut stripped = definition.to_owned();
+        let mut new_tags: Vec<char> = vec![];
+        let mut conflicts = vec![];
+        // from the end, so that the ranges stay valid
+        for (range, label) in parentheticals(definition).into_iter().rev() {
+            let Some(label_tags) = self.tags(label) else {
+                continue;
+            };
+            let conflict = label_tags.iter().find_map(|label_tag| {
+                tags.iter()
+                    .find(|t| *t != label_tag && rank(**t) == rank(*label_tag))
+                    .map(|t| (label, *label_tag, *t))
+            });
+            if let Some(conflict) = conflict {
+                conflicts.push(conflict);
+                continue;
+            }
+            stripped.replace_range(range, " ");
+            for label_tag in label_tags {
+                if !tags.contains(&label_tag) && !new_tags.contains(&label_tag) {
+                    new_tags.push(label_tag);
+                }
+            }
+        }
+        let stripped = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+        (stripped, new_tags, conflicts)
+    }
+
+    /// Tags of a parenthetical if all its comma-separated labels are known, e.g. "obsolete, Taiwan"
+    fn tags(&self, parenthetical: &str) -> Option<Vec<char>> {
+        let mut tags = vec![];
+        for label in parenthetical.split([',', ';', '，', '；']) {
+            let tag = self.tag(label)?;
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        Some(tags)
+    }
+}
+
+/// Byte ranges of the top-level parentheticals including the parentheses, and their content
+fn parentheticals(definition: &str) -> Vec<(Range<usize>, &str)> {
+    let mut parentheticals = vec![];
+    let mut depth = 0_usize;
+    // start of the parenthetical and of its content
+    let mut start = (0, 0);
+    for (i, c) in definition.char_indices() {
+        match c {
+            '(' | '（' => {
+                if depth == 0 {
+                    start = (i, i + c.len_utf8());
+                }
+                depth += 1;
+            }
+            ')' | '）' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    parentheticals.push((start.0..i + c.len_utf8(), &definition[start.1..i]));
+                }
+            }
+            _ => {}
+        }
+    }
+    parentheticals
+}
+
+/// Moves the usage labels in parentheses of the definitions to tags of the definitions, labels are kept if the
+/// tag conflicts with a tag of the same rank or nothing would be left of the definition. Returns a description
+/// of every change for the review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn move_usage_labels_to_tags(
+    conn: &Transaction,
+    usage_labels: &UsageLabels,
+) -> Result<Vec<String>, SqliteError> {
+    let mut stmt_definitions = conn.prepare(
+        r"
+        SELECT
+            def.id,
+            def.shared_id,
+            def.definition,
+            def.ext_def_id,
+            w.trad,
+            w.simp,
+            (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                WHERE st.for_shared_id = def.shared_id) AS tags
+        FROM dict_definition def
+        JOIN dict_word w ON def.word_id = w.id
+        JOIN dict_shared s ON def.shared_id = s.id
+        WHERE def.definition LIKE '%(%' OR def.definition LIKE '%（%'
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    let mut stmt_update =
+        conn.prepare("UPDATE dict_definition SET definition = ?2 WHERE id = ?1")?;
+
+    // collected first, the definitions are updated below
+    let definitions = stmt_definitions
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, SqliteId>("id")?,
+                row.get::<_, SqliteId>("shared_id")?,
+                row.get::<_, String>("definition")?,
+                common::format_word_def(
+                    &row.get::<_, String>("trad")?,
+                    &row.get::<_, String>("simp")?,
+                    row.get("ext_def_id")?,
+                ),
+                row.get::<_, Option<String>>("tags")?.unwrap_or_default(),
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = vec![];
+    for (definition_id, shared_id, definition, location, tags) in definitions {
+        let tags: Vec<char> = tags.chars().collect();
+        let (new_definition, new_tags, conflicts) = usage_labels.strip(&definition, &tags);
+        for (label, label_tag, tag) in conflicts {
+            changes.push(format!(
+                "{location}: kept ({label}), the tag {label_tag} conflicts with {tag}"
+            ));
+        }
+        if new_definition == definition.split_whitespace().collect::<Vec<_>>().join(" ") {
+            continue;
+        }
+        if new_definition.is_empty() {
+            changes.push(format!(
+                "{location}: kept \"{definition}\", nothing would be left of the definition"
+            ));
+            continue;
+        }
+
+        stmt_update.execute((definition_id, &new_definition))?;
+        for tag in &new_tags {
+            add_tag(conn, shared_id, *tag)?;
+        }
+        let added = if new_tags.is_empty() {
+            String::new()
+        } else {
+            format!(", added {}", new_tags.iter().join(""))
+        };
+        changes.push(format!(
+            "{location}: \"{definition}\" -> \"{new_definition}\"{added}"
+        ));
+    }
+    Ok(changes)
+}
+
+/// Definition of a word with definitions to split into sub-definitions
+#[derive(Debug)]
+struct TreeDefinition {
+    id: SqliteId,
+    shared_id: SqliteId,
+    ext_def_id: u32,
+    definition: String,
+    class_id: SqliteId,
+    parent_id: Option<SqliteId>,
+    /// Pronunciations (`dict_shared_pron` ids), sub-definitions have the same pronunciations as their parent
+    prons: String,
+}
+
+/// Texts of the parents of a definition and its own text
+fn definition_path(definitions: &[TreeDefinition], definition: &TreeDefinition) -> Vec<String> {
+    let parent = |d: &TreeDefinition| {
+        let parent_id = d.parent_id?;
+        definitions.iter().find(|p| p.id == parent_id)
+    };
+    let mut path: Vec<String> = std::iter::successors(Some(definition), |d| parent(d))
+        .take(definitions.len())
+        .map(|d| d.definition.clone())
+        .collect();
+    path.reverse();
+    path
+}
+
+/// Splits the definitions concatenated with `---` (definition trees of wiktionary) into sub-definitions, e.g.
+/// "a scale --- a balance" becomes the sub-definition "a balance" of a definition "a scale". Definitions with the
+/// same class and pronunciations and the text of a parent are reused as parent, otherwise a parent with a new id
+/// is added. The split definitions keep their ids. Returns a description of every change for the review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn split_definition_paths(conn: &Transaction) -> Result<Vec<String>, SqliteError> {
+    let mut stmt_definitions = conn.prepare(
+        r"
+        SELECT
+            def.id,
+            def.shared_id,
+            def.word_id,
+            def.ext_def_id,
+            def.definition,
+            def.class_id,
+            def.parent_id,
+            (SELECT GROUP_CONCAT(pdp.shared_pron_id ORDER BY pdp.shared_pron_id)
+                FROM dict_pron_definition pdp WHERE pdp.definition_id = def.id) AS prons,
+            w.trad,
+            w.simp
+        FROM dict_definition def
+        JOIN dict_word w ON def.word_id = w.id
+        JOIN dict_shared s ON def.shared_id = s.id
+        WHERE def.word_id IN (
+            SELECT word_id FROM dict_definition WHERE parent_id IS NULL AND definition LIKE '%---%')
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    // collected first, the definitions are updated below
+    let rows = stmt_definitions
+        .query_map([], |row| {
+            Ok((
+                (
+                    row.get::<_, SqliteId>("word_id")?,
+                    row.get::<_, String>("trad")?,
+                    row.get::<_, String>("simp")?,
+                ),
+                TreeDefinition {
+                    id: row.get("id")?,
+                    shared_id: row.get("shared_id")?,
+                    ext_def_id: row.get("ext_def_id")?,
+                    definition: row.get("definition")?,
+                    class_id: row.get("class_id")?,
+                    parent_id: row.get("parent_id")?,
+                    prons: row.get::<_, Option<String>>("prons")?.unwrap_or_default(),
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = vec![];
+    for (word, definitions) in &rows.into_iter().chunk_by(|(word, _)| word.clone()) {
+        let definitions = definitions.map(|(_, d)| d).collect();
+        split_word_definition_paths(conn, &word, definitions, &mut changes)?;
+    }
+    Ok(changes)
+}
+
+// first id after the ids of the definitions and redirects of a word
+fn next_ext_def_id(
+    conn: &Transaction,
+    word_id: SqliteId,
+    definitions: &[TreeDefinition],
+) -> Result<u32, SqliteError> {
+    let max_redirect_id: Option<u32> = conn.query_row(
+        "SELECT MAX(ext_def_id) FROM dict_redirect WHERE word_id = ?1",
+        (word_id,),
+        |row| row.get(0),
+    )?;
+    Ok(definitions
+        .iter()
+        .map(|d| d.ext_def_id)
+        .chain(max_redirect_id)
+        .max()
+        .unwrap_or_default()
+        + 1)
+}
+
+fn split_word_definition_paths(
+    conn: &Transaction,
+    (word_id, trad, simp): &(SqliteId, String, String),
+    mut definitions: Vec<TreeDefinition>,
+    changes: &mut Vec<String>,
+) -> Result<(), SqliteError> {
+    let location = |ext_def_id| common::format_word_def(trad, simp, Some(ext_def_id));
+    let mut next_ext_def_id = next_ext_def_id(conn, *word_id, &definitions)?;
+    // the ranks of the definitions of the word are rewritten at the end, so that sub-definitions follow their parent
+    let rank: u64 = conn.query_row(
+        "SELECT rank FROM dict_shared WHERE id = ?1",
+        (definitions[0].shared_id,),
+        |row| row.get(0),
+    )?;
+
+    // definitions which can be used as parents, by class, pronunciations and path
+    let mut parents: HashMap<(SqliteId, String, Vec<String>), SqliteId> = definitions
+        .iter()
+        .filter(|d| !d.definition.contains("---"))
+        .map(|d| {
+            let path = definition_path(&definitions, d);
+            ((d.class_id, d.prons.clone(), path), d.id)
+        })
+        .collect();
+    let to_split: Vec<SqliteId> = definitions
+        .iter()
+        .filter(|d| d.parent_id.is_none() && d.definition.contains("---"))
+        .map(|d| d.id)
+        .collect();
+
+    for id in to_split {
+        let Some(mut idx) = definitions.iter().position(|d| d.id == id) else {
+            continue;
+        };
+        let definition = &definitions[idx];
+        let parts: Vec<String> = definition
+            .definition
+            .split("---")
+            .map(|p| p.trim().to_owned())
+            .collect();
+        if parts.iter().any(String::is_empty) {
+            changes.push(format!(
+                "{}: kept \"{}\", empty part",
+                location(definition.ext_def_id),
+                definition.definition
+            ));
+            continue;
+        }
+        let (class_id, prons) = (definition.class_id, definition.prons.clone());
+        let mut parent_id = None;
+        for depth in 1..parts.len() {
+            let key = (class_id, prons.clone(), parts[..depth].to_vec());
+            if let Some(id) = parents.get(&key) {
+                parent_id = Some(*id);
+                continue;
+            }
+            let parent = add_definition(
+                conn,
+                *word_id,
+                &definitions[idx],
+                &parts[depth - 1],
+                next_ext_def_id,
+                parent_id,
+                rank,
+            )?;
+            changes.push(format!(
+                "{}: added \"{}\"{}",
+                location(next_ext_def_id),
+                parts[depth - 1],
+                parent_location(&definitions, parent_id)
+            ));
+            next_ext_def_id += 1;
+            parent_id = Some(parent.id);
+            parents.insert(key, parent.id);
+            definitions.insert(idx, parent);
+            idx += 1;
+        }
+
+        let definition = &mut definitions[idx];
+        let old_definition = mem::take(&mut definition.definition);
+        definition.definition = parts.last().cloned().unwrap_or_default();
+        definition.parent_id = parent_id;
+        conn.execute(
+            "UPDATE dict_definition SET definition = ?2, parent_id = ?3 WHERE id = ?1",
+            (definition.id, &definition.definition, parent_id),
+        )?;
+        parents.insert((class_id, prons, parts.clone()), definition.id);
+        let definition = &definitions[idx];
+        changes.push(format!(
+            "{}: \"{old_definition}\" -> \"{}\"{}",
+            location(definition.ext_def_id),
+            definition.definition,
+            parent_location(&definitions, parent_id)
+        ));
+    }
+
+    let mut stmt_rank =
+        conn.prepare_cached("UPDATE dict_shared SET rank = ?2, rank_relative = ?3 WHERE id = ?1")?;
+    for (rank_relative, definition) in tree_order(&definitions).into_iter().enumerate() {
+        stmt_rank.execute((definition.shared_id, rank, rank_relative))?;
+    }
+    Ok(())
+}
+
+/// Adds a parent for a definition, with the same class and pronunciations
+fn add_definition(
+    conn: &Transaction,
+    word_id: SqliteId,
+    child: &TreeDefinition,
+    definition: &str,
+    ext_def_id: u32,
+    parent_id: Option<SqliteId>,
+    rank: u64,
+) -> Result<TreeDefinition, SqliteError> {
+    conn.execute("INSERT INTO dict_shared (rank) VALUES (?1)", (rank,))?;
+    let shared_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO dict_definition (shared_id, word_id, definition, ext_def_id, class_id, parent_id) VALUES (?1,?2,?3,?4,?5,?6)",
+        (shared_id, word_id, definition, ext_def_id, child.class_id, parent_id),
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        r"
+        INSERT INTO dict_pron_definition (shared_pron_id, definition_id)
+        SELECT shared_pron_id, ?2 FROM dict_pron_definition WHERE definition_id = ?1 ORDER BY id
+        ",
+        (child.id, id),
+    )?;
+    Ok(TreeDefinition {
+        id,
+        shared_id,
+        ext_def_id,
+        definition: definition.to_owned(),
+        class_id: child.class_id,
+        parent_id,
+        prons: child.prons.clone(),
+    })
+}
+
+fn parent_location(definitions: &[TreeDefinition], parent_id: Option<SqliteId>) -> String {
+    parent_id
+        .and_then(|id| definitions.iter().find(|d| d.id == id))
+        .map(|p| format!(" below D{}", p.ext_def_id))
+        .unwrap_or_default()
+}
+
+/// Definitions in the order of the text representation: top-level definitions in their order, each followed by
+/// its sub-definitions
+fn tree_order(definitions: &[TreeDefinition]) -> Vec<&TreeDefinition> {
+    fn add<'a>(
+        order: &mut Vec<&'a TreeDefinition>,
+        definitions: &'a [TreeDefinition],
+        parent_id: Option<SqliteId>,
+    ) {
+        for definition in definitions.iter().filter(|d| d.parent_id == parent_id) {
+            order.push(definition);
+            add(order, definitions, Some(definition.id));
+        }
+    }
+    let mut order = vec![];
+    add(&mut order, definitions, None);
+    order
+}
+
+/// Frequency ranks of words (1 = most frequent) from a word frequency list
+#[derive(Debug, Clone, Default)]
+pub struct FrequencyList {
+    ranks: HashMap<String, u32>,
+}
+
+impl FrequencyList {
+    /// Read a word frequency list in UTF-8, e.g. SUBTLEX-CH ("的\t1681440\t..."): each line starts with a word
+    /// followed by its count, lines without a count (headers) are ignored. A list of words only is ranked in the
+    /// order of the lines.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails
+    pub fn read(reader: &mut dyn BufRead) -> io::Result<Self> {
+        let mut words: Vec<(String, Option<u64>)> = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next() else {
+                continue;
+            };
+            if word.starts_with('#') {
+                continue;
+            }
+            words.push((word.to_owned(), fields.next().and_then(|f| f.parse().ok())));
+        }
+        if words.iter().any(|(_, count)| count.is_some()) {
+            words.retain(|(_, count)| count.is_some());
+            // stable, words with the same count keep the order of the list
+            words.sort_by_key(|(_, count)| Reverse(*count));
+        }
+        let mut ranks = HashMap::new();
+        for (rank, (word, _)) in (1..).zip(words) {
+            ranks.entry(word).or_insert(rank);
+        }
+        Ok(Self { ranks })
+    }
+
+    /// Best rank of the traditional and the simplified form of a word
+    #[must_use]
+    pub fn rank(&self, trad: &str, simp: &str) -> Option<u32> {
+        [trad, simp]
+            .iter()
+            .filter_map(|w| self.ranks.get(*w))
+            .min()
+            .copied()
+    }
+}
+
+/// Frequency ranks from which words are tagged: up to `high` with +, after `low` with - and after `lowest` with x
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelevanceThresholds {
+    high: u32,
+    low: u32,
+    lowest: u32,
+}
+
+impl RelevanceThresholds {
+    /// # Errors
+    /// Returns an error if the thresholds are not in ascending order
+    pub fn new(high: u32, low: u32, lowest: u32) -> Result<Self, String> {
+        if high > low || low > lowest {
+            return Err(format!(
+                "Relevance thresholds must be in ascending order: {high},{low},{lowest}"
+            ));
+        }
+        Ok(Self { high, low, lowest })
+    }
+
+    /// Relevance tag of a frequency rank, None for the ranks between high and low relevance
+    #[must_use]
+    pub const fn tag(&self, rank: u32) -> Option<char> {
+        if rank <= self.high {
+            Some('+')
+        } else if rank > self.lowest {
+            Some('x')
+        } else if rank > self.low {
+            Some('-')
+        } else {
+            None
+        }
+    }
+}
+
+/// Stores the frequency ranks of the words and adds relevance tags to the words according to their rank, together
+/// with the source tag f. Relevance tags with the tag f are updated on every run, other relevance tags, e.g. set by
+/// a human, are kept and differences are reported. Returns a description of every change for the review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn tag_relevance_by_frequency(
+    conn: &Transaction,
+    frequency_list: &FrequencyList,
+    thresholds: RelevanceThresholds,
+) -> Result<Vec<String>, SqliteError> {
+    let mut stmt_words = conn.prepare(
+        r"
+        SELECT
+            w.id,
+            w.shared_id,
+            w.trad,
+            w.simp,
+            (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                WHERE st.for_shared_id = w.shared_id AND t.type = 'relevance') AS relevance_tags,
+            EXISTS (SELECT 1
+                FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                WHERE st.for_shared_id = w.shared_id AND t.ascii_symbol = 'f') AS from_frequency_list
+        FROM dict_word w
+        JOIN dict_shared s ON w.shared_id = s.id
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    let mut stmt_update = conn.prepare("UPDATE dict_word SET frequency_rank = ?2 WHERE id = ?1")?;
+
+    let words = stmt_words
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, SqliteId>("id")?,
+                row.get::<_, SqliteId>("shared_id")?,
+                row.get::<_, String>("trad")?,
+                row.get::<_, String>("simp")?,
+                row.get::<_, Option<String>>("relevance_tags")?
+                    .unwrap_or_default(),
+                row.get::<_, bool>("from_frequency_list")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = vec![];
+    for (word_id, shared_id, trad, simp, tags, from_frequency_list) in words {
+        let rank = frequency_list.rank(&trad, &simp);
+        stmt_update.execute((word_id, rank))?;
+        let location = common::format_word_def(&trad, &simp, None);
+        let tag = rank.and_then(|r| thresholds.tag(r));
+        let rank_str = rank.map_or_else(|| "no rank".to_owned(), |r| format!("rank {r}"));
+        if !from_frequency_list {
+            match tag {
+                Some(tag) if tags.is_empty() => {
+                    add_tag(conn, shared_id, tag)?;
+                    add_tag(conn, shared_id, 'f')?;
+                    changes.push(format!("{location}: {rank_str}, added {tag}"));
+                }
+                Some(tag) if !tags.contains(tag) => {
+                    changes.push(format!(
+                        "{location}: kept {tags}, {rank_str} would be {tag}"
+                    ));
+                }
+                _ => {}
+            }
+            continue;
+        }
+        // relevance from a previous run
+        if tag.is_some_and(|t| tags == t.to_string()) {
+            continue;
+        }
+        for old_tag in tags.chars() {
+            remove_tag(conn, shared_id, old_tag)?;
+        }
+        if let Some(tag) = tag {
+            add_tag(conn, shared_id, tag)?;
+            changes.push(format!("{location}: {rank_str}, changed {tags} to {tag}"));
+        } else {
+            remove_tag(conn, shared_id, 'f')?;
+            changes.push(format!("{location}: {rank_str}, removed {tags}"));
+        }
+    }
+    Ok(changes)
+}
+
+/// Readings of words in Taiwan and mainland China, the standard reading first, e.g. from the dictionaries of the
+/// MOE and the Xiandai Hanyu Cidian
+#[derive(Debug, Clone, Default)]
+pub struct RegionalReadings {
+    readings: HashMap<String, (Vec<String>, Vec<String>)>,
+}
+
+impl RegionalReadings {
+    /// Read a table with a word, its readings in Taiwan and its readings in mainland China per line, separated by
+    /// tabs, e.g. "垃圾\tle4se4; la1ji1\tla1ji1". Several readings are separated by ";", the standard reading
+    /// first. Lines starting with # are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if reading fails
+    pub fn read(reader: &mut dyn BufRead) -> io::Result<Self> {
+        let split = |readings: &str| -> Vec<String> {
+            readings
+                .split(config::ITEMS_SEP)
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        let mut readings = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with('#') {
+                continue;
+            }
+            if let [word, taiwan, mainland] = line.split('\t').collect::<Vec<_>>()[..] {
+                readings.insert(word.trim().to_owned(), (split(taiwan), split(mainland)));
+            }
+        }
+        Ok(Self { readings })
+    }
+
+    fn get(&self, trad: &str, simp: &str) -> Option<&(Vec<String>, Vec<String>)> {
+        self.readings.get(trad).or_else(|| self.readings.get(simp))
+    }
+}
+
+/// Country tag of a reading: only used in Taiwan or mainland China (T, C), or the standard reading on one side
+/// only (t, c). None if the word is not known on both sides or the reading is the same. All readings are compared
+/// by their keys.
+fn regional_tag(reading: &String, taiwan: &[String], mainland: &[String]) -> Option<char> {
+    let standard = |readings: &[String]| readings.first() == Some(reading);
+    match (taiwan.contains(reading), mainland.contains(reading)) {
+        _ if taiwan.is_empty() || mainland.is_empty() => None,
+        (true, false) => Some('T'),
+        (false, true) => Some('C'),
+        (true, true) if standard(taiwan) && !standard(mainland) => Some('t'),
+        (true, true) if standard(mainland) && !standard(taiwan) => Some('c'),
+        _ => None,
+    }
+}
+
+/// Adds the country tags (T, t, C, c) to the pronunciations of the words according to the readings in Taiwan and
+/// mainland China. Pronunciations which already have a country tag, e.g. set by a human, are not changed,
+/// differences are reported, as well as readings of the table which are missing in the dictionary. Returns a
+/// description of every change for the review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn tag_regional_pronunciations(
+    conn: &Transaction,
+    regional_readings: &RegionalReadings,
+) -> Result<Vec<String>, SqliteError> {
+    let mut stmt_prons = conn.prepare(
+        r"
+        SELECT
+            w.trad,
+            w.simp,
+            p_s.id AS shared_id,
+            p.pinyin_num,
+            (SELECT GROUP_CONCAT(t.ascii_symbol, '')
+                FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                WHERE st.for_shared_id = p_s.id AND t.type = 'country') AS country_tags
+        FROM dict_word w
+        JOIN dict_shared w_s ON w.shared_id = w_s.id
+        JOIN dict_definition def ON def.word_id = w.id
+        JOIN dict_pron_definition pdp ON def.id = pdp.definition_id
+        JOIN dict_shared_pron sp ON pdp.shared_pron_id = sp.id
+        JOIN dict_pron p ON sp.pron_id = p.id
+        JOIN dict_shared p_s ON sp.shared_id = p_s.id
+        GROUP BY w.id, p_s.id
+        ORDER BY w_s.rank, w_s.rank_relative, p_s.rank, p_s.rank_relative;
+        ",
+    )?;
+    let prons = stmt_prons
+        .query_map([], |row| {
+            Ok((
+                (row.get::<_, String>("trad")?, row.get::<_, String>("simp")?),
+                row.get::<_, SqliteId>("shared_id")?,
+                row.get::<_, String>("pinyin_num")?,
+                row.get::<_, Option<String>>("country_tags")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = vec![];
+    for ((trad, simp), prons) in &prons.into_iter().chunk_by(|(word, ..)| word.clone()) {
+        let Some((taiwan, mainland)) = regional_readings.get(&trad, &simp) else {
+            continue;
+        };
+        let keys = |readings: &[String]| -> Vec<String> {
+            readings.iter().map(|r| pinyin::reading_key(r)).collect()
+        };
+        let (taiwan_keys, mainland_keys) = (keys(taiwan), keys(mainland));
+        let location = common::format_word_def(&trad, &simp, None);
+        let mut word_keys = vec![];
+        for (_, shared_id, pinyin_num, country_tags) in prons {
+            let key = pinyin::reading_key(&pinyin_num);
+            let tag = regional_tag(&key, &taiwan_keys, &mainland_keys);
+            word_keys.push(key);
+            let Some(tag) = tag else {
+                continue;
+            };
+            match country_tags {
+                Some(tags) if tags.contains(tag) => {}
+                Some(tags) => {
+                    changes.push(format!(
+                        "{location}: {pinyin_num} kept {tags}, the table suggests {tag}"
+                    ));
+                }
+                None => {
+                    add_tag(conn, shared_id, tag)?;
+                    changes.push(format!("{location}: {pinyin_num} added {tag}"));
+                }
+            }
+        }
+        let table = taiwan.iter().zip(&taiwan_keys);
+        for (reading, key) in table.chain(mainland.iter().zip(&mainland_keys)) {
+            if word_keys.contains(key) {
+                continue;
+            }
+            let region = match (taiwan_keys.contains(key), mainland_keys.contains(key)) {
+                (true, true) => "Taiwan and mainland China",
+                (true, false) => "Taiwan",
+                _ => "mainland China",
+            };
+            changes.push(format!("{location}: {reading} ({region}) is missing"));
+            word_keys.push(key.clone());
+        }
+    }
+    Ok(changes)
+}
+
+/// Id of a reference type, which is added if it isn't used yet
+fn ref_type_id(conn: &Transaction, ref_type: char) -> Result<SqliteId, SqliteError> {
+    let (ref_type_full, is_symmetric) =
+        config::get_ref_type(ref_type).expect("known reference type");
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO dict_ref_type (type, ascii_symbol, is_symmetric) VALUES (?1,?2,?3)",
+    )?
+    .execute((ref_type_full, ref_type.to_string(), is_symmetric))?;
+    conn.prepare_cached("SELECT id FROM dict_ref_type WHERE type = ?1")?
+        .query_row((ref_type_full,), |row| row.get(0))
+}
+
+/// Adds a reference from a word to another word with a tag, ranked like the added symmetric references (see
+/// `add_reference_shared`). Returns the shared id of the reference.
+fn add_word_reference(
+    conn: &Transaction,
+    ref_type: char,
+    word_id_src: SqliteId,
+    word_id_dst: SqliteId,
+    tag: char,
+) -> Result<SqliteId, SqliteError> {
+    let shared_id = add_reference_shared(conn, word_id_src, None)?;
+    conn.prepare_cached(
+        "INSERT INTO dict_reference (shared_id, ref_type_id, word_id_src, word_id_dst) VALUES (?1,?2,?3,?4)",
+    )?
+    .execute((shared_id, ref_type_id(conn, ref_type)?, word_id_src, word_id_dst))?;
+    add_tag(conn, shared_id, tag)?;
+    Ok(shared_id)
+}
+
+/// Variants of characters from the Unihan database, in both directions
+#[derive(Debug, Clone, Default)]
+pub struct UnihanVariants {
+    variants: HashMap<char, Vec<(char, String)>>,
+}
+
+/// Fields of `Unihan_Variants.txt` which are character variants in the sense of the dictionary, the traditional
+/// and simplified variants are part of the same entry
+const UNIHAN_VARIANT_FIELDS: &[&str] = &["kSemanticVariant", "kZVariant"];
+
+impl UnihanVariants {
+    /// Read the semantic and z-variants of `Unihan_Variants.txt`, e.g. "U+5300\tkSemanticVariant\tU+8FF4<kMatthews",
+    /// other lines are ignored
+    ///
+    /// # Errors
+    /// Returns an error if reading fails
+    pub fn read(reader: &mut dyn BufRead) -> io::Result<Self> {
+        let mut unihan_variants = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            let [code_point, field, values] = line.split('\t').collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let Some(c) = han::parse_code_point(code_point) else {
+                continue;
+            };
+            if !UNIHAN_VARIANT_FIELDS.contains(&field) {
+                continue;
+            }
+            for value in values.split(' ') {
+                let code_point = value.split('<').next().unwrap_or_default();
+                if let Some(variant) = han::parse_code_point(code_point) {
+                    unihan_variants.insert(c, variant, field);
+                    unihan_variants.insert(variant, c, field);
+                }
+            }
+        }
+        Ok(unihan_variants)
+    }
+
+    fn insert(&mut self, c: char, variant: char, field: &str) {
+        let variants = self.variants.entry(c).or_default();
+        if c != variant && !variants.iter().any(|v| v.0 == variant) {
+            variants.push((variant, field.to_owned()));
+        }
+    }
+
+    fn get(&self, c: char) -> &[(char, String)] {
+        self.variants.get(&c).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Single-character word for the variant references
+struct CharacterWord {
+    id: SqliteId,
+    location: String,
+    chars: Vec<char>,
+    // all definitions have the class variant
+    is_variant: bool,
+    frequency_rank: Option<u32>,
+}
+
+/// Main form of a group of character variants: the only word whose definitions don't all have the class variant,
+/// or else the most frequent of these words
+fn variant_main_form(words: &[CharacterWord], group: &[usize]) -> Option<usize> {
+    let mut candidates: Vec<usize> = group
+        .iter()
+        .copied()
+        .filter(|idx| !words[*idx].is_variant)
+        .collect();
+    if candidates.is_empty() {
+        candidates = group.to_vec();
+    }
+    if let [main] = candidates[..] {
+        return Some(main);
+    }
+    let ranks = candidates
+        .iter()
+        .map(|idx| words[*idx].frequency_rank.map(|rank| (rank, *idx)))
+        .collect::<Option<Vec<_>>>()?;
+    let (min_rank, main) = *ranks.iter().min()?;
+    (ranks.iter().filter(|(rank, _)| *rank == min_rank).count() == 1).then_some(main)
+}
+
+/// Single-character words with their variant class and frequency rank, sorted like the dictionary
+fn load_character_words(conn: &Transaction) -> Result<Vec<CharacterWord>, SqliteError> {
+    let mut stmt_words = conn.prepare(
+        r"
+        SELECT
+            w.id,
+            w.trad,
+            w.simp,
+            w.frequency_rank,
+            EXISTS (SELECT 1 FROM dict_definition def WHERE def.word_id = w.id)
+                AND NOT EXISTS (
+                    SELECT 1 FROM dict_definition def JOIN dict_class c ON def.class_id = c.id
+                    WHERE def.word_id = w.id AND c.name != 'variant'
+                ) AS is_variant
+        FROM dict_word w
+        JOIN dict_shared s ON w.shared_id = s.id
+        WHERE length(w.trad) = 1 AND length(w.simp) = 1
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    stmt_words
+        .query_map([], |row| {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            Ok(CharacterWord {
+                id: row.get("id")?,
+                location: common::format_word_def(&trad, &simp, None),
+                chars: trad.chars().chain(simp.chars()).unique().collect(),
+                is_variant: row.get("is_variant")?,
+                frequency_rank: row.get("frequency_rank")?,
+            })
+        })?
+        .collect()
+}
+
+/// Variant links between the words with the Unihan field, for every word
+fn variant_links<'a>(
+    words: &[CharacterWord],
+    unihan_variants: &'a UnihanVariants,
+) -> Vec<Vec<(usize, &'a str)>> {
+    let mut words_by_char: HashMap<char, Vec<usize>> = HashMap::new();
+    for (idx, word) in words.iter().enumerate() {
+        for c in &word.chars {
+            words_by_char.entry(*c).or_default().push(idx);
+        }
+    }
+    let mut links: Vec<Vec<(usize, &str)>> = vec![vec![]; words.len()];
+    for (idx, word) in words.iter().enumerate() {
+        for c in &word.chars {
+            for (variant, field) in unihan_variants.get(*c) {
+                for other in words_by_char.get(variant).into_iter().flatten() {
+                    if *other != idx && !links[idx].iter().any(|(linked, _)| linked == other) {
+                        links[idx].push((*other, field));
+                    }
+                }
+            }
+        }
+    }
+    links
+}
+
+/// Breadth-first search of the variant links from a word, every reached word with the link it was reached by
+fn variant_reach<'a>(
+    links: &[Vec<(usize, &'a str)>],
+    start: usize,
+) -> Vec<(usize, Option<(usize, &'a str)>)> {
+    let mut reached = vec![(start, None)];
+    let mut visited = HashSet::from([start]);
+    let mut pos = 0;
+    while let Some((idx, _)) = reached.get(pos).copied() {
+        for (other, field) in &links[idx] {
+            if visited.insert(*other) {
+                reached.push((*other, Some((idx, *field))));
+            }
+        }
+        pos += 1;
+    }
+    reached
+}
+
+/// Adds character variant references (v) between single-character words which are variants in the Unihan
+/// database, tagged with u. The variants are grouped transitively and every word of a group refers to the main
+/// form of the group (`variant_main_form`). Words which already have a character variant reference are skipped,
+/// groups without a main form or whose main form is itself a variant are only reported. Returns a description of
+/// every change for the review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn add_unihan_variant_references(
+    conn: &Transaction,
+    unihan_variants: &UnihanVariants,
+) -> Result<Vec<String>, SqliteError> {
+    let words = load_character_words(conn)?;
+    let mut stmt_existing = conn.prepare(
+        r"
+        SELECT r.word_id_src, r.word_id_dst
+        FROM dict_reference r
+        JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+        WHERE rt.ascii_symbol = 'v'
+        ",
+    )?;
+    let mut known_pairs: HashSet<(SqliteId, SqliteId)> = HashSet::new();
+    let mut referring: HashSet<SqliteId> = HashSet::new();
+    for pair in stmt_existing.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (src, dst): (SqliteId, SqliteId) = pair?;
+        known_pairs.insert((src.min(dst), src.max(dst)));
+        referring.insert(src);
+    }
+
+    let links = variant_links(&words, unihan_variants);
+    let reach = |start| variant_reach(&links, start);
+
+    let mut changes = vec![];
+    let mut grouped: HashSet<usize> = HashSet::new();
+    for start in 0..words.len() {
+        if links[start].is_empty() || grouped.contains(&start) {
+            continue;
+        }
+        let group: Vec<usize> = reach(start).into_iter().map(|(idx, _)| idx).collect();
+        grouped.extend(&group);
+        let Some(main) = variant_main_form(&words, &group) else {
+            let fields = group
+                .iter()
+                .flat_map(|idx| links[*idx].iter().map(|(_, field)| *field))
+                .unique()
+                .sorted()
+                .join(", ");
+            changes.push(format!(
+                "{}: variants ({fields}), the main form is unknown",
+                group.iter().map(|idx| &words[*idx].location).join(", ")
+            ));
+            continue;
+        };
+        let main_word = &words[main];
+        if main_word.is_variant || referring.contains(&main_word.id) {
+            changes.push(format!(
+                "{}: main form of the variants {} is itself a variant",
+                main_word.location,
+                group
+                    .iter()
+                    .filter(|idx| **idx != main)
+                    .map(|idx| &words[*idx].location)
+                    .join(", ")
+            ));
+            continue;
+        }
+        for (idx, link) in reach(main) {
+            let word = &words[idx];
+            let Some((linked, field)) = link else {
+                continue;
+            };
+            if referring.contains(&word.id)
+                || known_pairs.contains(&(word.id.min(main_word.id), word.id.max(main_word.id)))
+            {
+                continue;
+            }
+            add_word_reference(conn, 'v', word.id, main_word.id, 'u')?;
+            if linked == main {
+                changes.push(format!(
+                    "{}: added v {} ({field})",
+                    word.location, main_word.location
+                ));
+            } else {
+                changes.push(format!(
+                    "{}: added v {} ({field} of {})",
+                    word.location, main_word.location, words[linked].location
+                ));
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Limits for the components of the composition references
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompositionOptions {
+    /// Minimum number of characters of a component, shorter components are only used for the segmentation
+    pub min_length: usize,
+    /// Only components without a low relevance tag (-, x)
+    pub relevant_only: bool,
+}
+
+impl Default for CompositionOptions {
+    fn default() -> Self {
+        Self {
+            min_length: 2,
+            relevant_only: false,
+        }
+    }
+}
+
+/// Word for the composition references
+struct CompositionWord {
+    id: SqliteId,
+    location: String,
+    trad: Vec<char>,
+    simp: Vec<char>,
+    relevance: Option<char>,
+}
+
+/// Best segmentation of a prefix of a word, ending with a segment which is a contained word or a single character
+#[derive(Debug, Clone, Copy, Default)]
+struct Segment {
+    // characters which are not in contained words
+    uncovered: usize,
+    segments: usize,
+    start: usize,
+    component: Option<usize>,
+}
+
+/// Segments a word into the words it contains (indices of the words), the segmentation with the most characters
+/// in contained words and then with the fewest segments wins. Characters which are not a word are skipped.
+fn segment_word(
+    word: &CompositionWord,
+    words: &[CompositionWord],
+    word_index: &HashMap<(&[char], &[char]), usize>,
+    is_component: impl Fn(&CompositionWord) -> bool,
+) -> Vec<usize> {
+    let n = word.trad.len();
+    let mut best: Vec<Option<Segment>> = vec![None; n + 1];
+    best[0] = Some(Segment::default());
+    for start in 0..n {
+        let Some(previous) = best[start] else {
+            continue;
+        };
+        for (end, best_end) in best.iter_mut().enumerate().skip(start + 1) {
+            let key = (&word.trad[start..end], &word.simp[start..end]);
+            let component = word_index
+                .get(&key)
+                .copied()
+                .filter(|idx| end - start < n && is_component(&words[*idx]));
+            if component.is_none() && end > start + 1 {
+                continue;
+            }
+            let candidate = Segment {
+                uncovered: previous.uncovered + usize::from(component.is_none()),
+                segments: previous.segments + 1,
+                start,
+                component,
+            };
+            if best_end.is_none_or(|b| {
+                (candidate.uncovered, candidate.segments) < (b.uncovered, b.segments)
+            }) {
+                *best_end = Some(candidate);
+            }
+        }
+    }
+    let mut components = vec![];
+    let mut end = n;
+    while let Some(segment) = best[end]
+        && end > 0
+    {
+        components.extend(segment.component);
+        end = segment.start;
+    }
+    components.reverse();
+    components
+}
+
+/// Adds contains references (>) from the words to the words they are composed of and the inverse part-of
+/// references (<), tagged with s. The words are segmented into the longest contained words, deleted words are
+/// skipped and existing references of the same type are kept. Returns a description of every change for the
+/// review.
+///
+/// # Errors
+/// Returns an error if the database can't be queried or updated
+pub fn add_composition_references(
+    conn: &Transaction,
+    options: CompositionOptions,
+) -> Result<Vec<String>, SqliteError> {
+    let mut stmt_words = conn.prepare(
+        r"
+        SELECT
+            w.id,
+            w.trad,
+            w.simp,
+            (SELECT t.ascii_symbol
+                FROM dict_shared_tag st JOIN dict_tag t ON st.tag_id = t.id
+                WHERE st.for_shared_id = w.shared_id AND t.type = 'relevance') AS relevance
+        FROM dict_word w
+        JOIN dict_shared s ON w.shared_id = s.id
+        ORDER BY s.rank, s.rank_relative;
+        ",
+    )?;
+    let words = stmt_words
+        .query_map([], |row| {
+            let trad: String = row.get("trad")?;
+            let simp: String = row.get("simp")?;
+            Ok(CompositionWord {
+                id: row.get("id")?,
+                location: common::format_word_def(&trad, &simp, None),
+                trad: trad.chars().collect(),
+                simp: simp.chars().collect(),
+                relevance: row
+                    .get::<_, Option<String>>("relevance")?
+                    .and_then(|r| r.chars().next()),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut stmt_existing = conn.prepare(
+        r"
+        SELECT r.word_id_src, r.word_id_dst, rt.ascii_symbol
+        FROM dict_reference r
+        JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+        WHERE rt.ascii_symbol IN ('<', '>')
+        ",
+    )?;
+    let existing = stmt_existing
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<HashSet<(SqliteId, SqliteId, String)>, _>>()?;
+
+    let word_index: HashMap<(&[char], &[char]), usize> = words
+        .iter()
+        .enumerate()
+        .map(|(idx, w)| ((w.trad.as_slice(), w.simp.as_slice()), idx))
+        .collect();
+    let is_component = |w: &CompositionWord| {
+        w.relevance != Some('X')
+            && !(options.relevant_only && matches!(w.relevance, Some('-' | 'x')))
+    };
+
+    let mut changes = vec![];
+    for word in &words {
+        if word.trad.len() < 2 || word.trad.len() != word.simp.len() || word.relevance == Some('X')
+        {
+            continue;
+        }
+        let components = segment_word(word, &words, &word_index, is_component);
+        for idx in components.into_iter().unique() {
+            let component = &words[idx];
+            if component.trad.len() < options.min_length {
+                continue;
+            }
+            for (src, dst, ref_type) in [(word, component, '>'), (component, word, '<')] {
+                if existing.contains(&(src.id, dst.id, ref_type.to_string())) {
+                    continue;
+                }
+                add_word_reference(conn, ref_type, src.id, dst.id, 's')?;
+                changes.push(format!(
+                    "{}: added {ref_type} {}",
+                    src.location, dst.location
+                ));
+            }
+        }
+    }
+    Ok(changes)
+}
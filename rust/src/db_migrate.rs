@@ -0,0 +1,123 @@
+// Migration of databases created by older versions of the tool, e.g. previous releases, to the current schema
+// (config::DB_SCHEMA). The version of the schema is stored in PRAGMA user_version, every migration upgrades a
+// database by one version.
+
+use std::fmt;
+
+use rusqlite::{Connection, Error as SqliteError};
+
+use crate::config;
+
+/// Version and the SQL which upgrades a database of the previous version to it
+const MIGRATIONS: &[(u32, &str)] = &[
+    (
+        // redirects of ids which are no longer used by a definition
+        2,
+        r#"
+        CREATE TABLE IF NOT EXISTS "dict_redirect" (
+            "id" INTEGER NOT NULL UNIQUE,
+            "shared_id" INTEGER NOT NULL,
+            "word_id" INTEGER NOT NULL,
+            "ext_def_id" INTEGER NOT NULL,
+            "word_id_dst" INTEGER,
+            "ext_def_id_dst" INTEGER,
+            "date_deleted" TEXT,
+            PRIMARY KEY("id"),
+            FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+            ON UPDATE NO ACTION ON DELETE NO ACTION,
+            FOREIGN KEY ("word_id") REFERENCES "dict_word"("id")
+            ON UPDATE NO ACTION ON DELETE NO ACTION,
+            FOREIGN KEY ("word_id_dst") REFERENCES "dict_word"("id")
+            ON UPDATE NO ACTION ON DELETE NO ACTION
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS "dict_redirect_index_0"
+        ON "dict_redirect" ("word_id", "ext_def_id");
+        "#,
+    ),
+    (
+        // sub-definitions
+        3,
+        r#"ALTER TABLE "dict_definition" ADD COLUMN "parent_id" INTEGER REFERENCES "dict_definition"("id");"#,
+    ),
+    (
+        // frequency ranks of the words
+        4,
+        r#"ALTER TABLE "dict_word" ADD COLUMN "frequency_rank" INTEGER;"#,
+    ),
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    SqliteError(SqliteError),
+    UnknownVersion(u32),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SqliteError(e) => write!(f, "Database error: {e}"),
+            Self::UnknownVersion(0) => write!(f, "Not a dictionary database (schema version 0)"),
+            Self::UnknownVersion(v) => write!(
+                f,
+                "Schema version {v} of the database is newer than the supported version {}, update the tool",
+                config::DB_SCHEMA_VERSION
+            ),
+        }
+    }
+}
+
+impl From<SqliteError> for MigrationError {
+    fn from(err: SqliteError) -> Self {
+        Self::SqliteError(err)
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::SqliteError(ref source) => Some(source),
+            Self::UnknownVersion(_) => None,
+        }
+    }
+}
+
+/// Upgrades a database to the current schema version, returns the version of the database before the upgrade
+///
+/// # Errors
+/// Returns an error for databases without version (not created by the tool) or of a newer version, and if the
+/// database can't be upgraded
+pub fn migrate(conn: &Connection) -> Result<u32, MigrationError> {
+    let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version == 0 || version > config::DB_SCHEMA_VERSION {
+        return Err(MigrationError::UnknownVersion(version));
+    }
+    for (migration_version, sql) in MIGRATIONS.iter().filter(|(v, _)| *v > version) {
+        conn.execute_batch(sql)?;
+        conn.pragma_update(None, "user_version", migration_version)?;
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(config::DB_SCHEMA).unwrap();
+        assert_eq!(migrate(&conn).unwrap(), config::DB_SCHEMA_VERSION);
+
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(
+            migrate(&conn).unwrap_err().to_string(),
+            "Not a dictionary database (schema version 0)"
+        );
+        conn.pragma_update(None, "user_version", config::DB_SCHEMA_VERSION + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&conn),
+            Err(MigrationError::UnknownVersion(_))
+        ));
+    }
+}
@@ -0,0 +1,467 @@
+// In-memory representation of the dictionary entries, used by the exporters which need the whole
+// P/C/D hierarchy of a word at once instead of streaming it like db_to_txt.
+
+use std::collections::HashSet;
+
+use rusqlite::{Connection, Error as SqliteError};
+
+use crate::common::SqliteId;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagInfo {
+    pub tag: String,
+    pub tag_type: String,
+    pub ascii_symbol: Option<char>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteInfo {
+    pub ext_note_id: u32,
+    pub note: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PronEntry {
+    pub pinyin_num: String,
+    pub pinyin_mark: String,
+    pub tags: Vec<TagInfo>,
+    pub note: Option<NoteInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceEntry {
+    pub ref_type: String,
+    pub ascii_symbol: String,
+    pub is_symmetric: bool,
+    pub dst_trad: String,
+    pub dst_simp: String,
+    pub dst_ext_def_id: Option<u32>,
+    pub tags: Vec<TagInfo>,
+    pub note: Option<NoteInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionEntry {
+    pub ext_def_id: u32,
+    /// Parent of a sub-definition, the definitions of a word are in the order of the text representation, i.e.
+    /// sub-definitions follow their parent
+    pub parent_ext_def_id: Option<u32>,
+    pub definition: String,
+    pub class_name: String,
+    pub prons: Vec<PronEntry>,
+    pub tags: Vec<TagInfo>,
+    pub note: Option<NoteInfo>,
+    pub references: Vec<ReferenceEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordEntry {
+    pub trad: String,
+    pub simp: String,
+    pub tags: Vec<TagInfo>,
+    pub note: Option<NoteInfo>,
+    pub references: Vec<ReferenceEntry>,
+    pub definitions: Vec<DefinitionEntry>,
+}
+
+impl WordEntry {
+    /// Parents of a definition, starting with the direct parent
+    pub fn parents<'a>(
+        &'a self,
+        definition: &'a DefinitionEntry,
+    ) -> impl Iterator<Item = &'a DefinitionEntry> {
+        let parent = |d: &DefinitionEntry| {
+            let parent_id = d.parent_ext_def_id?;
+            self.definitions.iter().find(|p| p.ext_def_id == parent_id)
+        };
+        // limited in case of a cycle
+        std::iter::successors(parent(definition), move |d| parent(d)).take(self.definitions.len())
+    }
+
+    /// Number of parents, 0 for a top-level definition
+    #[must_use]
+    pub fn depth(&self, definition: &DefinitionEntry) -> usize {
+        self.parents(definition).count()
+    }
+
+    /// Text of a definition preceded by the texts of its parents, as written before the definitions were split
+    /// into sub-definitions, e.g. "a scale --- a balance"
+    #[must_use]
+    pub fn full_definition(&self, definition: &DefinitionEntry) -> String {
+        let mut texts: Vec<&str> = self
+            .parents(definition)
+            .map(|d| d.definition.as_str())
+            .collect();
+        texts.reverse();
+        texts.push(&definition.definition);
+        texts.join(" --- ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectTarget {
+    Moved {
+        trad: String,
+        simp: String,
+        ext_def_id: u32,
+    },
+    Deleted {
+        date: String,
+    },
+}
+
+/// Definition id of a word which is no longer in use, see `dict_redirect`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectEntry {
+    pub trad: String,
+    pub simp: String,
+    pub ext_def_id: u32,
+    pub target: RedirectTarget,
+}
+
+/// Result of looking up a definition by word and `ext_def_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// The definition exists, possibly under another word or id after following redirects
+    Definition {
+        trad: String,
+        simp: String,
+        ext_def_id: u32,
+    },
+    /// The definition, or the definition it was merged into, was deleted
+    Deleted {
+        date: String,
+    },
+    NotFound,
+}
+
+/// Get the relevance tag (ascii symbol) of an item, if any
+#[must_use]
+pub fn relevance_tag(tags: &[TagInfo]) -> Option<char> {
+    tags.iter()
+        .filter(|t| t.tag_type == "relevance")
+        .find_map(|t| t.ascii_symbol)
+}
+
+#[must_use]
+pub fn has_ascii_tag(tags: &[TagInfo], ascii_tag: char) -> bool {
+    tags.iter().any(|t| t.ascii_symbol == Some(ascii_tag))
+}
+
+/// Number of entries per table, as written to the meta data of the dictionary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DictCounts {
+    pub num_words: u32,
+    pub num_definitions: u32,
+    pub num_references: u32,
+    pub num_notes: u32,
+}
+
+/// Count the words, definitions, references and notes
+///
+/// # Errors
+/// Returns an error if the database can't be queried
+pub fn count_entries(conn: &Connection) -> Result<DictCounts, SqliteError> {
+    conn.query_row(
+        r"
+        SELECT
+            (SELECT COUNT(dict_definition.id) FROM dict_definition) AS num_defs,
+            (SELECT COUNT(dict_word.id) FROM dict_word) AS num_words,
+            (SELECT COUNT(dict_note.id) FROM dict_note) AS num_notes,
+            (SELECT COUNT(dict_reference.id) FROM dict_reference) AS num_refs;
+        ",
+        [],
+        |row| {
+            Ok(DictCounts {
+                num_words: row.get("num_words")?,
+                num_definitions: row.get("num_defs")?,
+                num_references: row.get("num_refs")?,
+                num_notes: row.get("num_notes")?,
+            })
+        },
+    )
+}
+
+/// Load all words with their definitions, in the order of the text representation, including words without
+/// definitions (e.g. with redirects only)
+///
+/// # Errors
+/// Returns an error if the database can't be queried
+pub fn load_word_entries(conn: &Connection) -> Result<Vec<WordEntry>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT
+            w.id AS word_id,
+            w.shared_id AS word_shared_id,
+            w.trad,
+            w.simp,
+            c.name AS class_name,
+            def.id AS def_id,
+            def.shared_id AS def_shared_id,
+            def.ext_def_id,
+            def.definition,
+            parent.ext_def_id AS parent_ext_def_id
+        FROM dict_word w
+        JOIN dict_shared ws ON w.shared_id = ws.id
+        LEFT JOIN dict_definition def ON def.word_id = w.id
+        LEFT JOIN dict_shared s ON def.shared_id = s.id
+        LEFT JOIN dict_class c ON def.class_id = c.id
+        LEFT JOIN dict_definition parent ON def.parent_id = parent.id
+        ORDER BY ws.rank, ws.rank_relative, s.rank, s.rank_relative;
+        ",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut entries: Vec<WordEntry> = vec![];
+    let mut last_word_id = -1;
+
+    while let Some(row) = rows.next()? {
+        let word_id: SqliteId = row.get("word_id")?;
+        if word_id != last_word_id {
+            let word_shared_id: SqliteId = row.get("word_shared_id")?;
+            entries.push(WordEntry {
+                trad: row.get("trad")?,
+                simp: row.get("simp")?,
+                tags: load_tags(conn, word_shared_id)?,
+                note: load_note(conn, word_shared_id)?,
+                references: load_references(conn, word_id, None)?,
+                definitions: vec![],
+            });
+            last_word_id = word_id;
+        }
+        // words without definitions, e.g. with redirects only
+        let Some(def_id) = row.get::<_, Option<SqliteId>>("def_id")? else {
+            continue;
+        };
+        let def_shared_id: SqliteId = row.get("def_shared_id")?;
+        let definition = DefinitionEntry {
+            ext_def_id: row.get("ext_def_id")?,
+            parent_ext_def_id: row.get("parent_ext_def_id")?,
+            definition: row.get("definition")?,
+            class_name: row.get("class_name")?,
+            prons: load_prons(conn, def_id)?,
+            tags: load_tags(conn, def_shared_id)?,
+            note: load_note(conn, def_shared_id)?,
+            references: load_references(conn, word_id, Some(def_id))?,
+        };
+        if let Some(entry) = entries.last_mut() {
+            entry.definitions.push(definition);
+        }
+    }
+    Ok(entries)
+}
+
+/// Load all redirects, in the order of the text representation
+///
+/// # Errors
+/// Returns an error if the database can't be queried
+pub fn load_redirects(conn: &Connection) -> Result<Vec<RedirectEntry>, SqliteError> {
+    let mut stmt = conn.prepare(
+        r"
+        SELECT
+            w.trad,
+            w.simp,
+            r.ext_def_id,
+            w_dst.trad,
+            w_dst.simp,
+            r.ext_def_id_dst,
+            r.date_deleted
+        FROM dict_redirect r
+        JOIN dict_shared s ON r.shared_id = s.id
+        JOIN dict_word w ON r.word_id = w.id
+        LEFT JOIN dict_word w_dst ON r.word_id_dst = w_dst.id
+        ORDER BY s.rank, s.rank_relative
+        ",
+    )?;
+    stmt.query_map([], |row| {
+        let dst_trad: Option<String> = row.get(3)?;
+        let target = match dst_trad {
+            Some(trad) => RedirectTarget::Moved {
+                trad,
+                simp: row.get(4)?,
+                ext_def_id: row.get(5)?,
+            },
+            None => RedirectTarget::Deleted { date: row.get(6)? },
+        };
+        Ok(RedirectEntry {
+            trad: row.get(0)?,
+            simp: row.get(1)?,
+            ext_def_id: row.get(2)?,
+            target,
+        })
+    })?
+    .collect()
+}
+
+/// Look up a definition by its word and `ext_def_id`, following redirects of moved definitions
+///
+/// Links to `word#Dn` stay valid this way even if the definition was merged into another one. A cycle of
+/// redirects results in `NotFound`.
+///
+/// # Errors
+/// Returns an error if the database can't be queried
+pub fn resolve_definition(
+    conn: &Connection,
+    trad: &str,
+    simp: &str,
+    ext_def_id: u32,
+) -> Result<Resolution, SqliteError> {
+    let mut def_stmt = conn.prepare_cached(
+        r"
+        SELECT 1 FROM dict_definition def
+        JOIN dict_word w ON def.word_id = w.id
+        WHERE w.trad = ?1 AND w.simp = ?2 AND def.ext_def_id = ?3
+        ",
+    )?;
+    let mut redirect_stmt = conn.prepare_cached(
+        r"
+        SELECT w_dst.trad, w_dst.simp, r.ext_def_id_dst, r.date_deleted
+        FROM dict_redirect r
+        JOIN dict_word w ON r.word_id = w.id
+        LEFT JOIN dict_word w_dst ON r.word_id_dst = w_dst.id
+        WHERE w.trad = ?1 AND w.simp = ?2 AND r.ext_def_id = ?3
+        ",
+    )?;
+    let mut cur = (trad.to_owned(), simp.to_owned(), ext_def_id);
+    let mut visited = HashSet::new();
+    while visited.insert(cur.clone()) {
+        if def_stmt.exists((&cur.0, &cur.1, cur.2))? {
+            let (trad, simp, ext_def_id) = cur;
+            return Ok(Resolution::Definition {
+                trad,
+                simp,
+                ext_def_id,
+            });
+        }
+        let mut rows = redirect_stmt.query((&cur.0, &cur.1, cur.2))?;
+        let Some(row) = rows.next()? else {
+            return Ok(Resolution::NotFound);
+        };
+        let dst_trad: Option<String> = row.get(0)?;
+        match dst_trad {
+            Some(dst_trad) => cur = (dst_trad, row.get(1)?, row.get(2)?),
+            None => return Ok(Resolution::Deleted { date: row.get(3)? }),
+        }
+    }
+    Ok(Resolution::NotFound)
+}
+
+fn load_tags(conn: &Connection, shared_id: SqliteId) -> Result<Vec<TagInfo>, SqliteError> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        SELECT t.tag, t.type, t.ascii_symbol
+        FROM dict_shared_tag st
+        JOIN dict_tag t ON st.tag_id = t.id
+        WHERE st.for_shared_id = ?1
+        ORDER BY st.rowid
+        ",
+    )?;
+    stmt.query_map([shared_id], |row| {
+        let ascii_symbol: Option<String> = row.get(2)?;
+        Ok(TagInfo {
+            tag: row.get(0)?,
+            tag_type: row.get(1)?,
+            ascii_symbol: ascii_symbol.and_then(|s| s.chars().next()),
+        })
+    })?
+    .collect()
+}
+
+fn load_note(conn: &Connection, shared_id: SqliteId) -> Result<Option<NoteInfo>, SqliteError> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        SELECT n.ext_note_id, n.note
+        FROM dict_shared s
+        JOIN dict_note n ON s.note_id = n.id
+        WHERE s.id = ?1
+        ",
+    )?;
+    let mut rows = stmt.query([shared_id])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some(NoteInfo {
+            ext_note_id: row.get(0)?,
+            note: row.get(1)?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+fn load_prons(conn: &Connection, def_id: SqliteId) -> Result<Vec<PronEntry>, SqliteError> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        SELECT p.pinyin_num, p.pinyin_mark, p_s.id AS pron_shared_id
+        FROM dict_pron_definition pdp
+        JOIN dict_shared_pron sp ON pdp.shared_pron_id = sp.id
+        JOIN dict_pron p ON sp.pron_id = p.id
+        JOIN dict_shared p_s ON sp.shared_id = p_s.id
+        WHERE pdp.definition_id = ?1
+        ORDER BY p_s.rank, p_s.rank_relative
+        ",
+    )?;
+    let prons: Vec<(String, String, SqliteId)> = stmt
+        .query_map([def_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_, _>>()?;
+    prons
+        .into_iter()
+        .map(|(pinyin_num, pinyin_mark, shared_id)| {
+            Ok(PronEntry {
+                pinyin_num,
+                pinyin_mark,
+                tags: load_tags(conn, shared_id)?,
+                note: load_note(conn, shared_id)?,
+            })
+        })
+        .collect()
+}
+
+fn load_references(
+    conn: &Connection,
+    src_word_id: SqliteId,
+    src_def_id: Option<SqliteId>,
+) -> Result<Vec<ReferenceEntry>, SqliteError> {
+    let mut stmt = conn.prepare_cached(
+        r"
+        SELECT
+            rt.type,
+            rt.ascii_symbol,
+            rt.is_symmetric,
+            r.shared_id,
+            w_dst.trad,
+            w_dst.simp,
+            def_dst.ext_def_id
+        FROM dict_reference r
+        JOIN dict_shared s ON r.shared_id = s.id
+        JOIN dict_ref_type rt ON r.ref_type_id = rt.id
+        JOIN dict_word w_dst ON r.word_id_dst = w_dst.id
+        LEFT JOIN dict_definition def_dst ON r.definition_id_dst = def_dst.id
+        WHERE
+            r.word_id_src = ?1 AND
+            ((?2 IS NULL AND r.definition_id_src IS NULL) OR r.definition_id_src = ?2)
+        ORDER BY s.rank, s.rank_relative
+        ",
+    )?;
+    let refs: Vec<(ReferenceEntry, SqliteId)> = stmt
+        .query_map((src_word_id, src_def_id), |row| {
+            Ok((
+                ReferenceEntry {
+                    ref_type: row.get(0)?,
+                    ascii_symbol: row.get(1)?,
+                    is_symmetric: row.get(2)?,
+                    dst_trad: row.get(4)?,
+                    dst_simp: row.get(5)?,
+                    dst_ext_def_id: row.get(6)?,
+                    tags: vec![],
+                    note: None,
+                },
+                row.get(3)?,
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+    refs.into_iter()
+        .map(|(mut reference, shared_id)| {
+            reference.tags = load_tags(conn, shared_id)?;
+            reference.note = load_note(conn, shared_id)?;
+            Ok(reference)
+        })
+        .collect()
+}
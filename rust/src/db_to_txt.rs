@@ -67,6 +67,7 @@ struct DefinitionEntry {
     def_shared_id: SqliteId,
     ext_def_id: u32,
     definition: String,
+    parent_id: Option<SqliteId>,
 }
 
 struct PinyinData {
@@ -121,6 +122,25 @@ impl<'a> DbToTxt<'a> {
         }
     }
 
+    // keeps the parents of the definition in the path and appends the definition
+    fn update_definition_path(path: &mut Vec<SqliteId>, entry: &DefinitionEntry) -> Result<()> {
+        if let Some(parent_id) = entry.parent_id {
+            while path.last().is_some_and(|id| *id != parent_id) {
+                path.pop();
+            }
+            if path.is_empty() {
+                return Err(DbToTxtError::InvalidDbData(format!(
+                    "sub-definition D{} of {} is not below its parent",
+                    entry.ext_def_id, entry.trad
+                )));
+            }
+        } else {
+            path.clear();
+        }
+        path.push(entry.def_id);
+        Ok(())
+    }
+
     pub fn generate_txt_file(&mut self, limit_to_word: Option<&str>) -> Result<()> {
         let mut stmt = self.conn.prepare(
             r"
@@ -135,17 +155,21 @@ impl<'a> DbToTxt<'a> {
                 def.shared_id AS def_shared_id,
                 def.ext_def_id,
                 def.definition,
-                GROUP_CONCAT(p_s.id ORDER BY p_s.rank, p_s.rank_relative) -- NULLS FIRST default
-            FROM dict_definition def
-            JOIN dict_shared s ON def.shared_id = s.id
-            JOIN dict_word w ON def.word_id = w.id
-            JOIN dict_class c ON def.class_id = c.id
+                def.parent_id,
+                GROUP_CONCAT(p_s.id ORDER BY p_s.rank, p_s.rank_relative) AS pinyin_shared_ids -- NULLS FIRST default
+            FROM dict_word w
+            JOIN dict_shared w_s ON w.shared_id = w_s.id
+            LEFT JOIN dict_definition def ON def.word_id = w.id
+            LEFT JOIN dict_shared s ON def.shared_id = s.id
+            LEFT JOIN dict_class c ON def.class_id = c.id
             LEFT JOIN dict_pron_definition pdp ON def.id = pdp.definition_id
             LEFT JOIN dict_shared_pron sp ON pdp.shared_pron_id = sp.id
             LEFT JOIN dict_pron p ON sp.pron_id = p.id
             LEFT JOIN dict_shared p_s ON sp.shared_id = p_s.id
-            GROUP BY def.id
-            ORDER BY s.rank, s.rank_relative; -- NULLS FIRST default
+            -- words without definitions are only kept for their redirects
+            WHERE def.id IS NOT NULL OR EXISTS (SELECT 1 FROM dict_redirect r WHERE r.word_id = w.id)
+            GROUP BY w.id, def.id
+            ORDER BY COALESCE(s.rank, w_s.rank), s.rank_relative; -- NULLS FIRST default
             ",
         )?;
 
@@ -153,10 +177,27 @@ impl<'a> DbToTxt<'a> {
         let mut last_word_id = -1;
         let mut last_pinyin_shared_ids = vec![];
         let mut last_class_id = -1;
+        // definition and its parents, sub-definitions are indented below their parent
+        let mut definition_path: Vec<SqliteId> = vec![];
 
         self.write_shared_items(1, 0)?; // header comment
 
         while let Some(row) = rows.next()? {
+            let def_id: Option<SqliteId> = row.get("def_id")?;
+            if def_id.is_none() {
+                let trad: String = row.get("trad")?;
+                if limit_to_word.is_some_and(|stop_word| trad == stop_word) {
+                    break;
+                }
+                self.write_word_entry(
+                    row.get("word_id")?,
+                    row.get("word_shared_id")?,
+                    &trad,
+                    &row.get::<_, String>("simp")?,
+                )?;
+                last_word_id = -1;
+                continue;
+            }
             let definition_entry = Self::row_to_definition_entry(row)?;
 
             if let Some(stop_word) = limit_to_word {
@@ -167,11 +208,17 @@ impl<'a> DbToTxt<'a> {
 
             // 1. Word Entry
             if definition_entry.word_id != last_word_id {
-                self.write_word_entry(&definition_entry)?;
+                self.write_word_entry(
+                    definition_entry.word_id,
+                    definition_entry.word_shared_id,
+                    &definition_entry.trad,
+                    &definition_entry.simp,
+                )?;
                 last_word_id = definition_entry.word_id;
                 // Reset child states when word changes
                 last_pinyin_shared_ids.clear();
                 last_class_id = -1;
+                definition_path.clear();
             }
 
             // 2. Pinyin Entry
@@ -182,23 +229,26 @@ impl<'a> DbToTxt<'a> {
                 )?;
                 last_pinyin_shared_ids = definition_entry.pinyin_shared_ids.clone();
                 last_class_id = -1;
+                definition_path.clear();
             }
 
             // 3. Class Entry
             if definition_entry.class_id != last_class_id {
                 self.write_class_entry(&definition_entry.class_name)?;
                 last_class_id = definition_entry.class_id;
+                definition_path.clear();
             }
 
             // 4. Definition Entry
-            self.write_definition_entry(&definition_entry)?;
+            Self::update_definition_path(&mut definition_path, &definition_entry)?;
+            self.write_definition_entry(&definition_entry, 2 + definition_path.len())?;
         }
 
         Ok(())
     }
 
     fn row_to_definition_entry(row: &Row) -> Result<DefinitionEntry> {
-        let pinyin_shared_ids_str: Option<String> = row.get(10)?;
+        let pinyin_shared_ids_str: Option<String> = row.get("pinyin_shared_ids")?;
         let pinyin_shared_ids = pinyin_shared_ids_str
             .unwrap()
             .split(',')
@@ -217,16 +267,71 @@ impl<'a> DbToTxt<'a> {
             def_shared_id: row.get("def_shared_id")?,
             ext_def_id: row.get("ext_def_id")?,
             definition: row.get("definition")?,
+            parent_id: row.get("parent_id")?,
         })
     }
 
-    fn write_word_entry(&mut self, entry: &DefinitionEntry) -> Result<()> {
-        let tags = self.get_formatted_tags(entry.word_shared_id)?;
-        let word_str = common::format_word_def(&entry.trad, &entry.simp, None);
+    fn write_word_entry(
+        &mut self,
+        word_id: SqliteId,
+        word_shared_id: SqliteId,
+        trad: &str,
+        simp: &str,
+    ) -> Result<()> {
+        let tags = self.get_formatted_tags(word_shared_id)?;
+        let word_str = common::format_word_def(trad, simp, None);
         // TODO character variants (Xv reference, same word with different characters) should be listed in the same line, separated by ;
         writeln!(self.writer, "W{tags}{word_str}")?;
-        self.write_shared_items(entry.word_shared_id, 1)?;
-        self.write_cross_references(entry.word_id, None, 1)?;
+        self.write_shared_items(word_shared_id, 1)?;
+        self.write_cross_references(word_id, None, 1)?;
+        self.write_redirects(word_id)?;
+        Ok(())
+    }
+
+    fn write_redirects(&mut self, word_id: SqliteId) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(
+            r"
+            SELECT
+                r.shared_id,
+                r.ext_def_id,
+                w_dst.trad,
+                w_dst.simp,
+                r.ext_def_id_dst,
+                r.date_deleted
+            FROM dict_redirect r
+            JOIN dict_shared s ON r.shared_id = s.id
+            LEFT JOIN dict_word w_dst ON r.word_id_dst = w_dst.id
+            WHERE r.word_id = ?1
+            ORDER BY s.rank, s.rank_relative
+            ",
+        )?;
+        let redirects: Vec<(SqliteId, u32, Option<String>)> = stmt
+            .query_map([word_id], |row| {
+                let dst_trad: Option<String> = row.get(2)?;
+                let dst_simp: Option<String> = row.get(3)?;
+                let dst_ext_def_id: Option<u32> = row.get(4)?;
+                let date_deleted: Option<String> = row.get(5)?;
+                let target = match (dst_trad, dst_simp, date_deleted) {
+                    (Some(trad), Some(simp), _) => Some(format!(
+                        "-> {}",
+                        common::format_word_def(&trad, &simp, dst_ext_def_id)
+                    )),
+                    (_, _, Some(date)) => Some(format!("deleted {date}")),
+                    _ => None,
+                };
+                Ok((row.get(0)?, row.get(1)?, target))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        let indentation = self.indent_str.clone();
+        for (shared_id, ext_def_id, target) in redirects {
+            let Some(target) = target else {
+                return Err(DbToTxtError::InvalidDbData(format!(
+                    "redirect R{ext_def_id} without target"
+                )));
+            };
+            writeln!(self.writer, "{indentation}R{ext_def_id} {target}")?;
+            self.write_shared_items(shared_id, 2)?;
+        }
         Ok(())
     }
 
@@ -309,18 +414,18 @@ impl<'a> DbToTxt<'a> {
         Ok(())
     }
 
-    fn write_definition_entry(&mut self, entry: &DefinitionEntry) -> Result<()> {
+    fn write_definition_entry(&mut self, entry: &DefinitionEntry, indent: usize) -> Result<()> {
         let tags = self.get_formatted_tags(entry.def_shared_id)?;
         writeln!(
             self.writer,
             "{}D{}{}{}",
-            self.indent_str.repeat(3),
+            self.indent_str.repeat(indent),
             entry.ext_def_id,
             tags,
-            format_multiline(&entry.definition, 3, &self.indent_str),
+            format_multiline(&entry.definition, indent, &self.indent_str),
         )?;
-        self.write_shared_items(entry.def_shared_id, 4)?;
-        self.write_cross_references(entry.word_id, Some(entry.def_id), 4)?;
+        self.write_shared_items(entry.def_shared_id, indent + 1)?;
+        self.write_cross_references(entry.word_id, Some(entry.def_id), indent + 1)?;
         Ok(())
     }
 
@@ -0,0 +1,164 @@
+// Export in the DSL format (ABBYY Lingvo, GoldenDict), UTF-16LE with BOM
+//
+// Each word is one card with the traditional and simplified headwords, the indentation levels [m1] to [m4]
+// mirror the P/C/D hierarchy of the text format, sub-definitions are indented one level more than their parent.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use itertools::Itertools;
+
+use crate::db_model::{NoteInfo, PronEntry, ReferenceEntry, TagInfo, WordEntry};
+
+/// Deepest indentation level of the DSL format
+const MAX_LEVEL: usize = 9;
+
+const HEADER: &str = "#NAME \"Free Mandarin Learner's Dictionary\"\r\n#INDEX_LANGUAGE \"Chinese\"\r\n#CONTENTS_LANGUAGE \"English\"\r\n";
+
+/// Escape characters with a special meaning in DSL headwords and cards
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\[]{}()~@^#<>".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn format_tags(tags: &[TagInfo]) -> String {
+    tags.iter()
+        .map(|t| format!(" [p]{}[/p]", escape(&t.tag)))
+        .join("")
+}
+
+fn format_reference_target(reference: &ReferenceEntry) -> String {
+    let mut target = format!("[ref]{}[/ref]", escape(&reference.dst_trad));
+    if reference.dst_trad != reference.dst_simp {
+        let _ = write!(target, " / [ref]{}[/ref]", escape(&reference.dst_simp));
+    }
+    if let Some(id) = reference.dst_ext_def_id {
+        let _ = write!(target, " {id}");
+    }
+    target
+}
+
+struct Card {
+    lines: Vec<String>,
+}
+
+impl Card {
+    /// Add a line at the given indentation level, multiline text results in several lines at the same level
+    fn push(&mut self, level: usize, txt: &str) {
+        for line in txt.lines() {
+            self.lines.push(format!("\t[m{level}]{line}[/m]"));
+        }
+    }
+
+    fn push_note(&mut self, level: usize, note: Option<&NoteInfo>) {
+        if let Some(n) = note {
+            for line in n.note.lines() {
+                self.lines
+                    .push(format!("\t[m{level}][com]{}[/com][/m]", escape(line)));
+            }
+        }
+    }
+
+    fn push_references(&mut self, level: usize, references: &[ReferenceEntry]) {
+        for (ref_type, group) in &references.iter().chunk_by(|r| r.ref_type.clone()) {
+            let group: Vec<_> = group.collect();
+            let targets = group
+                .iter()
+                .map(|r| format!("{}{}", format_reference_target(r), format_tags(&r.tags)))
+                .join(", ");
+            self.lines
+                .push(format!("\t[m{level}][p]{}[/p] {targets}[/m]", escape(&ref_type)));
+            for r in group {
+                self.push_note(level + 1, r.note.as_ref());
+            }
+        }
+    }
+}
+
+fn format_prons(prons: &[PronEntry]) -> String {
+    prons
+        .iter()
+        .map(|p| format!("[b]{}[/b]{}", escape(&p.pinyin_mark), format_tags(&p.tags)))
+        .join(", ")
+}
+
+fn word_to_card(entry: &WordEntry) -> Card {
+    let mut card = Card { lines: vec![] };
+    if !entry.tags.is_empty() {
+        card.push(1, format_tags(&entry.tags).trim_start());
+    }
+    card.push_note(1, entry.note.as_ref());
+    card.push_references(1, &entry.references);
+
+    for (_, pron_group) in &entry
+        .definitions
+        .iter()
+        .chunk_by(|d| d.prons.iter().map(|p| (&p.pinyin_num, &p.tags)).collect_vec())
+    {
+        let pron_group: Vec<_> = pron_group.collect();
+        let prons = &pron_group[0].prons;
+        card.lines
+            .push(format!("\t[m1]{}[/m]", format_prons(prons)));
+        for pron in prons {
+            card.push_note(2, pron.note.as_ref());
+        }
+        for (class_name, class_group) in &pron_group.into_iter().chunk_by(|d| &d.class_name) {
+            card.lines
+                .push(format!("\t[m2][i]{}[/i][/m]", escape(class_name)));
+            for def in class_group {
+                // notes of the references are two levels deeper
+                let level = (3 + entry.depth(def)).min(MAX_LEVEL - 2);
+                let mut def_lines = def.definition.lines();
+                card.lines.push(format!(
+                    "\t[m{level}][b]{}.[/b] [trn]{}[/trn]{}[/m]",
+                    def.ext_def_id,
+                    escape(def_lines.next().unwrap_or_default()),
+                    format_tags(&def.tags)
+                ));
+                for line in def_lines {
+                    card.lines
+                        .push(format!("\t[m{level}][trn]{}[/trn][/m]", escape(line)));
+                }
+                card.push_note(level + 1, def.note.as_ref());
+                card.push_references(level + 1, &def.references);
+            }
+        }
+    }
+    card
+}
+
+fn write_utf16le(writer: &mut dyn Write, s: &str) -> io::Result<()> {
+    let bytes: Vec<u8> = s.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    writer.write_all(&bytes)
+}
+
+/// Write the dictionary in DSL format, including the byte order mark
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_dsl(writer: &mut dyn Write, entries: &[WordEntry]) -> io::Result<()> {
+    writer.write_all(&[0xFF, 0xFE])?;
+    write_utf16le(writer, HEADER)?;
+    // words without definitions (e.g. with redirects only) would be cards without a body
+    for entry in entries.iter().filter(|e| !e.definitions.is_empty()) {
+        let mut card = String::from("\r\n");
+        card.push_str(&escape(&entry.trad));
+        card.push_str("\r\n");
+        if entry.simp != entry.trad {
+            card.push_str(&escape(&entry.simp));
+            card.push_str("\r\n");
+        }
+        for line in word_to_card(entry).lines {
+            card.push_str(&line);
+            card.push_str("\r\n");
+        }
+        write_utf16le(writer, &card)?;
+    }
+    Ok(())
+}
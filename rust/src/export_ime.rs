@@ -0,0 +1,166 @@
+// Export of words and pronunciations as input method dictionaries:
+// - Rime: *.dict.yaml with text, code (toneless pinyin, separated by spaces) and weight
+// - fcitx5 (libime text format): word, pinyin separated by apostrophes and weight
+// The weight is derived from the relevance tags, entries tagged as deleted are skipped.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::db_model::{WordEntry, relevance_tag};
+use crate::pinyin;
+
+const DEFAULT_WEIGHT: u32 = 100;
+
+/// Weight of a relevance tag, None if the item is deleted
+const fn relevance_weight(ascii_tag: char) -> Option<u32> {
+    Some(match ascii_tag {
+        '+' => 1000,
+        '-' => 10,
+        'x' => 1,
+        'X' => return None,
+        _ => DEFAULT_WEIGHT,
+    })
+}
+
+/// Convert numbered pinyin to toneless syllables, e.g. `Zhong1guo2` -> `zhong guo`
+/// Erhua r5 becomes "er" since it is written with a separate character, the spelling is normalized first
+/// (e.g. the IPA ɡ).
+#[must_use]
+pub fn toneless_syllables(pinyin_num: &str) -> Vec<String> {
+    pinyin::normalize_spelling(pinyin_num)
+        .to_lowercase()
+        .replace('ê', "e")
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|s| !s.is_empty())
+        .map(|s| if s == "r" { "er".to_owned() } else { s.to_owned() })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImeEntry {
+    pub text: String,
+    pub syllables: Vec<String>,
+    pub weight: u32,
+}
+
+/// Collect all (word, pinyin) candidates with their weights, in the order of the dictionary
+///
+/// The most specific relevance tag wins (pronunciation, definition, word). If a pronunciation is used by
+/// several definitions, the highest weight is used.
+#[must_use]
+pub fn collect_ime_entries(entries: &[WordEntry]) -> Vec<ImeEntry> {
+    let mut ime_entries: Vec<ImeEntry> = vec![];
+    let mut index: HashMap<(String, Vec<String>), usize> = HashMap::new();
+
+    for entry in entries {
+        // words with punctuation or spaces are not useful as candidates
+        if !entry.trad.chars().all(char::is_alphanumeric)
+            || !entry.simp.chars().all(char::is_alphanumeric)
+        {
+            continue;
+        }
+        let word_tag = relevance_tag(&entry.tags);
+        if word_tag == Some('X') {
+            continue;
+        }
+        for def in &entry.definitions {
+            let def_tag = relevance_tag(&def.tags).or(word_tag);
+            if def_tag == Some('X') {
+                continue;
+            }
+            for pron in &def.prons {
+                let Some(weight) = relevance_tag(&pron.tags)
+                    .or(def_tag)
+                    .map_or(Some(DEFAULT_WEIGHT), relevance_weight)
+                else {
+                    continue;
+                };
+                let syllables = toneless_syllables(&pron.pinyin_num);
+                if syllables.is_empty() {
+                    continue;
+                }
+                let mut texts = vec![&entry.trad];
+                if entry.simp != entry.trad {
+                    texts.push(&entry.simp);
+                }
+                for text in texts {
+                    let key = (text.clone(), syllables.clone());
+                    if let Some(idx) = index.get(&key) {
+                        let existing = &mut ime_entries[*idx];
+                        existing.weight = existing.weight.max(weight);
+                    } else {
+                        index.insert(key, ime_entries.len());
+                        ime_entries.push(ImeEntry {
+                            text: text.clone(),
+                            syllables: syllables.clone(),
+                            weight,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    ime_entries
+}
+
+/// Write a Rime dictionary, the name has to match the file name (name.dict.yaml)
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_rime(writer: &mut dyn Write, entries: &[WordEntry], name: &str) -> io::Result<()> {
+    writeln!(writer, "# Rime dictionary")?;
+    writeln!(writer, "# Free Mandarin Learner's Dictionary")?;
+    writeln!(writer, "# encoding: utf-8")?;
+    writeln!(writer)?;
+    writeln!(writer, "---")?;
+    writeln!(writer, "name: {name}")?;
+    writeln!(writer, "version: \"{}\"", env!("CARGO_PKG_VERSION"))?;
+    writeln!(writer, "sort: by_weight")?;
+    writeln!(writer, "use_preset_vocabulary: false")?;
+    writeln!(writer, "columns:\n  - text\n  - code\n  - weight")?;
+    writeln!(writer, "...")?;
+    writeln!(writer)?;
+    for ime_entry in collect_ime_entries(entries) {
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            ime_entry.text,
+            ime_entry.syllables.join(" "),
+            ime_entry.weight
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a plain table for fcitx5 (libime text format, can be converted with `libime_pinyindict`)
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_fcitx(writer: &mut dyn Write, entries: &[WordEntry]) -> io::Result<()> {
+    for ime_entry in collect_ime_entries(entries) {
+        writeln!(
+            writer,
+            "{} {} {}",
+            ime_entry.text,
+            ime_entry.syllables.join("'"),
+            ime_entry.weight
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toneless_syllables() {
+        assert_eq!(toneless_syllables("ni3hao3"), vec!["ni", "hao"]);
+        assert_eq!(toneless_syllables("Zhong1guo2"), vec!["zhong", "guo"]);
+        assert_eq!(toneless_syllables("nü3ren2"), vec!["nv", "ren"]);
+        assert_eq!(toneless_syllables("nv3"), vec!["nv"]);
+        assert_eq!(toneless_syllables("ɡuo4cheng2"), vec!["guo", "cheng"]);
+        assert_eq!(toneless_syllables("yi1dian3r5"), vec!["yi", "dian", "er"]);
+        assert_eq!(toneless_syllables("ma3ma5 hu1hu1"), vec!["ma", "ma", "hu", "hu"]);
+    }
+}
@@ -0,0 +1,360 @@
+// Export as LaTeX source for a printed pocket dictionary (compile with XeLaTeX)
+//
+// Two-column layout with guide words in the header, sections either by the first letter of the pinyin or by
+// the radical of the first character. Classes and tags are abbreviated, references become hyperlinks.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+use itertools::Itertools;
+
+use crate::db_model::{DefinitionEntry, ReferenceEntry, TagInfo, WordEntry, relevance_tag};
+use crate::{export_ime, han};
+
+const PREAMBLE: &str = r"\documentclass[twocolumn,10pt]{article}
+\usepackage[a5paper,margin=1.2cm,headsep=0.4cm]{geometry}
+\usepackage{xeCJK}
+\setCJKmainfont{Noto Serif CJK TC}
+\usepackage{fancyhdr}
+\usepackage[hidelinks]{hyperref}
+\setlength{\columnsep}{0.5cm}
+\setlength{\parindent}{0pt}
+\pagestyle{fancy}
+\fancyhf{}
+% guide words: first and last entry on the page
+\fancyhead[L]{\textbf{\rightmark}}
+\fancyhead[R]{\textbf{\leftmark}}
+\fancyfoot[C]{\thepage}
+\renewcommand{\headrulewidth}{0.4pt}
+\newcommand{\entry}[3]{\par\hangindent=1em\markboth{#1}{#1}\hypertarget{#2}{}\textbf{#3}}
+\newcommand{\pron}[1]{\textsf{#1}}
+\newcommand{\class}[1]{\textit{#1}}
+\newcommand{\taglabel}[1]{{\footnotesize\textsc{#1}}}
+\newcommand{\note}[1]{{\small[#1]}}
+\newcommand{\subdef}{$\triangleright$\,}
+\newcommand{\dictsection}[1]{\section*{#1}}
+\begin{document}
+";
+
+const POSTAMBLE: &str = "\\end{document}\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatexOrder {
+    Pinyin,
+    Radical,
+}
+
+#[derive(Debug)]
+pub struct LatexOptions {
+    pub order: LatexOrder,
+    /// Relevance tags (ascii symbol) of the definitions to include, None for definitions without relevance tag
+    pub relevance: Vec<Option<char>>,
+    /// Radical number and residual stroke count for each character, needed for `LatexOrder::Radical`
+    pub radicals: HashMap<char, (u32, i32)>,
+}
+
+/// Read the radical and stroke count from the kRSUnicode field of the Unihan database (`Unihan_IRGSources.txt`)
+///
+/// # Errors
+/// Returns an error if reading fails
+pub fn read_radical_table(reader: &mut dyn BufRead) -> io::Result<HashMap<char, (u32, i32)>> {
+    let mut radicals = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        let mut fields = line.split('\t');
+        let (Some(code_point), Some("kRSUnicode"), Some(value)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some(c) = han::parse_code_point(code_point) else {
+            continue;
+        };
+        // e.g. "120'.3 120.6", only the first value is used, ' marks a simplified radical form
+        let Some((radical, strokes)) = value
+            .split(' ')
+            .next()
+            .and_then(|v| v.split_once('.'))
+        else {
+            continue;
+        };
+        if let (Ok(radical), Ok(strokes)) = (
+            radical.trim_end_matches('\'').parse::<u32>(),
+            strokes.parse::<i32>(),
+        ) {
+            radicals.insert(c, (radical, strokes));
+        }
+    }
+    Ok(radicals)
+}
+
+/// Escape special characters for LaTeX
+#[must_use]
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Short labels for classes, unknown classes are written in full
+fn class_label(class_name: &str) -> &str {
+    match class_name {
+        "noun" => "n.",
+        "verb" => "v.",
+        "adj" | "adjective" => "adj.",
+        "adv" | "adverb" => "adv.",
+        "pron" | "pronoun" => "pron.",
+        "num" | "numeral" => "num.",
+        "classifier" => "mw.",
+        "particle" => "part.",
+        "prep" | "preposition" => "prep.",
+        "conj" | "conjunction" => "conj.",
+        "intj" | "interjection" => "interj.",
+        "name" => "name",
+        "phrase" => "phr.",
+        "idiom" => "idiom",
+        _ => class_name,
+    }
+}
+
+/// Short labels for tags relevant to readers, tags for editors (source, checks, ...) are not printed
+fn tag_label(tag: &TagInfo) -> Option<&'static str> {
+    Some(match tag.tag.as_str() {
+        "taiwan-only" => "Tw.",
+        "taiwan-chiefly" => "chiefly Tw.",
+        "china-only" => "PRC",
+        "china-chiefly" => "chiefly PRC",
+        "in-compounds" => "in compounds",
+        "high-relevance" => "$\\star$",
+        "lowest-relevance" => "rare",
+        "deleted" => "obs.",
+        _ => return None,
+    })
+}
+
+fn reference_label(ref_type: &str) -> &str {
+    match ref_type {
+        "synonym-equal" => "=",
+        "synonym-similar" => "$\\approx$",
+        "antonym" => "ant.",
+        "could-be-confused-with" => "cf.",
+        "part-of" => "part of",
+        "contains" => "contains",
+        "word-variant-of" => "var. of",
+        "character-variant-of" => "char. var. of",
+        "used-with-measure-word" => "mw.",
+        "collocation" => "coll.",
+        "word-group" => "group",
+        _ => ref_type,
+    }
+}
+
+fn format_tags(tags: &[TagInfo]) -> String {
+    tags.iter()
+        .filter_map(tag_label)
+        .map(|l| format!(" \\taglabel{{{l}}}"))
+        .join("")
+}
+
+fn format_headword(trad: &str, simp: &str) -> String {
+    if trad == simp {
+        escape(trad)
+    } else {
+        format!("{}｜{}", escape(trad), escape(simp))
+    }
+}
+
+/// Printed entry: a word and the definitions which are included for the selected relevance tags
+struct PrintEntry<'a> {
+    word: &'a WordEntry,
+    definitions: Vec<&'a DefinitionEntry>,
+    sort_key: (String, String, String),
+    section: String,
+    guide_word: String,
+}
+
+fn filter_entries<'a>(entries: &'a [WordEntry], options: &LatexOptions) -> Vec<PrintEntry<'a>> {
+    entries
+        .iter()
+        .filter_map(|word| {
+            let word_tag = relevance_tag(&word.tags);
+            let definitions: Vec<_> = word
+                .definitions
+                .iter()
+                .filter(|d| {
+                    options
+                        .relevance
+                        .contains(&relevance_tag(&d.tags).or(word_tag))
+                })
+                .collect();
+            let pinyin = definitions.first()?.prons.first()?;
+            let (sort_key, section, guide_word) = match options.order {
+                LatexOrder::Pinyin => {
+                    let syllables = export_ime::toneless_syllables(&pinyin.pinyin_num).join(" ");
+                    let section = syllables
+                        .chars()
+                        .next()
+                        .filter(char::is_ascii_alphabetic)
+                        .map_or_else(|| "#".to_owned(), |c| c.to_ascii_uppercase().to_string());
+                    (
+                        (
+                            syllables,
+                            pinyin.pinyin_num.to_lowercase(),
+                            word.trad.clone(),
+                        ),
+                        section,
+                        escape(&pinyin.pinyin_mark),
+                    )
+                }
+                LatexOrder::Radical => {
+                    let first_char = word.trad.chars().next()?;
+                    let (radical, strokes) = options
+                        .radicals
+                        .get(&first_char)
+                        .copied()
+                        .unwrap_or((0, 0));
+                    let radical_char = char::from_u32(0x2F00 + radical.saturating_sub(1))
+                        .filter(|_| radical > 0)
+                        .map_or_else(|| "?".to_owned(), |c| c.to_string());
+                    (
+                        (
+                            format!("{radical:03}.{strokes:03}"),
+                            word.trad.clone(),
+                            pinyin.pinyin_num.to_lowercase(),
+                        ),
+                        format!("{radical_char} ({radical})"),
+                        format_headword(&word.trad, &word.simp),
+                    )
+                }
+            };
+            Some(PrintEntry {
+                word,
+                definitions,
+                sort_key,
+                section,
+                guide_word,
+            })
+        })
+        .sorted_by(|a, b| a.sort_key.cmp(&b.sort_key))
+        .collect()
+}
+
+fn write_references(
+    out: &mut String,
+    references: &[ReferenceEntry],
+    targets: &HashMap<(&str, &str), String>,
+) {
+    for (ref_type, group) in &references.iter().chunk_by(|r| &r.ref_type) {
+        let links = group
+            .map(|r| {
+                let mut txt = format_headword(&r.dst_trad, &r.dst_simp);
+                if let Some(id) = r.dst_ext_def_id {
+                    let _ = write!(txt, "~{id}");
+                }
+                // references to a definition link to its anchor instead of the word
+                targets
+                    .get(&(r.dst_trad.as_str(), r.dst_simp.as_str()))
+                    .map_or(txt.clone(), |target| match r.dst_ext_def_id {
+                        Some(id) => format!("\\hyperlink{{{target}-{id}}}{{{txt}}}"),
+                        None => format!("\\hyperlink{{{target}}}{{{txt}}}"),
+                    })
+            })
+            .join(", ");
+        let _ = write!(out, " \\taglabel{{{}}} {links}", reference_label(ref_type));
+    }
+}
+
+fn format_entry(entry: &PrintEntry, target: &str, targets: &HashMap<(&str, &str), String>) -> String {
+    let word = entry.word;
+    let mut out = format!(
+        "\\entry{{{}}}{{{target}}}{{{}}}{}",
+        entry.guide_word,
+        format_headword(&word.trad, &word.simp),
+        format_tags(&word.tags)
+    );
+    write_references(&mut out, &word.references, targets);
+    if let Some(note) = &word.note {
+        let _ = write!(out, " \\note{{{}}}", escape(&note.note));
+    }
+
+    for (_, pron_group) in &entry
+        .definitions
+        .iter()
+        .chunk_by(|d| d.prons.iter().map(|p| &p.pinyin_num).collect_vec())
+    {
+        let pron_group: Vec<_> = pron_group.collect();
+        let prons = pron_group[0]
+            .prons
+            .iter()
+            .map(|p| format!("{}{}", escape(&p.pinyin_mark), format_tags(&p.tags)))
+            .join(", ");
+        let _ = write!(out, " \\pron{{{prons}}}");
+        for (class_name, class_group) in &pron_group.into_iter().chunk_by(|d| &d.class_name) {
+            let _ = write!(out, " \\class{{{}}}", escape(class_label(class_name)));
+            for def in class_group {
+                // one marker per level of a sub-definition
+                let _ = write!(
+                    out,
+                    " {}\\hypertarget{{{target}-{id}}}{{}}\\textbf{{{id}}}~{}{}",
+                    "\\subdef".repeat(word.depth(def)),
+                    escape(&def.definition),
+                    format_tags(&def.tags),
+                    id = def.ext_def_id,
+                );
+                write_references(&mut out, &def.references, targets);
+                if let Some(note) = &def.note {
+                    let _ = write!(out, " \\note{{{}}}", escape(&note.note));
+                }
+                out.push(';');
+            }
+        }
+    }
+    if out.ends_with(';') {
+        out.pop();
+    }
+    out
+}
+
+/// Write the dictionary as LaTeX document
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_latex(
+    writer: &mut dyn Write,
+    entries: &[WordEntry],
+    options: &LatexOptions,
+) -> io::Result<()> {
+    let print_entries = filter_entries(entries, options);
+    // hyperlink targets only for printed words
+    let targets: HashMap<(&str, &str), String> = print_entries
+        .iter()
+        .enumerate()
+        .map(|(idx, e)| ((e.word.trad.as_str(), e.word.simp.as_str()), format!("w{idx}")))
+        .collect();
+
+    write!(writer, "{PREAMBLE}")?;
+    let mut last_section = "";
+    for entry in &print_entries {
+        if entry.section != last_section {
+            last_section = &entry.section;
+            writeln!(writer, "\n\\dictsection{{{}}}\n", escape(last_section))?;
+        }
+        let target = &targets[&(entry.word.trad.as_str(), entry.word.simp.as_str())];
+        writeln!(writer, "{}\n", format_entry(entry, target, &targets))?;
+    }
+    write!(writer, "{POSTAMBLE}")?;
+    Ok(())
+}
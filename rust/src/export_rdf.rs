@@ -0,0 +1,367 @@
+// Export as linked data in Turtle format, following OntoLex-Lemon (core, vartrans, lexicog and lime modules).
+//
+// IRIs are minted from the word and the ext_def_id, which are stable across versions:
+//   <base>entry/{trad}/{simp}       ontolex:LexicalEntry
+//   <base>entry/{trad}/{simp}#D{n}  ontolex:LexicalSense
+// References become vartrans:SenseRelation (definition to definition) or vartrans:LexicalRelation (otherwise),
+// the category is picked based on dict_ref_type.type.
+// Redirects keep the IRIs of removed definitions: dct:isReplacedBy for moved ones, fmld:deleted with the date otherwise.
+// Sub-definitions are lexicog components nested in the component of their parent definition.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::db_model::{
+    DefinitionEntry, NoteInfo, RedirectEntry, RedirectTarget, ReferenceEntry, TagInfo, WordEntry,
+};
+
+pub const DEFAULT_BASE_IRI: &str = "https://w3id.org/fmld/";
+
+const PREFIXES: &str = r"@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+@prefix skos: <http://www.w3.org/2004/02/skos/core#> .
+@prefix dct: <http://purl.org/dc/terms/> .
+@prefix ontolex: <http://www.w3.org/ns/lemon/ontolex#> .
+@prefix vartrans: <http://www.w3.org/ns/lemon/vartrans#> .
+@prefix lexicog: <http://www.w3.org/ns/lemon/lexicog#> .
+@prefix lime: <http://www.w3.org/ns/lemon/lime#> .
+@prefix lexinfo: <http://www.lexinfo.net/ontology/3.0/lexinfo#> .
+";
+
+/// Get the relation category for the full reference type name (`dict_ref_type.type`)
+/// Types without a matching `LexInfo` category use the fmld vocabulary
+fn ref_type_category(ref_type: &str) -> Option<&'static str> {
+    Some(match ref_type {
+        "synonym-equal" => "lexinfo:exactSynonym",
+        "synonym-similar" => "lexinfo:approximateSynonym",
+        "antonym" => "lexinfo:antonym",
+        "could-be-confused-with" => "fmld:couldBeConfusedWith",
+        "part-of" => "fmld:partOf",
+        "contains" => "fmld:contains",
+        "word-variant-of" => "fmld:wordVariantOf",
+        "character-variant-of" => "fmld:characterVariantOf",
+        "used-with-measure-word" => "fmld:usedWithMeasureWord",
+        "collocation" => "fmld:collocation",
+        "word-group" => "fmld:wordGroup",
+        _ => return None,
+    })
+}
+
+/// Get the `LexInfo` part of speech for the class name (`dict_class.name`)
+fn class_part_of_speech(class_name: &str) -> Option<&'static str> {
+    Some(match class_name {
+        "noun" => "lexinfo:noun",
+        "name" => "lexinfo:properNoun",
+        "verb" => "lexinfo:verb",
+        "adj" | "adjective" => "lexinfo:adjective",
+        "adv" | "adverb" => "lexinfo:adverb",
+        "pron" | "pronoun" => "lexinfo:pronoun",
+        "num" | "numeral" => "lexinfo:numeral",
+        "classifier" => "lexinfo:classifier",
+        "particle" => "lexinfo:particle",
+        "prep" | "preposition" => "lexinfo:preposition",
+        "conj" | "conjunction" => "lexinfo:conjunction",
+        "intj" | "interjection" => "lexinfo:interjection",
+        "phrase" => "lexinfo:phraseologicalUnit",
+        "idiom" => "lexinfo:idiom",
+        "proverb" => "lexinfo:proverb",
+        _ => return None,
+    })
+}
+
+/// Percent-encode everything which is not allowed or not desirable in the path of an IRI.
+/// Non-ASCII characters are kept as they are, which is allowed for IRIs.
+fn encode_iri_segment(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || "-._~".contains(c) || (!c.is_ascii() && !c.is_whitespace())
+        {
+            encoded.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(encoded, "%{b:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn entry_iri(base_iri: &str, trad: &str, simp: &str) -> String {
+    format!(
+        "<{base_iri}entry/{}/{}>",
+        encode_iri_segment(trad),
+        encode_iri_segment(simp)
+    )
+}
+
+fn sense_iri(base_iri: &str, trad: &str, simp: &str, ext_def_id: u32) -> String {
+    format!(
+        "<{base_iri}entry/{}/{}#D{ext_def_id}>",
+        encode_iri_segment(trad),
+        encode_iri_segment(simp)
+    )
+}
+
+fn literal(s: &str, lang: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2 + lang.len());
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    if !lang.is_empty() {
+        escaped.push('@');
+        escaped.push_str(lang);
+    }
+    escaped
+}
+
+/// Write the dictionary as OntoLex-Lemon Turtle
+///
+/// # Errors
+/// Returns an error if writing fails
+pub fn write_rdf(
+    writer: &mut dyn Write,
+    entries: &[WordEntry],
+    redirects: &[RedirectEntry],
+    base_iri: &str,
+) -> io::Result<()> {
+    writeln!(writer, "{PREFIXES}@prefix fmld: <{base_iri}ontology#> .")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "<{base_iri}lexicon> a lime:Lexicon ;\n    lime:language \"zh\" ;\n    dct:title {} .",
+        literal("Free Mandarin Learner's Dictionary", "en")
+    )?;
+    writeln!(
+        writer,
+        "<{base_iri}dictionary> a lexicog:LexicographicResource ;\n    lexicog:describes <{base_iri}lexicon> ."
+    )?;
+    writeln!(writer)?;
+
+    // words without definitions (e.g. with redirects only) are not lexical entries
+    let entries = entries.iter().filter(|e| !e.definitions.is_empty());
+    for (idx, entry) in entries.enumerate() {
+        write_entry(writer, entry, base_iri)?;
+        // the lexicographic resource preserves the order of the entries
+        writeln!(
+            writer,
+            "<{base_iri}dictionary> lexicog:entry <{base_iri}component/{}> .",
+            idx + 1
+        )?;
+        write!(
+            writer,
+            "<{base_iri}component/{}> a lexicog:Entry ;\n    lexicog:describes {}",
+            idx + 1,
+            entry_iri(base_iri, &entry.trad, &entry.simp)
+        )?;
+        let top_level = entry
+            .definitions
+            .iter()
+            .filter(|d| d.parent_ext_def_id.is_none());
+        for (def_idx, def) in top_level.enumerate() {
+            write!(
+                writer,
+                " ;\n    rdf:_{} {}",
+                def_idx + 1,
+                sense_component(base_iri, entry, def)
+            )?;
+        }
+        writeln!(writer, " .")?;
+        writeln!(writer)?;
+    }
+    for redirect in redirects {
+        write_redirect(writer, base_iri, redirect)?;
+    }
+    Ok(())
+}
+
+/// Component describing a sense, with the components of its sub-definitions as members
+fn sense_component(base_iri: &str, entry: &WordEntry, definition: &DefinitionEntry) -> String {
+    let mut component = format!(
+        "[ a lexicog:LexicographicComponent ; lexicog:describes {}",
+        sense_iri(base_iri, &entry.trad, &entry.simp, definition.ext_def_id)
+    );
+    let children = entry
+        .definitions
+        .iter()
+        .filter(|d| d.parent_ext_def_id == Some(definition.ext_def_id));
+    for (idx, child) in children.enumerate() {
+        let _ = write!(
+            component,
+            " ; rdf:_{} {}",
+            idx + 1,
+            sense_component(base_iri, entry, child)
+        );
+    }
+    component.push_str(" ]");
+    component
+}
+
+fn write_redirect(
+    writer: &mut dyn Write,
+    base_iri: &str,
+    redirect: &RedirectEntry,
+) -> io::Result<()> {
+    let src = sense_iri(
+        base_iri,
+        &redirect.trad,
+        &redirect.simp,
+        redirect.ext_def_id,
+    );
+    match &redirect.target {
+        RedirectTarget::Moved {
+            trad,
+            simp,
+            ext_def_id,
+        } => writeln!(
+            writer,
+            "{src} dct:isReplacedBy {} .",
+            sense_iri(base_iri, trad, simp, *ext_def_id)
+        ),
+        RedirectTarget::Deleted { date } => {
+            writeln!(
+                writer,
+                "{src} fmld:deleted {}^^xsd:date .",
+                literal(date, "")
+            )
+        }
+    }
+}
+
+fn write_entry(writer: &mut dyn Write, entry: &WordEntry, base_iri: &str) -> io::Result<()> {
+    let entry_iri = entry_iri(base_iri, &entry.trad, &entry.simp);
+    writeln!(writer, "<{base_iri}lexicon> lime:entry {entry_iri} .")?;
+
+    let mut lines = vec!["a ontolex:LexicalEntry".to_owned()];
+    lines.push(format!(
+        "rdfs:label {}, {}",
+        literal(&entry.trad, "zh-Hant"),
+        literal(&entry.simp, "zh-Hans")
+    ));
+
+    // pronunciations and classes are properties of the entry in OntoLex, collect them from all definitions
+    let mut phonetic_reps: Vec<String> = vec![];
+    let mut parts_of_speech: Vec<String> = vec![];
+    for def in &entry.definitions {
+        for pron in &def.prons {
+            let rep = literal(&pron.pinyin_mark, "zh-Latn-pinyin");
+            if !phonetic_reps.contains(&rep) {
+                phonetic_reps.push(rep);
+            }
+        }
+        let pos = class_part_of_speech(&def.class_name).map_or_else(
+            || format!("fmld:class {}", literal(&def.class_name, "")),
+            |p| format!("lexinfo:partOfSpeech {p}"),
+        );
+        if !parts_of_speech.contains(&pos) {
+            parts_of_speech.push(pos);
+        }
+    }
+    let mut form = format!(
+        "ontolex:canonicalForm [ a ontolex:Form ; ontolex:writtenRep {}, {}",
+        literal(&entry.trad, "zh-Hant"),
+        literal(&entry.simp, "zh-Hans")
+    );
+    if !phonetic_reps.is_empty() {
+        let _ = write!(form, " ; ontolex:phoneticRep {}", phonetic_reps.join(", "));
+    }
+    form.push_str(" ]");
+    lines.push(form);
+    lines.extend(parts_of_speech);
+    lines.extend(tag_lines(&entry.tags));
+    lines.extend(note_lines(entry.note.as_ref()));
+    for def in &entry.definitions {
+        lines.push(format!(
+            "ontolex:sense {}",
+            sense_iri(base_iri, &entry.trad, &entry.simp, def.ext_def_id)
+        ));
+    }
+    write_subject(writer, &entry_iri, &lines)?;
+
+    for def in &entry.definitions {
+        let sense_iri = sense_iri(base_iri, &entry.trad, &entry.simp, def.ext_def_id);
+        let mut lines = vec![
+            "a ontolex:LexicalSense".to_owned(),
+            format!("dct:identifier {}", literal(&format!("D{}", def.ext_def_id), "")),
+            format!("skos:definition {}", literal(&def.definition, "en")),
+        ];
+        lines.extend(tag_lines(&def.tags));
+        lines.extend(note_lines(def.note.as_ref()));
+        write_subject(writer, &sense_iri, &lines)?;
+        for reference in &def.references {
+            write_reference(writer, base_iri, &sense_iri, &entry_iri, reference)?;
+        }
+    }
+    for reference in &entry.references {
+        write_reference(writer, base_iri, &entry_iri, &entry_iri, reference)?;
+    }
+    Ok(())
+}
+
+fn tag_lines(tags: &[TagInfo]) -> Vec<String> {
+    tags.iter()
+        .map(|t| format!("fmld:tag {}", literal(&t.tag, "")))
+        .collect()
+}
+
+fn note_lines(note: Option<&NoteInfo>) -> Vec<String> {
+    note.map(|n| format!("skos:note {}", literal(&n.note, "en")))
+        .into_iter()
+        .collect()
+}
+
+fn write_subject(writer: &mut dyn Write, subject: &str, lines: &[String]) -> io::Result<()> {
+    writeln!(writer, "{subject} {} .", lines.join(" ;\n    "))
+}
+
+/// Write a reified relation, sense relations are only possible if both sides are definitions
+fn write_reference(
+    writer: &mut dyn Write,
+    base_iri: &str,
+    src_iri: &str,
+    src_entry_iri: &str,
+    reference: &ReferenceEntry,
+) -> io::Result<()> {
+    let dst_entry_iri = entry_iri(base_iri, &reference.dst_trad, &reference.dst_simp);
+    let is_src_sense = src_iri != src_entry_iri;
+    let (relation_type, src, dst) = match (is_src_sense, reference.dst_ext_def_id) {
+        (true, Some(dst_ext_def_id)) => (
+            "vartrans:SenseRelation",
+            src_iri.to_owned(),
+            sense_iri(
+                base_iri,
+                &reference.dst_trad,
+                &reference.dst_simp,
+                dst_ext_def_id,
+            ),
+        ),
+        _ => (
+            "vartrans:LexicalRelation",
+            src_entry_iri.to_owned(),
+            dst_entry_iri,
+        ),
+    };
+    let category = ref_type_category(&reference.ref_type).map_or_else(
+        || format!("fmld:{}", encode_iri_segment(&reference.ref_type)),
+        ToOwned::to_owned,
+    );
+    let mut lines = vec![
+        format!("a {relation_type}"),
+        format!("vartrans:source {src}"),
+        format!("vartrans:target {dst}"),
+        format!("vartrans:category {category}"),
+    ];
+    lines.extend(tag_lines(&reference.tags));
+    lines.extend(note_lines(reference.note.as_ref()));
+    writeln!(writer, "[] {} .", lines.join(" ;\n    "))
+}
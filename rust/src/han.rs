@@ -0,0 +1,127 @@
+// Han characters (Unicode script Han) and the compatibility ideographs, which look like unified ideographs but are
+// different code points
+
+use std::collections::HashMap;
+
+/// Code point ranges of the Unicode script Han (`Scripts.txt`, Unicode 16.0) with the name of the block
+static HAN_RANGES: &[(u32, u32, &str)] = &[
+    (0x2E80, 0x2E99, "CJK Radicals Supplement"),
+    (0x2E9B, 0x2EF3, "CJK Radicals Supplement"),
+    (0x2F00, 0x2FD5, "Kangxi Radicals"),
+    (0x3005, 0x3005, "CJK Symbols and Punctuation"), // ideographic iteration mark
+    (0x3007, 0x3007, "CJK Symbols and Punctuation"), // ideographic number zero
+    (0x3021, 0x3029, "CJK Symbols and Punctuation"), // Hangzhou numerals
+    (0x3038, 0x303B, "CJK Symbols and Punctuation"), // Hangzhou numerals, vertical iteration mark
+    (0x3400, 0x4DBF, "CJK Unified Ideographs Extension A"),
+    (0x4E00, 0x9FFF, "CJK Unified Ideographs"),
+    (0xF900, 0xFA6D, "CJK Compatibility Ideographs"),
+    (0xFA70, 0xFAD9, "CJK Compatibility Ideographs"),
+    (0x16FE2, 0x16FE3, "Ideographic Symbols and Punctuation"),
+    (0x16FF0, 0x16FF1, "Ideographic Symbols and Punctuation"),
+    (0x20000, 0x2A6DF, "CJK Unified Ideographs Extension B"),
+    (0x2A700, 0x2B739, "CJK Unified Ideographs Extension C"),
+    (0x2B740, 0x2B81D, "CJK Unified Ideographs Extension D"),
+    (0x2B820, 0x2CEA1, "CJK Unified Ideographs Extension E"),
+    (0x2CEB0, 0x2EBE0, "CJK Unified Ideographs Extension F"),
+    (0x2EBF0, 0x2EE5D, "CJK Unified Ideographs Extension I"),
+    (0x2F800, 0x2FA1D, "CJK Compatibility Ideographs Supplement"),
+    (0x30000, 0x3134A, "CJK Unified Ideographs Extension G"),
+    (0x31350, 0x323AF, "CJK Unified Ideographs Extension H"),
+];
+
+/// Compatibility ideographs and their canonical equivalents, generated from `UnicodeData.txt`
+static COMPATIBILITY_IDEOGRAPHS: &str = include_str!("../data/cjk_compatibility_ideographs.txt");
+
+/// Block of a Han character, None for other characters
+#[must_use]
+pub fn han_block(c: char) -> Option<&'static str> {
+    let cp = u32::from(c);
+    HAN_RANGES
+        .iter()
+        .find(|(first, last, _)| (*first..=*last).contains(&cp))
+        .map(|r| r.2)
+}
+
+#[must_use]
+pub fn is_han(c: char) -> bool {
+    han_block(c).is_some()
+}
+
+/// Only words which consist of Chinese characters, e.g. no letters or punctuation
+#[must_use]
+pub fn is_han_only(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(is_han)
+}
+
+/// Unified ideographs for the compatibility ideographs (e.g. U+F900 for U+8C48 豈), the twelve unified ideographs
+/// in the block of the compatibility ideographs (e.g. U+FA11 﨑) are not included
+#[must_use]
+pub fn compatibility_ideographs() -> HashMap<char, char> {
+    COMPATIBILITY_IDEOGRAPHS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (compatibility, unified) = line.split_once('\t')?;
+            Some((parse_code_point(compatibility)?, parse_code_point(unified)?))
+        })
+        .collect()
+}
+
+/// Full-width form of ASCII punctuation, as used between Chinese characters, e.g. ， for ,
+#[must_use]
+pub fn full_width_punctuation(c: char) -> Option<char> {
+    match c {
+        '.' => Some('。'),
+        ',' | '!' | '?' | ':' | ';' | '(' | ')' => char::from_u32(u32::from(c) + 0xFEE0),
+        _ => None,
+    }
+}
+
+/// Character of a code point in the notation of the Unihan database, e.g. U+4E2D for 中
+#[must_use]
+pub fn parse_code_point(code_point: &str) -> Option<char> {
+    code_point
+        .strip_prefix("U+")
+        .and_then(|h| u32::from_str_radix(h, 16).ok())
+        .and_then(char::from_u32)
+}
+
+/// Half-width form of full-width letters and digits, e.g. A for Ａ
+#[must_use]
+pub fn half_width_alphanumeric(c: char) -> Option<char> {
+    char::from_u32(u32::from(c).checked_sub(0xFEE0)?).filter(char::is_ascii_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_han() {
+        for han in ['中', '〇', '㐀', '鿿', '𠀀', '𪜀', '𰀀', '𱍐', '𮯰', '豈'] {
+            assert!(is_han(han), "{han}");
+        }
+        for other in ['a', 'の', '，', '·', '\u{A000}'] {
+            assert!(!is_han(other), "{other}");
+        }
+        assert!(is_han_only("中國"));
+        assert!(!is_han_only("卡拉OK"));
+        assert!(!is_han_only(""));
+        assert_eq!(han_block('𱍐'), Some("CJK Unified Ideographs Extension H"));
+
+        let compatibility = compatibility_ideographs();
+        assert_eq!(compatibility.len(), 1002);
+        assert_eq!(compatibility.get(&'\u{F900}'), Some(&'\u{8C48}'));
+        assert_eq!(compatibility.get(&'\u{FA11}'), None);
+
+        assert_eq!(full_width_punctuation(','), Some('，'));
+        assert_eq!(full_width_punctuation('.'), Some('。'));
+        assert_eq!(full_width_punctuation('-'), None);
+        assert_eq!(half_width_alphanumeric('Ａ'), Some('A'));
+        assert_eq!(half_width_alphanumeric('９'), Some('9'));
+        assert_eq!(half_width_alphanumeric('，'), None);
+
+        assert_eq!(parse_code_point("U+4E2D"), Some('中'));
+        assert_eq!(parse_code_point("4E2D"), None);
+    }
+}
@@ -1,8 +1,18 @@
+pub mod changelog;
 mod common;
 mod config;
 pub mod db_check;
+pub mod db_diff;
 pub mod db_edit;
+pub mod db_migrate;
+pub mod db_model;
 pub mod db_to_txt;
+pub mod export_dsl;
+pub mod export_ime;
+pub mod export_latex;
+pub mod export_rdf;
+pub mod han;
 pub mod pinyin;
+pub mod txt_merge;
 mod txt_parser;
 pub mod txt_to_db;
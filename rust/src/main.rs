@@ -1,15 +1,27 @@
+use fmld::changelog;
 use fmld::db_check;
+use fmld::db_diff;
 use fmld::db_edit;
+use fmld::db_migrate;
+use fmld::db_model;
 
 use fmld::db_to_txt;
+use fmld::export_dsl;
+use fmld::export_ime;
+use fmld::export_latex;
+use fmld::export_rdf;
+use fmld::txt_merge;
 use fmld::txt_to_db;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::File;
+use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::Write;
+use std::io::stdout;
 
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -20,12 +32,18 @@ use serde::{Deserialize, Serialize};
 use rusqlite::{Connection, backup};
 
 #[derive(Parser)]
+#[allow(clippy::struct_excessive_bools, reason = "command line flags")]
 #[command(name = "FMLD Tool")]
 #[command(version = "0.0.1")]
 #[command(about = "Free Mandarin Learner's Dictionary Tool", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input file, .txt or .db (sqlite)
-    input_file: PathBuf,
+    #[arg(required = true)]
+    input_file: Option<PathBuf>,
 
     /// Output as .db file (sqlite)
     #[arg(short, long)]
@@ -39,6 +57,139 @@ struct Cli {
     #[arg(long)]
     finalize_with_meta: Option<PathBuf>,
 
+    /// Output as .ttl file (RDF Turtle, OntoLex-Lemon)
+    #[arg(long)]
+    rdf: Option<PathBuf>,
+
+    /// Base IRI for the entries and senses in the RDF output
+    #[arg(long, default_value = export_rdf::DEFAULT_BASE_IRI)]
+    rdf_base_iri: String,
+
+    /// Output as .dsl file (`GoldenDict`, ABBYY Lingvo)
+    #[arg(long)]
+    dsl: Option<PathBuf>,
+
+    /// Output as Rime input method dictionary (name.dict.yaml)
+    #[arg(long)]
+    rime: Option<PathBuf>,
+
+    /// Output as fcitx5 input method dictionary (libime text format)
+    #[arg(long)]
+    fcitx: Option<PathBuf>,
+
+    /// Output as .tex file (LaTeX source for a printed dictionary, compile with xelatex)
+    #[arg(long)]
+    latex: Option<PathBuf>,
+
+    /// Order of the entries in the LaTeX output
+    #[arg(long, value_enum, default_value_t = LatexOrder::Pinyin)]
+    latex_order: LatexOrder,
+
+    /// Relevance of the definitions to include in the LaTeX output
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Relevance::High, Relevance::Untagged, Relevance::Low])]
+    latex_relevance: Vec<Relevance>,
+
+    /// Unihan file with kRSUnicode data (`Unihan_IRGSources.txt`), needed to order by radical
+    #[arg(long)]
+    unihan_radicals: Option<PathBuf>,
+
+    /// Reference release (.txt or .db) for the check stable-ids: definition and note ids must be stable compared
+    /// to it
+    #[arg(long)]
+    stable_ids: Option<PathBuf>,
+
+    /// Minimum similarity (0.0 to 1.0) of definitions and notes with the same id as in the reference release
+    #[arg(long, default_value_t = 0.5)]
+    min_similarity: f64,
+
+    /// Enable a check which is disabled by default or in the check config (see `list-checks`)
+    #[arg(long, value_name = "ID")]
+    enable_check: Vec<String>,
+
+    /// Disable a check
+    #[arg(long, value_name = "ID")]
+    disable_check: Vec<String>,
+
+    /// Severity of the findings of a check, overrides the default of the check (warnings don't let the run fail)
+    #[arg(long, value_name = "ID=warning|error")]
+    check_severity: Vec<String>,
+
+    /// JSON file with the checks to enable and disable and their severity:
+    /// {"enable": [...], "disable": [...], "severity": {"ID": "warning"}}
+    #[arg(long)]
+    check_config: Option<PathBuf>,
+
+    /// JSON file with known findings, which are not reported and don't let the run fail
+    #[arg(long)]
+    baseline: Option<PathBuf>,
+
+    /// Write all findings of the checks to a JSON file, to be used with --baseline
+    #[arg(long)]
+    write_baseline: Option<PathBuf>,
+
+    /// Apply the fixes of the checks (see `list-checks`) before the output is written, every change is reported
+    #[arg(long)]
+    fix: bool,
+
+    /// Split definitions concatenated with --- into sub-definitions, every change is reported
+    #[arg(long)]
+    split_definitions: bool,
+
+    /// Move usage labels in parentheses of the definitions to tags, e.g. "(chiefly Taiwan)" to t, every change is
+    /// reported
+    #[arg(long)]
+    move_usage_labels: bool,
+
+    /// JSON file with the usage labels to move and their ascii tags, replaces the defaults:
+    /// {"pattern": "t"}, patterns are case-insensitive regular expressions for the whole label
+    #[arg(long, requires = "move_usage_labels")]
+    usage_labels: Option<PathBuf>,
+
+    /// Word frequency list (e.g. SUBTLEX-CH, a word and its count per line), the frequency ranks of the words are
+    /// stored in the database and words without a relevance tag are tagged by rank with the source tag f, tags with
+    /// the source tag f are updated on every run, other relevance tags are kept, every change is reported
+    #[arg(long)]
+    frequency_list: Option<PathBuf>,
+
+    /// Frequency ranks for the relevance tags: up to HIGH +, after LOW - and after LOWEST x, words in between and
+    /// words which are not in the list are not tagged
+    #[arg(long, value_name = "HIGH,LOW,LOWEST", value_delimiter = ',', default_values_t = [5000, 20000, 50000], requires = "frequency_list")]
+    relevance_thresholds: Vec<u32>,
+
+    /// Table of the readings in Taiwan and mainland China (word, Taiwan readings and mainland readings separated
+    /// by tabs, several readings separated by ;, the standard reading first), the pronunciations are tagged with
+    /// T, t, C or c, every change and every reading missing in the dictionary is reported
+    #[arg(long)]
+    regional_readings: Option<PathBuf>,
+
+    /// Unihan file with the character variants (`Unihan_Variants.txt`), character variant references are added
+    /// between single-character words with the tag u, every change is reported
+    #[arg(long)]
+    unihan_variants: Option<PathBuf>,
+
+    /// Add contains references (>) from the words to the words they are composed of and the inverse part-of
+    /// references (<) with the tag s, every change is reported
+    #[arg(long)]
+    add_compositions: bool,
+
+    /// Minimum number of characters of the components of --add-compositions
+    #[arg(long, default_value_t = 2, requires = "add_compositions")]
+    composition_min_length: usize,
+
+    /// Only use components without a low relevance tag (-, x) for --add-compositions
+    #[arg(long, requires = "add_compositions")]
+    composition_relevant_only: bool,
+
+    /// Only report the changes of --split-definitions, --move-usage-labels, --frequency-list,
+    /// --regional-readings, --unihan-variants, --add-compositions and --fix, nothing is changed
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Traditional to simplified character table (`OpenCC` `TSCharacters.txt` or `Unihan_Variants.txt`),
+    /// replaces the bundled table of common characters
+    #[arg(long)]
+    trad_simp_table: Option<PathBuf>,
+
     /// Limit input or output in text format to all entries up to the provided word
     #[arg(short, long)]
     limit_to_word: Option<String>,
@@ -52,6 +203,91 @@ struct Cli {
     round_trip_check: Option<PathBuf>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Compare two versions of the dictionary entity by entity (words, definitions, notes, ...)
+    Diff {
+        /// Old version, .txt or .db (sqlite)
+        old: PathBuf,
+
+        /// New version, .txt or .db (sqlite)
+        new: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+
+        /// Write the diff to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write a Markdown changelog for a release, e.g. from the previous release .db to the current .txt
+    Changelog {
+        /// Previous version, .txt or .db (sqlite)
+        old: PathBuf,
+
+        /// Current version, .txt or .db (sqlite)
+        new: PathBuf,
+
+        /// Title of the changelog
+        #[arg(long, default_value = "Changelog")]
+        title: String,
+
+        /// Write the changelog to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Three-way merge for git (merge driver), the result is written to the file of our version
+    ///
+    /// Configure with `git config merge.fmld.driver "fmld merge-driver %O %A %B"` and
+    /// `dict/fmld.en.txt merge=fmld` in .gitattributes.
+    MergeDriver {
+        /// Common ancestor (%O)
+        base: PathBuf,
+
+        /// Our version (%A), replaced by the merged version
+        ours: PathBuf,
+
+        /// Their version (%B)
+        theirs: PathBuf,
+    },
+    /// List the available checks with their default severity
+    ListChecks,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LatexOrder {
+    Pinyin,
+    Radical,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Relevance {
+    High,
+    Untagged,
+    Low,
+    Lowest,
+    Deleted,
+}
+
+impl Relevance {
+    const fn ascii_tag(self) -> Option<char> {
+        match self {
+            Self::High => Some('+'),
+            Self::Untagged => None,
+            Self::Low => Some('-'),
+            Self::Lowest => Some('x'),
+            Self::Deleted => Some('X'),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct DictMeta {
     #[serde(default)]
@@ -87,6 +323,7 @@ fn read_input(path: &Path, limit_to_word: Option<&str>) -> anyhow::Result<DictDb
                 let backup = backup::Backup::new(&input_conn, &mut conn)?;
                 backup.run_to_completion(4000, Duration::new(0, 0), None)?;
             }
+            db_migrate::migrate(&conn).context(format!("Could not read {}", path.display()))?;
             Ok(DictDb {
                 source: DbSource::Db,
                 conn,
@@ -106,16 +343,31 @@ fn read_input(path: &Path, limit_to_word: Option<&str>) -> anyhow::Result<DictDb
     }
 }
 
+fn create_output_file(path_out: &Path, cli: &Cli) -> anyhow::Result<BufWriter<File>> {
+    if Some(path_out) == cli.input_file.as_deref() {
+        bail!("Input file and output file must be different");
+    }
+    let file_out = File::create(path_out).context(format!(
+        "Could not create output file {}",
+        path_out.display()
+    ))?;
+    Ok(BufWriter::new(file_out))
+}
+
+/// Load the entries for the exporters only once, and only if needed
+fn word_entries<'a>(
+    entries: &'a mut Option<Vec<db_model::WordEntry>>,
+    conn: &Connection,
+) -> anyhow::Result<&'a [db_model::WordEntry]> {
+    if entries.is_none() {
+        *entries = Some(db_model::load_word_entries(conn)?);
+    }
+    Ok(entries.as_deref().unwrap_or_default())
+}
+
 fn write_output(db_source: &DictDb, cli: &Cli) -> anyhow::Result<()> {
     if let Some(path_out) = &cli.txt {
-        if *path_out == cli.input_file {
-            bail!("Input file and output file must be different");
-        }
-        let file_out = File::create(path_out).context(format!(
-            "Could not create output file {}",
-            path_out.display()
-        ))?;
-        let mut writer_out = BufWriter::new(file_out);
+        let mut writer_out = create_output_file(path_out, cli)?;
         db_to_txt::db_to_txt(
             &mut writer_out,
             &db_source.conn,
@@ -124,8 +376,65 @@ fn write_output(db_source: &DictDb, cli: &Cli) -> anyhow::Result<()> {
         )?;
     }
 
+    let mut entries = None;
+    if let Some(path_out) = &cli.rdf {
+        let entries = word_entries(&mut entries, &db_source.conn)?;
+        let mut writer_out = create_output_file(path_out, cli)?;
+        let redirects = db_model::load_redirects(&db_source.conn)?;
+        export_rdf::write_rdf(&mut writer_out, entries, &redirects, &cli.rdf_base_iri)?;
+    }
+
+    if let Some(path_out) = &cli.dsl {
+        let entries = word_entries(&mut entries, &db_source.conn)?;
+        let mut writer_out = create_output_file(path_out, cli)?;
+        export_dsl::write_dsl(&mut writer_out, entries)?;
+    }
+
+    if let Some(path_out) = &cli.rime {
+        let entries = word_entries(&mut entries, &db_source.conn)?;
+        let file_name = path_out
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or("fmld");
+        let name = file_name
+            .strip_suffix(".dict.yaml")
+            .or_else(|| file_name.split('.').next())
+            .unwrap_or(file_name);
+        let mut writer_out = create_output_file(path_out, cli)?;
+        export_ime::write_rime(&mut writer_out, entries, name)?;
+    }
+
+    if let Some(path_out) = &cli.fcitx {
+        let entries = word_entries(&mut entries, &db_source.conn)?;
+        let mut writer_out = create_output_file(path_out, cli)?;
+        export_ime::write_fcitx(&mut writer_out, entries)?;
+    }
+
+    if let Some(path_out) = &cli.latex {
+        let entries = word_entries(&mut entries, &db_source.conn)?;
+        let order = match cli.latex_order {
+            LatexOrder::Pinyin => export_latex::LatexOrder::Pinyin,
+            LatexOrder::Radical => export_latex::LatexOrder::Radical,
+        };
+        let radicals = if let Some(path) = &cli.unihan_radicals {
+            let file = File::open(path).context(format!("Could not open {}", path.display()))?;
+            export_latex::read_radical_table(&mut BufReader::new(file))?
+        } else if order == export_latex::LatexOrder::Radical {
+            bail!("Ordering by radical requires --unihan-radicals");
+        } else {
+            HashMap::new()
+        };
+        let options = export_latex::LatexOptions {
+            order,
+            relevance: cli.latex_relevance.iter().map(|r| r.ascii_tag()).collect(),
+            radicals,
+        };
+        let mut writer_out = create_output_file(path_out, cli)?;
+        export_latex::write_latex(&mut writer_out, entries, &options)?;
+    }
+
     if let Some(path_out) = &cli.db {
-        if *path_out == cli.input_file {
+        if Some(path_out) == cli.input_file.as_ref() {
             bail!("Input file and output file must be different");
         }
         let mut db_out = Connection::open(path_out).context(format!(
@@ -157,27 +466,11 @@ fn finalize(db_source: &mut DictDb, meta_path: &Path) -> anyhow::Result<()> {
     tx.commit()?;
 
     if let Some(mut m) = external_meta {
-        let mut stmt = db_source.conn.prepare(
-            "
-        SELECT
-            (SELECT COUNT(dict_definition.id) FROM dict_definition) AS num_defs ,
-            (SELECT COUNT(dict_word.id) FROM dict_word) AS num_words,
-            (SELECT COUNT(dict_note.id) FROM dict_note) AS num_notes,
-            (SELECT COUNT(dict_reference.id) FROM dict_reference) AS num_refs;
-        ",
-        )?;
-        let (num_words, num_defs, num_refs, num_notes) = stmt.query_row([], |row| {
-            Ok((
-                row.get("num_words")?,
-                row.get("num_defs")?,
-                row.get("num_refs")?,
-                row.get("num_notes")?,
-            ))
-        })?;
-        m.num_notes = num_notes;
-        m.num_definitions = num_defs;
-        m.num_references = num_refs;
-        m.num_words = num_words;
+        let counts = db_model::count_entries(&db_source.conn)?;
+        m.num_notes = counts.num_notes;
+        m.num_definitions = counts.num_definitions;
+        m.num_references = counts.num_references;
+        m.num_words = counts.num_words;
         m.max_note_id = new_max_ext_note_id;
         let s = serde_json::to_string_pretty(&m)?;
         fs::write(meta_path, s)?;
@@ -186,34 +479,371 @@ fn finalize(db_source: &mut DictDb, meta_path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Print the parser errors of a txt input, returns false if there were any
+fn report_input_errors(db_source: &DictDb) -> bool {
+    if let DbSource::Txt(errors) = &db_source.source {
+        for err in errors {
+            eprintln!("{err}");
+        }
+        errors.is_empty()
+    } else {
+        true
+    }
+}
+
+fn add_symmetric_references(db_source: &mut DictDb) -> anyhow::Result<()> {
+    let tx = db_source.conn.transaction()?;
+    db_edit::add_missing_symmetric_references(&tx)?;
+    db_edit::add_missing_notes_and_tags_for_symmetric_references(&tx)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Apply the fixes of the checks to the findings, returns false if nothing was changed (always in a dry run)
+fn fix_findings(
+    db_source: &mut DictDb,
+    checks: &[Box<dyn db_check::Check>],
+    findings: &[db_check::Finding],
+    dry_run: bool,
+) -> anyhow::Result<bool> {
+    let tx = db_source.conn.transaction()?;
+    let fixes = db_check::apply_fixes(&tx, checks, findings)?;
+    if !dry_run {
+        tx.commit()?;
+    }
+    for fix in &fixes {
+        eprintln!("{fix}");
+    }
+    Ok(!dry_run && !fixes.is_empty())
+}
+
+fn read_usage_labels(cli: &Cli) -> anyhow::Result<db_edit::UsageLabels> {
+    let Some(path) = &cli.usage_labels else {
+        return Ok(db_edit::UsageLabels::default());
+    };
+    let s = fs::read_to_string(path)
+        .context(format!("Could not read usage labels {}", path.display()))?;
+    let labels: BTreeMap<String, char> =
+        serde_json::from_str(&s).context(format!("Invalid usage labels {}", path.display()))?;
+    db_edit::UsageLabels::new(labels.iter().map(|(pattern, tag)| (pattern.as_str(), *tag)))
+        .map_err(|e| anyhow!(e))
+}
+
+fn read_frequency_list(path: &Path) -> anyhow::Result<db_edit::FrequencyList> {
+    let file = File::open(path).context(format!("Could not open {}", path.display()))?;
+    db_edit::FrequencyList::read(&mut BufReader::new(file))
+        .context(format!("Could not read frequency list {}", path.display()))
+}
+
+fn read_regional_readings(path: &Path) -> anyhow::Result<db_edit::RegionalReadings> {
+    let file = File::open(path).context(format!("Could not open {}", path.display()))?;
+    db_edit::RegionalReadings::read(&mut BufReader::new(file)).context(format!(
+        "Could not read regional readings {}",
+        path.display()
+    ))
+}
+
+fn read_unihan_variants(path: &Path) -> anyhow::Result<db_edit::UnihanVariants> {
+    let file = File::open(path).context(format!("Could not open {}", path.display()))?;
+    db_edit::UnihanVariants::read(&mut BufReader::new(file))
+        .context(format!("Could not read Unihan variants {}", path.display()))
+}
+
+/// Edit the entries before the checks, in a dry run the changes are only reported
+fn edit_entries(db_source: &mut DictDb, cli: &Cli) -> anyhow::Result<()> {
+    let usage_labels = if cli.move_usage_labels {
+        Some(read_usage_labels(cli)?)
+    } else {
+        None
+    };
+    let frequency_list = if let Some(path) = &cli.frequency_list {
+        let [high, low, lowest] = cli.relevance_thresholds[..] else {
+            bail!("--relevance-thresholds requires three ranks");
+        };
+        let thresholds =
+            db_edit::RelevanceThresholds::new(high, low, lowest).map_err(|e| anyhow!(e))?;
+        Some((read_frequency_list(path)?, thresholds))
+    } else {
+        None
+    };
+    let regional_readings = cli
+        .regional_readings
+        .as_deref()
+        .map(read_regional_readings)
+        .transpose()?;
+    let unihan_variants = cli
+        .unihan_variants
+        .as_deref()
+        .map(read_unihan_variants)
+        .transpose()?;
+    let tx = db_source.conn.transaction()?;
+    if cli.split_definitions {
+        for change in db_edit::split_definition_paths(&tx)? {
+            eprintln!("Sub-definition: {change}");
+        }
+    }
+    if let Some(usage_labels) = &usage_labels {
+        for change in db_edit::move_usage_labels_to_tags(&tx, usage_labels)? {
+            eprintln!("Usage label: {change}");
+        }
+    }
+    if let Some((frequency_list, thresholds)) = &frequency_list {
+        for change in db_edit::tag_relevance_by_frequency(&tx, frequency_list, *thresholds)? {
+            eprintln!("Relevance: {change}");
+        }
+    }
+    if let Some(regional_readings) = &regional_readings {
+        for change in db_edit::tag_regional_pronunciations(&tx, regional_readings)? {
+            eprintln!("Pronunciation: {change}");
+        }
+    }
+    if let Some(unihan_variants) = &unihan_variants {
+        for change in db_edit::add_unihan_variant_references(&tx, unihan_variants)? {
+            eprintln!("Character variant: {change}");
+        }
+    }
+    if cli.add_compositions {
+        let options = db_edit::CompositionOptions {
+            min_length: cli.composition_min_length,
+            relevant_only: cli.composition_relevant_only,
+        };
+        for change in db_edit::add_composition_references(&tx, options)? {
+            eprintln!("Composition: {change}");
+        }
+    }
+    if !cli.dry_run {
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Read an input for the comparison with another version, symmetric references are completed on both sides
+fn read_version(path: &Path) -> anyhow::Result<DictDb> {
+    let mut db_source = read_input(path, None)?;
+    if !report_input_errors(&db_source) {
+        bail!("Could not parse {}", path.display());
+    }
+    add_symmetric_references(&mut db_source)?;
+    Ok(db_source)
+}
+
+/// Output of the commands comparing two versions, stdout if no file is provided
+fn create_report_writer(output: Option<&Path>, inputs: &[&Path]) -> anyhow::Result<Box<dyn Write>> {
+    Ok(if let Some(path_out) = output {
+        if inputs.contains(&path_out) {
+            bail!("Input file and output file must be different");
+        }
+        Box::new(BufWriter::new(File::create(path_out).context(format!(
+            "Could not create output file {}",
+            path_out.display()
+        ))?))
+    } else {
+        Box::new(stdout().lock())
+    })
+}
+
+fn diff(old: &Path, new: &Path, format: DiffFormat, output: Option<&Path>) -> anyhow::Result<()> {
+    let changes = db_diff::diff_entries(
+        &db_model::load_word_entries(&read_version(old)?.conn)?,
+        &db_model::load_word_entries(&read_version(new)?.conn)?,
+    );
+    let mut writer_out = create_report_writer(output, &[old, new])?;
+    match format {
+        DiffFormat::Text => {
+            for change in &changes {
+                writeln!(writer_out, "{change}")?;
+            }
+        }
+        DiffFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer_out, &changes)?;
+            writeln!(writer_out)?;
+        }
+    }
+    writer_out.flush()?;
+    Ok(())
+}
+
+fn write_changelog(
+    old: &Path,
+    new: &Path,
+    title: &str,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let (db_old, db_new) = (read_version(old)?, read_version(new)?);
+    let entries_new = db_model::load_word_entries(&db_new.conn)?;
+    let changes = db_diff::diff_entries(&db_model::load_word_entries(&db_old.conn)?, &entries_new);
+    let counts = (
+        &db_model::count_entries(&db_old.conn)?,
+        &db_model::count_entries(&db_new.conn)?,
+    );
+    let mut writer_out = create_report_writer(output, &[old, new])?;
+    changelog::write_changelog(&mut writer_out, title, counts, &changes, &entries_new)?;
+    writer_out.flush()?;
+    Ok(())
+}
+
+fn merge_driver(base: &Path, ours: &Path, theirs: &Path) -> anyhow::Result<()> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).context(format!("Could not read {}", path.display()))
+    };
+    let result = txt_merge::merge_txt(&read(base)?, &read(ours)?, &read(theirs)?)?;
+    fs::write(ours, &result.text).context(format!("Could not write {}", ours.display()))?;
+    for err in &result.errors {
+        eprintln!("{err}");
+    }
+    if result.conflicts > 0 {
+        bail!("Merge conflicts in {} entries", result.conflicts);
+    }
+    if !result.errors.is_empty() {
+        bail!("Merged version is not valid");
+    }
+    Ok(())
+}
+
+fn list_checks() {
+    for check in db_check::registry() {
+        let default = if check.enabled_by_default() {
+            ""
+        } else {
+            ", disabled by default"
+        };
+        let fixable = if check.is_fixable() { ", fixable" } else { "" };
+        println!(
+            "{} ({}{default}{fixable}): {}",
+            check.id(),
+            check.default_severity(),
+            check.description()
+        );
+    }
+}
+
+/// Checks with the data from the files provided on the command line
+fn checks(cli: &Cli) -> anyhow::Result<Vec<Box<dyn db_check::Check>>> {
+    let trad_simp_table = if let Some(path) = &cli.trad_simp_table {
+        let file = File::open(path).context(format!("Could not open {}", path.display()))?;
+        Some(db_check::TradSimpTable::read(&mut BufReader::new(file))?)
+    } else {
+        None
+    };
+    let stable_ids = if let Some(path) = &cli.stable_ids {
+        Some(db_check::StableIdsReference {
+            entries: db_model::load_word_entries(&read_version(path)?.conn)?,
+            min_similarity: cli.min_similarity,
+        })
+    } else {
+        None
+    };
+    Ok(db_check::registry_with_options(db_check::CheckOptions {
+        trad_simp_table,
+        stable_ids,
+    }))
+}
+
+/// Selection of the checks from the config file and the command line, which takes precedence
+fn read_check_config(
+    cli: &Cli,
+    checks: &[Box<dyn db_check::Check>],
+) -> anyhow::Result<db_check::CheckConfig> {
+    let mut config = if let Some(path) = &cli.check_config {
+        let s = fs::read_to_string(path)
+            .context(format!("Could not read check config {}", path.display()))?;
+        serde_json::from_str(&s).context(format!("Invalid check config {}", path.display()))?
+    } else {
+        db_check::CheckConfig::default()
+    };
+    config.apply(&cli.enable_check, &cli.disable_check);
+    for id_severity in &cli.check_severity {
+        let (id, severity) = id_severity.split_once('=').context(format!(
+            "Expected ID=warning|error instead of {id_severity}"
+        ))?;
+        config.severity.insert(
+            id.to_owned(),
+            severity.parse().map_err(|e: String| anyhow!(e))?,
+        );
+    }
+    let unknown = config.unknown_ids(checks);
+    if !unknown.is_empty() {
+        bail!("Unknown check: {}", unknown.join(", "));
+    }
+    Ok(config)
+}
+
+fn read_baseline(cli: &Cli) -> anyhow::Result<db_check::Baseline> {
+    let Some(path) = &cli.baseline else {
+        return Ok(db_check::Baseline::default());
+    };
+    let s =
+        fs::read_to_string(path).context(format!("Could not read baseline {}", path.display()))?;
+    serde_json::from_str(&s).context(format!("Invalid baseline {}", path.display()))
+}
+
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let mut status_ok = true;
 
-    let mut db_source = read_input(&cli.input_file, cli.limit_to_word.as_deref())?;
-    if let DbSource::Txt(errors) = &db_source.source {
-        if !errors.is_empty() {
-            status_ok = false;
-            for err in errors {
-                eprintln!("{err}");
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Diff {
+                old,
+                new,
+                format,
+                output,
+            } => diff(old, new, *format, output.as_deref()),
+            Command::Changelog {
+                old,
+                new,
+                title,
+                output,
+            } => write_changelog(old, new, title, output.as_deref()),
+            Command::MergeDriver { base, ours, theirs } => merge_driver(base, ours, theirs),
+            Command::ListChecks => {
+                list_checks();
+                Ok(())
             }
-        }
+        };
     }
 
-    let check_result = db_check::check_entries(&db_source.conn);
-    if let Ok(err_list) = check_result {
-        if !err_list.is_empty() {
+    let input_file = cli
+        .input_file
+        .as_deref()
+        .context("No input file provided")?;
+    let mut db_source = read_input(input_file, cli.limit_to_word.as_deref())?;
+    let mut status_ok = report_input_errors(&db_source);
+
+    if cli.split_definitions
+        || cli.move_usage_labels
+        || cli.frequency_list.is_some()
+        || cli.regional_readings.is_some()
+        || cli.unihan_variants.is_some()
+        || cli.add_compositions
+    {
+        edit_entries(&mut db_source, &cli)?;
+    }
+
+    let checks = checks(&cli)?;
+    let check_config = read_check_config(&cli, &checks)?;
+    let mut findings = db_check::run_checks(&db_source.conn, &checks, &check_config)?;
+    if cli.fix && fix_findings(&mut db_source, &checks, &findings, cli.dry_run)? {
+        findings = db_check::run_checks(&db_source.conn, &checks, &check_config)?;
+    }
+    let baseline = read_baseline(&cli)?;
+    for finding in findings.iter().filter(|f| !baseline.contains(f)) {
+        if finding.severity == db_check::Severity::Error {
             status_ok = false;
         }
-        for err in err_list {
-            eprintln!("{err}");
-        }
+        eprintln!("{finding}");
     }
-    let tx = db_source.conn.transaction()?;
-
-    db_edit::add_missing_symmetric_references(&tx)?;
-    db_edit::add_missing_notes_and_tags_for_symmetric_references(&tx)?;
-    tx.commit()?;
+    if !baseline.is_empty() {
+        let num_fixed = baseline.num_fixed(&findings);
+        eprintln!(
+            "Baseline: {} known findings not reported, {num_fixed} of them are fixed",
+            baseline.len() - num_fixed
+        );
+    }
+    if let Some(path) = &cli.write_baseline {
+        let writer = create_output_file(path, &cli)?;
+        serde_json::to_writer_pretty(writer, &db_check::Baseline::from_findings(&findings))?;
+    }
+    add_symmetric_references(&mut db_source)?;
 
     if let Some(meta_path) = &cli.finalize_with_meta {
         finalize(&mut db_source, meta_path)?;
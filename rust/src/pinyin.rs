@@ -1,3 +1,5 @@
+use crate::common;
+
 #[must_use]
 pub fn pinyin_mark_from_num(pinyin_num: &str) -> String {
     // TODO currently no unicode normalization for ê and
@@ -23,6 +25,175 @@ pub fn count_syllables(pinyin_num: &str) -> usize {
     pinyin_num.chars().filter(|c| pattern.contains(c)).count()
 }
 
+/// Valid Mandarin syllables without tone, ü is written as v like in the pinyin of the dictionary
+static SYLLABLES: &[&str] = &[
+    // zero initial, interjections and erhua
+    "a", "o", "e", "ê", "ai", "ei", "ao", "ou", "an", "en", "ang", "eng", "er", "yi", "ya", "yo",
+    "ye", "yai", "yao", "you", "yan", "yin", "yang", "ying", "yong", "wu", "wa", "wo", "wai",
+    "wei", "wan", "wen", "wang", "weng", "yu", "yue", "yuan", "yun", "m", "n", "ng", "hm", "hng",
+    "r", //
+    "ba", "bo", "bai", "bei", "bao", "ban", "ben", "bang", "beng", "bi", "bie", "biao", "bian",
+    "bin", "bing", "biang", "bu", //
+    "pa", "po", "pai", "pei", "pao", "pou", "pan", "pen", "pang", "peng", "pi", "pie", "piao",
+    "pian", "pin", "ping", "pu", //
+    "ma", "mo", "me", "mai", "mei", "mao", "mou", "man", "men", "mang", "meng", "mi", "mie",
+    "miao", "miu", "mian", "min", "ming", "mu", //
+    "fa", "fo", "fei", "fou", "fan", "fen", "fang", "feng", "fiao", "fu", //
+    "da", "de", "dai", "dei", "dao", "dou", "dan", "den", "dang", "deng", "dong", "di", "dia",
+    "die", "diao", "diu", "dian", "ding", "du", "duo", "dui", "duan", "dun", //
+    "ta", "te", "tai", "tei", "tao", "tou", "tan", "tang", "teng", "tong", "ti", "tie", "tiao",
+    "tian", "ting", "tu", "tuo", "tui", "tuan", "tun", //
+    "na", "ne", "nai", "nei", "nao", "nou", "nan", "nen", "nang", "neng", "nong", "ni", "nie",
+    "niao", "niu", "nian", "nin", "niang", "ning", "nu", "nuo", "nuan", "nun", "nv", "nve", //
+    "la", "lo", "le", "lai", "lei", "lao", "lou", "lan", "lang", "leng", "long", "li", "lia",
+    "lie", "liao", "liu", "lian", "lin", "liang", "ling", "lu", "luo", "luan", "lun", "lv",
+    "lve", //
+    "ga", "ge", "gai", "gei", "gao", "gou", "gan", "gen", "gang", "geng", "gong", "gu", "gua",
+    "guo", "guai", "gui", "guan", "gun", "guang", //
+    "ka", "ke", "kai", "kei", "kao", "kou", "kan", "ken", "kang", "keng", "kong", "ku", "kua",
+    "kuo", "kuai", "kui", "kuan", "kun", "kuang", //
+    "ha", "he", "hai", "hei", "hao", "hou", "han", "hen", "hang", "heng", "hong", "hu", "hua",
+    "huo", "huai", "hui", "huan", "hun", "huang", //
+    "ji", "jia", "jie", "jiao", "jiu", "jian", "jin", "jiang", "jing", "jiong", "ju", "jue",
+    "juan", "jun", //
+    "qi", "qia", "qie", "qiao", "qiu", "qian", "qin", "qiang", "qing", "qiong", "qu", "que",
+    "quan", "qun", //
+    "xi", "xia", "xie", "xiao", "xiu", "xian", "xin", "xiang", "xing", "xiong", "xu", "xue",
+    "xuan", "xun", //
+    "zha", "zhe", "zhi", "zhai", "zhei", "zhao", "zhou", "zhan", "zhen", "zhang", "zheng", "zhong",
+    "zhu", "zhua", "zhuo", "zhuai", "zhui", "zhuan", "zhun", "zhuang", //
+    "cha", "che", "chi", "chai", "chao", "chou", "chan", "chen", "chang", "cheng", "chong", "chu",
+    "chua", "chuo", "chuai", "chui", "chuan", "chun", "chuang", //
+    "sha", "she", "shi", "shai", "shei", "shao", "shou", "shan", "shen", "shang", "sheng", "shu",
+    "shua", "shuo", "shuai", "shui", "shuan", "shun", "shuang", //
+    "re", "ri", "rao", "rou", "ran", "ren", "rang", "reng", "rong", "ru", "rua", "ruo", "rui",
+    "ruan", "run", //
+    "za", "ze", "zi", "zai", "zei", "zao", "zou", "zan", "zen", "zang", "zeng", "zong", "zu",
+    "zuo", "zui", "zuan", "zun", //
+    "ca", "ce", "ci", "cai", "cao", "cou", "can", "cen", "cang", "ceng", "cong", "cu", "cuo",
+    "cui", "cuan", "cun", //
+    "sa", "se", "si", "sai", "sao", "sou", "san", "sen", "sang", "seng", "song", "su", "suo",
+    "sui", "suan", "sun", "sei",
+];
+
+/// Characters which look like pinyin letters, e.g. the IPA ɡ from pinyin fonts
+static LOOKALIKES: &[(char, char)] = &[('ɡ', 'g'), ('ɑ', 'a')];
+
+/// Maximum edit distance for suggesting a valid syllable instead of an invalid one
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// A syllable which is not in the Mandarin syllable inventory
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidSyllable {
+    /// Position of the syllable in the pinyin, starting with 1
+    pub position: usize,
+    pub syllable: String,
+    /// Nearest valid syllable with the same tone
+    pub suggestion: Option<String>,
+}
+
+/// Character which looks like a pinyin letter, see `normalize_spelling`
+#[must_use]
+pub fn is_lookalike(c: char) -> bool {
+    LOOKALIKES.iter().any(|l| l.0 == c)
+}
+
+/// Spelling of pinyin with tone numbers as used in the dictionary: ü is written as v (shown as ü with tone
+/// marks) and lookalikes are replaced by the pinyin letters
+#[must_use]
+pub fn normalize_spelling(pinyin_num: &str) -> String {
+    pinyin_num
+        .chars()
+        .map(|c| match c {
+            'ü' => 'v',
+            'Ü' => 'V',
+            _ => LOOKALIKES.iter().find(|l| l.0 == c).map_or(c, |l| l.1),
+        })
+        .collect()
+}
+
+/// Splits pinyin with tone numbers into syllables, separators (spaces, commas, ...) are dropped
+pub fn split_syllables(pinyin_num: &str) -> impl Iterator<Item = &str> {
+    let split_pattern = |c: char| (c > '0') && (c < '6');
+    pinyin_num
+        .split(|c: char| !c.is_alphanumeric())
+        .flat_map(move |part| part.split_inclusive(split_pattern))
+        .filter(|syllable| !syllable.is_empty())
+}
+
+/// Lowercase syllables without separators, with 5 for the neutral tone and v for ü, to compare readings from
+/// different sources, e.g. "Ni3 hao5" and "ni3hao" are the same reading
+#[must_use]
+pub fn reading_key(pinyin_num: &str) -> String {
+    let pinyin_num = normalize_spelling(&pinyin_num.replace("u:", "v"));
+    let mut key = String::new();
+    for syllable in split_syllables(&pinyin_num) {
+        let (sound, tone) = split_tone(syllable);
+        key.push_str(&sound);
+        key.push(tone.unwrap_or('5'));
+    }
+    key
+}
+
+/// Splits off the tone number, returns the lowercase sound with v for ü
+fn split_tone(syllable: &str) -> (String, Option<char>) {
+    let sound = syllable.to_lowercase().replace('ü', "v");
+    match sound.strip_suffix(|c: char| ('1'..='5').contains(&c)) {
+        Some(base) => (base.to_owned(), sound.chars().next_back()),
+        None => (sound, None),
+    }
+}
+
+/// Checks a syllable with or without tone number, e.g. `zhong1`, `Zhong`, `r5` or the erhua `huar4`
+#[must_use]
+pub fn is_valid_syllable(syllable: &str) -> bool {
+    let (sound, tone) = split_tone(syllable);
+    if sound == "r" {
+        return tone.is_none_or(|t| t == '5');
+    }
+    SYLLABLES.contains(&sound.as_str())
+        || sound
+            .strip_suffix('r')
+            .is_some_and(|base| !["er", "r"].contains(&base) && SYLLABLES.contains(&base))
+}
+
+/// Nearest valid syllable with the tone of the invalid one, keeps an uppercase first letter
+fn suggest_syllable(syllable: &str) -> Option<String> {
+    let (sound, tone) = split_tone(syllable);
+    let sound = normalize_spelling(&sound);
+    let (distance, nearest) = SYLLABLES
+        .iter()
+        .map(|valid| (common::edit_distance(&sound, valid), valid))
+        .min_by_key(|(distance, _)| *distance)?;
+    if distance > MAX_SUGGESTION_DISTANCE {
+        return None;
+    }
+    let mut suggestion = String::new();
+    for (i, c) in nearest.chars().enumerate() {
+        if i == 0 && syllable.starts_with(char::is_uppercase) {
+            suggestion.extend(c.to_uppercase());
+        } else {
+            suggestion.push(c);
+        }
+    }
+    suggestion.extend(tone);
+    Some(suggestion)
+}
+
+/// All syllables of the pinyin which are not in the Mandarin syllable inventory
+#[must_use]
+pub fn invalid_syllables(pinyin_num: &str) -> Vec<InvalidSyllable> {
+    split_syllables(pinyin_num)
+        .enumerate()
+        .filter(|(_, syllable)| !is_valid_syllable(syllable))
+        .map(|(i, syllable)| InvalidSyllable {
+            position: i + 1,
+            syllable: syllable.to_owned(),
+            suggestion: suggest_syllable(syllable),
+        })
+        .collect()
+}
+
 #[must_use]
 fn pinyin_syllable_mark_from_num(pinyin_num: &str) -> String {
     // "normalize" pinyin, could be extended for handling of MDBG u:
@@ -196,4 +367,37 @@ mod tests {
         assert_eq!(pinyin_mark_from_num("v3"), "ǚ");
         assert_eq!(pinyin_mark_from_num("V3"), "Ǚ");
     }
+
+    #[test]
+    fn test_syllables() {
+        assert_eq!(
+            split_syllables("Zhong1guo2, r5 ren2-men5").collect::<Vec<_>>(),
+            ["Zhong1", "guo2", "r5", "ren2", "men5"]
+        );
+        for valid in [
+            "zhong1", "Xi4", "lve4", "nü3", "ê2", "m2", "ng4", "hng5", "r5", "huar4",
+        ] {
+            assert!(is_valid_syllable(valid), "{valid}");
+        }
+        for invalid in ["zhuong1", "ɡuo4", "r3", "err2", "rr5", "x1"] {
+            assert!(!is_valid_syllable(invalid), "{invalid}");
+        }
+        assert_eq!(
+            invalid_syllables("ni3 hao3 Zhuong1ɡuo2"),
+            [
+                InvalidSyllable {
+                    position: 3,
+                    syllable: "Zhuong1".to_owned(),
+                    suggestion: Some("Zhong1".to_owned()),
+                },
+                InvalidSyllable {
+                    position: 4,
+                    syllable: "ɡuo2".to_owned(),
+                    suggestion: Some("guo2".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(invalid_syllables("xyzxyz1")[0].suggestion, None);
+        assert_eq!(normalize_spelling("Lü4ɡuo2 nv3"), "Lv4guo2 nv3");
+    }
 }
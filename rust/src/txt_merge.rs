@@ -0,0 +1,726 @@
+// Three-way merge of the text format, used as git merge driver
+//
+// All three versions are converted to the canonical text representation (txt -> db -> txt) first, so that
+// formatting differences don't matter. The canonical text is split into merge units: words (by trad/simp),
+// definitions and sub-definitions (by ext_def_id, together with their P and C lines) and notes (by
+// ext_note_id). Note texts are replaced by links (N->id) while merging and written once again at their first
+// occurrence afterwards.
+// Conflict markers are only used for words where both sides changed the same unit in different ways.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Write as _;
+use std::hash::Hash;
+
+use rusqlite::{Connection, Error as SqliteError};
+
+use crate::common::MAX_TEMPORARY_NOTE_ID;
+use crate::db_edit;
+use crate::db_to_txt::{self, DbToTxtError};
+use crate::txt_to_db;
+
+#[derive(Debug)]
+pub enum MergeError {
+    ParseError {
+        version: &'static str,
+        errors: Vec<String>,
+    },
+    SqliteError(SqliteError),
+    DbToTxtError(DbToTxtError),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ParseError { version, errors } => {
+                write!(
+                    f,
+                    "Could not parse {version} version:\n{}",
+                    errors.join("\n")
+                )
+            }
+            Self::SqliteError(e) => write!(f, "Database error: {e}"),
+            Self::DbToTxtError(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<SqliteError> for MergeError {
+    fn from(err: SqliteError) -> Self {
+        Self::SqliteError(err)
+    }
+}
+
+impl From<DbToTxtError> for MergeError {
+    fn from(err: DbToTxtError) -> Self {
+        Self::DbToTxtError(err)
+    }
+}
+
+impl std::error::Error for MergeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::ParseError { .. } => None,
+            Self::SqliteError(ref source) => Some(source),
+            Self::DbToTxtError(ref source) => Some(source),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MergeResult {
+    /// Merged text, canonical if there are neither conflicts nor errors
+    pub text: String,
+    /// Number of words with conflict markers
+    pub conflicts: usize,
+    /// Errors of the merged text without conflicts, e.g. a reference to a definition deleted on the other side
+    pub errors: Vec<String>,
+}
+
+/// Convert the text to its canonical representation, including the symmetric references
+fn canonical_txt(txt: &str, version: &'static str) -> Result<String, MergeError> {
+    let mut conn = Connection::open_in_memory()?;
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    if !errors.is_empty() {
+        return Err(MergeError::ParseError { version, errors });
+    }
+    let tx = conn.transaction()?;
+    db_edit::add_missing_symmetric_references(&tx)?;
+    db_edit::add_missing_notes_and_tags_for_symmetric_references(&tx)?;
+    tx.commit()?;
+    let mut out: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut out, &conn, false, None)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// A definition with the P and C lines it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DefUnit {
+    ext_def_id: u32,
+    pron: Vec<String>,
+    class: String,
+    body: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WordUnit {
+    key: String,
+    /// W line with the items of the word (references, notes, comments)
+    head: Vec<String>,
+    definitions: Vec<DefUnit>,
+}
+
+#[derive(Debug, Default)]
+struct Document {
+    header: Vec<String>,
+    max_note_link: Option<u32>,
+    words: Vec<WordUnit>,
+    /// Note texts by `ext_note_id`, one entry per physical line
+    notes: HashMap<u32, Vec<String>>,
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn parse_note_link(content: &str) -> Option<u32> {
+    content.strip_prefix("N->")?.trim_end().parse().ok()
+}
+
+/// Split a note line `N12 text` into id and first line of the text
+fn parse_note(content: &str) -> Option<(u32, &str)> {
+    let rest = content.strip_prefix('N')?;
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let id = rest[..digits].parse().ok()?;
+    let txt = &rest[digits..];
+    if txt.is_empty() {
+        Some((id, txt))
+    } else {
+        txt.strip_prefix(' ').map(|t| (id, t))
+    }
+}
+
+fn parse_ext_def_id(content: &str) -> Option<u32> {
+    let rest = content.strip_prefix('D')?;
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    rest[..digits].parse().ok()
+}
+
+/// Definition ids of the word which are taken by redirects (R lines)
+fn redirect_ids(word: &WordUnit) -> impl Iterator<Item = u32> + '_ {
+    word.head.iter().filter_map(|line| {
+        let rest = line.strip_prefix(" R")?;
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        rest[..digits].parse().ok()
+    })
+}
+
+/// The word as written in the W line (after the tags), also used in references
+fn word_key(content: &str) -> String {
+    content
+        .strip_prefix("W|")
+        .and_then(|s| s.split_once('|'))
+        .map_or(content, |(_, word)| word)
+        .to_owned()
+}
+
+/// Group the physical lines into logical lines, continuation lines are indented by more than one level
+fn logical_lines(txt: &str) -> Vec<(usize, Vec<String>)> {
+    let mut lines: Vec<(usize, Vec<String>)> = vec![];
+    for line in txt.lines() {
+        let indent = indentation(line);
+        if let Some((cur_indent, cur_lines)) = lines.last_mut()
+            && (line.trim().is_empty() || indent > *cur_indent + 1)
+        {
+            cur_lines.push(line.to_owned());
+            continue;
+        }
+        lines.push((indent, vec![line.to_owned()]));
+    }
+    lines
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Head,
+    Pron,
+    Definition,
+}
+
+fn parse_document(txt: &str) -> Document {
+    let mut doc = Document::default();
+    let mut pron: Vec<String> = vec![];
+    let mut class = String::new();
+    let mut section = Section::Head;
+
+    for (indent, mut lines) in logical_lines(txt) {
+        let content = lines[0][indent..].to_owned();
+        if let Some((id, first)) = parse_note(&content) {
+            // the note text is stored separately, the line becomes a link
+            let mut note = vec![first.to_owned()];
+            note.extend(lines[1..].iter().map(|l| {
+                l.get(indent + 2..)
+                    .unwrap_or_else(|| l.trim_start())
+                    .to_owned()
+            }));
+            doc.notes.entry(id).or_insert(note);
+            lines = vec![format!("{}N->{id}", " ".repeat(indent))];
+        }
+
+        let Some(word) = doc.words.last_mut() else {
+            if content.starts_with('W') {
+                doc.words.push(WordUnit {
+                    key: word_key(&content),
+                    head: lines,
+                    definitions: vec![],
+                });
+            } else if indent == 0 && parse_note_link(&content).is_some() {
+                doc.max_note_link = parse_note_link(&content);
+            } else {
+                doc.header.extend(lines);
+            }
+            continue;
+        };
+
+        match (indent, content.chars().next()) {
+            (0, Some('W')) => {
+                doc.words.push(WordUnit {
+                    key: word_key(&content),
+                    head: lines,
+                    definitions: vec![],
+                });
+                section = Section::Head;
+            }
+            (1, Some('P')) => {
+                pron = lines;
+                section = Section::Pron;
+            }
+            (2, Some('P')) if section == Section::Pron => pron.extend(lines),
+            (2, Some('C')) => {
+                class = lines.join("\n");
+                section = Section::Pron;
+            }
+            // sub-definitions are separate units, so that changes of different sub-definitions don't conflict
+            (3.., Some('D')) if parse_ext_def_id(&content).is_some() => {
+                word.definitions.push(DefUnit {
+                    ext_def_id: parse_ext_def_id(&content).unwrap_or_default(),
+                    pron: pron.clone(),
+                    class: class.clone(),
+                    body: lines,
+                });
+                section = Section::Definition;
+            }
+            _ => match section {
+                Section::Head => word.head.extend(lines),
+                Section::Pron => pron.extend(lines),
+                Section::Definition => {
+                    if let Some(def) = word.definitions.last_mut() {
+                        def.body.extend(lines);
+                    }
+                }
+            },
+        }
+    }
+    doc
+}
+
+/// Three-way merge of a single unit, None if both sides changed it in different ways
+#[allow(
+    clippy::option_option,
+    reason = "Some(None) is a deleted unit, None a conflict"
+)]
+fn merge3<T: PartialEq + Clone>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+) -> Option<Option<T>> {
+    if ours == theirs || base == theirs {
+        Some(ours.cloned())
+    } else if base == ours {
+        Some(theirs.cloned())
+    } else {
+        None
+    }
+}
+
+/// Order of all keys of the three versions
+///
+/// The order of the side which changed the order of the common keys is kept, the keys which are only on the
+/// other side are inserted after their predecessor.
+fn merge_order<K: Eq + Hash + Clone>(base: &[K], ours: &[K], theirs: &[K]) -> Vec<K> {
+    let base_set: HashSet<&K> = base.iter().collect();
+    let ours_set: HashSet<&K> = ours.iter().collect();
+    let ours_common: Vec<&K> = ours.iter().filter(|k| base_set.contains(k)).collect();
+    let base_common: Vec<&K> = base.iter().filter(|k| ours_set.contains(k)).collect();
+    let (primary, secondary) = if ours_common == base_common {
+        (theirs, ours)
+    } else {
+        (ours, theirs)
+    };
+
+    let primary_set: HashSet<&K> = primary.iter().collect();
+    let mut inserted_after: HashMap<Option<&K>, Vec<&K>> = HashMap::new();
+    let mut inserted: HashSet<&K> = HashSet::new();
+    let mut anchor = None;
+    for k in secondary.iter().chain(base) {
+        if primary_set.contains(k) {
+            anchor = Some(k);
+        } else if inserted.insert(k) {
+            inserted_after.entry(anchor).or_default().push(k);
+        }
+    }
+
+    let mut order: Vec<K> = inserted_after
+        .get(&None)
+        .into_iter()
+        .flatten()
+        .map(|k| (*k).clone())
+        .collect();
+    for k in primary {
+        order.push(k.clone());
+        if let Some(after) = inserted_after.get(&Some(k)) {
+            order.extend(after.iter().map(|k| (*k).clone()));
+        }
+    }
+    order
+}
+
+enum MergedWord {
+    Resolved(WordUnit),
+    Conflict(Option<WordUnit>, Option<WordUnit>),
+}
+
+/// Merge the definitions of a word which was changed on both sides
+fn merge_word(base: Option<&WordUnit>, ours: &WordUnit, theirs: &WordUnit) -> MergedWord {
+    let head = merge3(base.map(|w| &w.head), Some(&ours.head), Some(&theirs.head));
+    let find = |w: Option<&WordUnit>, id: u32| -> Option<DefUnit> {
+        w.and_then(|w| w.definitions.iter().find(|d| d.ext_def_id == id).cloned())
+    };
+    let ids = |w: &WordUnit| {
+        w.definitions
+            .iter()
+            .map(|d| d.ext_def_id)
+            .collect::<Vec<_>>()
+    };
+
+    let mut defs_ours = vec![];
+    let mut defs_theirs = vec![];
+    let mut is_conflict = head.is_none();
+    for id in merge_order(&base.map(ids).unwrap_or_default(), &ids(ours), &ids(theirs)) {
+        let (b, o, t) = (find(base, id), find(Some(ours), id), find(Some(theirs), id));
+        if let Some(def) = merge3(b.as_ref(), o.as_ref(), t.as_ref()) {
+            defs_ours.extend(def.clone());
+            defs_theirs.extend(def);
+            continue;
+        }
+        // changed on both sides, try to merge pron, class and definition separately
+        if let (Some(o), Some(t)) = (&o, &t) {
+            let pron = merge3(b.as_ref().map(|d| &d.pron), Some(&o.pron), Some(&t.pron));
+            let class = merge3(b.as_ref().map(|d| &d.class), Some(&o.class), Some(&t.class));
+            let body = merge3(b.as_ref().map(|d| &d.body), Some(&o.body), Some(&t.body));
+            if let (Some(Some(pron)), Some(Some(class)), Some(Some(body))) = (pron, class, body) {
+                let def = DefUnit {
+                    ext_def_id: id,
+                    pron,
+                    class,
+                    body,
+                };
+                defs_ours.push(def.clone());
+                defs_theirs.push(def);
+                continue;
+            }
+        }
+        is_conflict = true;
+        defs_ours.extend(o);
+        defs_theirs.extend(t);
+    }
+
+    if is_conflict {
+        MergedWord::Conflict(
+            Some(WordUnit {
+                key: ours.key.clone(),
+                head: head.clone().flatten().unwrap_or_else(|| ours.head.clone()),
+                definitions: defs_ours,
+            }),
+            Some(WordUnit {
+                key: theirs.key.clone(),
+                head: head.flatten().unwrap_or_else(|| theirs.head.clone()),
+                definitions: defs_theirs,
+            }),
+        )
+    } else {
+        MergedWord::Resolved(WordUnit {
+            key: ours.key.clone(),
+            head: head.flatten().unwrap_or_default(),
+            definitions: defs_ours,
+        })
+    }
+}
+
+fn replace_lines(lines: &mut [String], replace: &impl Fn(&str) -> Option<String>) {
+    for line in lines {
+        if let Some(new_line) = replace(line) {
+            *line = new_line;
+        }
+    }
+}
+
+fn replace_in_document(doc: &mut Document, replace: &impl Fn(&str) -> Option<String>) {
+    for word in &mut doc.words {
+        replace_lines(&mut word.head, replace);
+        for def in &mut word.definitions {
+            replace_lines(&mut def.pron, replace);
+            replace_lines(&mut def.body, replace);
+        }
+    }
+}
+
+/// Replace references to `word#D<old_id>` in a reference or redirect line
+fn replace_reference(line: &str, word: &str, old_id: u32, new_id: u32) -> Option<String> {
+    if !line.trim_start().starts_with(['X', 'R']) {
+        return None;
+    }
+    let target = format!("{word}#D{old_id}");
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut replaced = false;
+    while let Some(pos) = rest.find(&target) {
+        let end = pos + target.len();
+        let preceded_ok = rest[..pos].ends_with(['|', ';']) || rest[..pos].ends_with("-> ");
+        let followed_ok = !rest[end..].starts_with(|c: char| c.is_ascii_digit());
+        result.push_str(&rest[..pos]);
+        if preceded_ok && followed_ok {
+            let _ = write!(result, "{word}#D{new_id}");
+            replaced = true;
+        } else {
+            result.push_str(&target);
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    replaced.then_some(result)
+}
+
+/// Give new ids to notes and definitions which were added on both sides with the same id
+fn renumber_theirs(base: &Document, ours: &Document, theirs: &mut Document) {
+    // temporary note ids
+    let mut next_note_id = base
+        .notes
+        .keys()
+        .chain(ours.notes.keys())
+        .chain(theirs.notes.keys())
+        .filter(|id| **id <= MAX_TEMPORARY_NOTE_ID)
+        .max()
+        .copied()
+        .unwrap_or_default();
+    let mut note_ids: Vec<u32> = theirs.notes.keys().copied().collect();
+    note_ids.sort_unstable();
+    for id in note_ids {
+        if id > MAX_TEMPORARY_NOTE_ID || base.notes.contains_key(&id) {
+            continue;
+        }
+        if ours.notes.get(&id).is_some_and(|n| *n != theirs.notes[&id]) {
+            next_note_id += 1;
+            let new_id = next_note_id;
+            if let Some(note) = theirs.notes.remove(&id) {
+                theirs.notes.insert(new_id, note);
+            }
+            replace_in_document(theirs, &|line: &str| {
+                (parse_note_link(line.trim_start()) == Some(id))
+                    .then(|| format!("{}N->{new_id}", " ".repeat(indentation(line))))
+            });
+        }
+    }
+
+    // definition ids
+    let mut renumbered: Vec<(String, u32, u32)> = vec![];
+    for word in &mut theirs.words {
+        let Some(word_ours) = ours.words.iter().find(|w| w.key == word.key) else {
+            continue;
+        };
+        let word_base = base.words.iter().find(|w| w.key == word.key);
+        let mut max_id = word
+            .definitions
+            .iter()
+            .chain(&word_ours.definitions)
+            .chain(word_base.iter().flat_map(|w| &w.definitions))
+            .map(|d| d.ext_def_id)
+            .chain(redirect_ids(word))
+            .chain(redirect_ids(word_ours))
+            .chain(word_base.into_iter().flat_map(redirect_ids))
+            .max()
+            .unwrap_or_default();
+        let redirects_ours: Vec<u32> = redirect_ids(word_ours).collect();
+        for def in &mut word.definitions {
+            let id = def.ext_def_id;
+            if word_base.is_some_and(|w| w.definitions.iter().any(|d| d.ext_def_id == id)) {
+                continue;
+            }
+            if redirects_ours.contains(&id)
+                || word_ours
+                    .definitions
+                    .iter()
+                    .any(|d| d.ext_def_id == id && d != def)
+            {
+                max_id += 1;
+                def.ext_def_id = max_id;
+                def.body[0] = def.body[0].replacen(&format!("D{id}"), &format!("D{max_id}"), 1);
+                renumbered.push((word.key.clone(), id, max_id));
+            }
+        }
+    }
+    for (word, old_id, new_id) in renumbered {
+        replace_in_document(theirs, &|line: &str| {
+            replace_reference(line, &word, old_id, new_id)
+        });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Ours,
+    Theirs,
+}
+
+enum MergedNote {
+    Resolved(Vec<String>),
+    Conflict(Vec<String>, Vec<String>),
+}
+
+fn merge_notes(base: &Document, ours: &Document, theirs: &Document) -> HashMap<u32, MergedNote> {
+    let ids: HashSet<u32> = base
+        .notes
+        .keys()
+        .chain(ours.notes.keys())
+        .chain(theirs.notes.keys())
+        .copied()
+        .collect();
+    ids.into_iter()
+        .map(|id| {
+            let (b, o, t) = (
+                base.notes.get(&id),
+                ours.notes.get(&id),
+                theirs.notes.get(&id),
+            );
+            let note = match merge3(b, o, t) {
+                Some(Some(note)) => MergedNote::Resolved(note),
+                // the note is not used anymore, but might still be linked from the other side
+                Some(None) => MergedNote::Resolved(o.or(t).or(b).cloned().unwrap_or_default()),
+                None => match (o, t) {
+                    (Some(o), Some(t)) => MergedNote::Conflict(o.clone(), t.clone()),
+                    (o, t) => MergedNote::Resolved(o.or(t).cloned().unwrap_or_default()),
+                },
+            };
+            (id, note)
+        })
+        .collect()
+}
+
+/// Write the lines of a word, the P and C lines are only written if they differ from the previous definition
+fn word_lines(word: &WordUnit) -> Vec<String> {
+    let mut lines = word.head.clone();
+    let mut last_pron: Option<&Vec<String>> = None;
+    let mut last_class: Option<&String> = None;
+    for def in &word.definitions {
+        if last_pron != Some(&def.pron) {
+            lines.extend(def.pron.iter().cloned());
+            last_pron = Some(&def.pron);
+            last_class = None;
+        }
+        if last_class != Some(&def.class) {
+            lines.push(def.class.clone());
+            last_class = Some(&def.class);
+        }
+        lines.extend(def.body.iter().cloned());
+    }
+    lines
+}
+
+/// Replace the first link to each note with the note text
+fn expand_notes(
+    lines: Vec<String>,
+    notes: &HashMap<u32, MergedNote>,
+    written_notes: &mut HashSet<u32>,
+    side: Side,
+) -> Vec<String> {
+    let mut expanded = Vec::with_capacity(lines.len());
+    for line in lines {
+        let indent = indentation(&line);
+        let Some(id) = parse_note_link(&line[indent..]) else {
+            expanded.push(line);
+            continue;
+        };
+        if !written_notes.insert(id) {
+            expanded.push(line);
+            continue;
+        }
+        let note = match notes.get(&id) {
+            Some(MergedNote::Resolved(note)) => note,
+            Some(MergedNote::Conflict(ours, theirs)) => {
+                if side == Side::Ours {
+                    ours
+                } else {
+                    theirs
+                }
+            }
+            None => {
+                expanded.push(line);
+                continue;
+            }
+        };
+        let mut note_lines = note.iter();
+        expanded.push(format!(
+            "{}N{id} {}",
+            " ".repeat(indent),
+            note_lines.next().map_or("", String::as_str)
+        ));
+        expanded.extend(note_lines.map(|l| format!("{}{l}", " ".repeat(indent + 2))));
+    }
+    expanded
+}
+
+fn write_conflict(out: &mut Vec<String>, ours: Vec<String>, theirs: Vec<String>) {
+    out.push("<<<<<<< ours".to_owned());
+    out.extend(ours);
+    out.push("=======".to_owned());
+    out.extend(theirs);
+    out.push(">>>>>>> theirs".to_owned());
+}
+
+/// Three-way merge of the text format, the versions don't need to be canonical
+///
+/// # Errors
+/// Returns an error if one of the versions can't be parsed or converted
+pub fn merge_txt(base: &str, ours: &str, theirs: &str) -> Result<MergeResult, MergeError> {
+    let base = parse_document(&canonical_txt(base, "base")?);
+    let ours = parse_document(&canonical_txt(ours, "ours")?);
+    let mut theirs = parse_document(&canonical_txt(theirs, "theirs")?);
+    renumber_theirs(&base, &ours, &mut theirs);
+
+    let notes = merge_notes(&base, &ours, &theirs);
+    let mut out: Vec<String> = vec![];
+    let mut conflicts = 0;
+
+    if let Some(header) = merge3(Some(&base.header), Some(&ours.header), Some(&theirs.header)) {
+        out.extend(header.unwrap_or_default());
+    } else {
+        conflicts += 1;
+        write_conflict(&mut out, ours.header.clone(), theirs.header.clone());
+    }
+    if let Some(max_note_id) = ours.max_note_link.max(theirs.max_note_link) {
+        out.push(format!("N->{max_note_id}"));
+    }
+
+    let by_key = |doc: &Document| -> HashMap<String, WordUnit> {
+        doc.words
+            .iter()
+            .map(|w| (w.key.clone(), w.clone()))
+            .collect()
+    };
+    let keys = |doc: &Document| doc.words.iter().map(|w| w.key.clone()).collect::<Vec<_>>();
+    let (base_words, ours_words, theirs_words) = (by_key(&base), by_key(&ours), by_key(&theirs));
+    let mut written_notes: HashSet<u32> = HashSet::new();
+
+    for key in merge_order(&keys(&base), &keys(&ours), &keys(&theirs)) {
+        let (b, o, t) = (
+            base_words.get(&key),
+            ours_words.get(&key),
+            theirs_words.get(&key),
+        );
+        let merged = match merge3(b, o, t) {
+            Some(Some(word)) => MergedWord::Resolved(word),
+            Some(None) => continue,
+            None => match (o, t) {
+                (Some(o), Some(t)) => merge_word(b, o, t),
+                (o, t) => MergedWord::Conflict(o.cloned(), t.cloned()),
+            },
+        };
+        let (word_ours, word_theirs) = match &merged {
+            MergedWord::Resolved(word) => (Some(word), Some(word)),
+            MergedWord::Conflict(o, t) => (o.as_ref(), t.as_ref()),
+        };
+        let mut written_ours = written_notes.clone();
+        let lines_ours = expand_notes(
+            word_ours.map(word_lines).unwrap_or_default(),
+            &notes,
+            &mut written_ours,
+            Side::Ours,
+        );
+        let lines_theirs = expand_notes(
+            word_theirs.map(word_lines).unwrap_or_default(),
+            &notes,
+            &mut written_notes,
+            Side::Theirs,
+        );
+        written_notes.extend(written_ours);
+        if lines_ours == lines_theirs {
+            out.extend(lines_ours);
+        } else {
+            conflicts += 1;
+            write_conflict(&mut out, lines_ours, lines_theirs);
+        }
+    }
+
+    let mut text = out.join("\n");
+    text.push('\n');
+    if conflicts > 0 {
+        return Ok(MergeResult {
+            text,
+            conflicts,
+            errors: vec![],
+        });
+    }
+    match canonical_txt(&text, "merged") {
+        Ok(canonical) => Ok(MergeResult {
+            text: canonical,
+            conflicts,
+            errors: vec![],
+        }),
+        Err(MergeError::ParseError { errors, .. }) => Ok(MergeResult {
+            text,
+            conflicts,
+            errors,
+        }),
+        Err(e) => Err(e),
+    }
+}
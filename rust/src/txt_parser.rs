@@ -1,15 +1,17 @@
 use nom::{
     IResult, Parser,
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    character::complete::{anychar, char, multispace0, none_of, u32},
-    combinator::{all_consuming, fail, map, opt, rest, value},
+    bytes::complete::{tag, take_while_m_n, take_while1},
+    character::complete::{anychar, char, multispace0, multispace1, none_of, u32},
+    combinator::{all_consuming, fail, map, opt, recognize, rest, value},
     multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, terminated},
 };
 
 use std::fmt;
 
+use crate::pinyin;
+
 const WORD_SEP: &str = "／";
 
 #[derive(Debug, PartialEq, Eq)]
@@ -72,6 +74,19 @@ pub struct Note {
     pub txt: String,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum RedirectTarget {
+    Moved(Word, u32),
+    Deleted(String),
+}
+
+/// Definition id which is no longer used by the word, it moved to another definition or was deleted
+#[derive(Debug, PartialEq, Eq)]
+pub struct Redirect {
+    pub id: u32,
+    pub target: RedirectTarget,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum DictLine {
     Word(Vec<WordTagGroup>),
@@ -79,6 +94,7 @@ pub enum DictLine {
     Class(String),
     Definition(DefinitionTag),
     CrossReference(Vec<ReferenceTagGroup>),
+    Redirect(Redirect),
     Note(Note),
     Comment(String),
 }
@@ -200,6 +216,7 @@ fn parse_line(line: &str) -> Result<DictLine, ()> {
             preceded(char('X'), parse_reference_line),
             DictLine::CrossReference,
         ),
+        map(preceded(char('R'), parse_redirect_line), DictLine::Redirect),
         map(preceded(char('N'), parse_note_line), DictLine::Note),
         map(preceded(char('#'), parse_comment_line), DictLine::Comment),
     ));
@@ -282,7 +299,10 @@ fn parse_word_line(word_line: &str) -> IResult<&str, Vec<WordTagGroup>> {
 fn parse_pinyin_list(pinyin_list: &str) -> IResult<&str, Vec<&str>> {
     let pinyin_parser = delimited(
         multispace0,
-        take_while1(|c: char| c.is_ascii_alphanumeric() || "ê. -,".contains(c)),
+        // ü and lookalikes of pinyin letters are accepted, so that the checks can report and fix them
+        take_while1(|c: char| {
+            c.is_ascii_alphanumeric() || "üÜê. -,".contains(c) || pinyin::is_lookalike(c)
+        }),
         multispace0,
     );
     separated_list1(char(';'), pinyin_parser).parse(pinyin_list)
@@ -385,5 +405,32 @@ fn parse_reference_line(reference_line: &str) -> IResult<&str, Vec<ReferenceTagG
     all_consuming(many1(parse_reference_tag_group)).parse(reference_line)
 }
 
+fn parse_date(date: &str) -> IResult<&str, &str> {
+    let digits = |n| take_while_m_n(n, n, |c: char| c.is_ascii_digit());
+    recognize((digits(4), char('-'), digits(2), char('-'), digits(2))).parse(date)
+}
+
+fn parse_redirect_line(redirect_line: &str) -> IResult<&str, Redirect> {
+    // R3 -> word#D5 or R3 deleted 2025-06-01
+    let moved = map(
+        preceded(
+            delimited(multispace0, tag("->"), multispace0),
+            pair(parse_word, preceded(tag("#D"), u32)),
+        ),
+        |(word, id)| RedirectTarget::Moved(word, id),
+    );
+    let deleted = map(
+        preceded(
+            delimited(multispace1, tag("deleted"), multispace1),
+            parse_date,
+        ),
+        |date| RedirectTarget::Deleted(date.to_owned()),
+    );
+    let (remainder, (id, target)) =
+        all_consuming(terminated(pair(u32, alt((moved, deleted))), multispace0))
+            .parse(redirect_line)?;
+    Ok((remainder, Redirect { id, target }))
+}
+
 #[cfg(test)]
 mod tests;
@@ -99,6 +99,12 @@ fn test_parse_pinyin_list() {
         parse_pinyin_list("dan1ci2; ci2yu3"),
         Ok(("", vec!["dan1ci2", "ci2yu3"]))
     );
+    assert_eq!(
+        parse_pinyin_list("nü3er2; ɡuo4"),
+        Ok(("", vec!["nü3er2", "ɡuo4"]))
+    );
+    parse_line("P||你hao3").unwrap_err();
+    parse_line("P||ni３hao3").unwrap_err();
 }
 
 #[test]
@@ -342,6 +348,29 @@ fn test_parse_line_dispatcher() {
             }]
         }]))
     );
+    assert_eq!(
+        parse_line("R3 -> 再會／再会#D5"),
+        Ok(DictLine::Redirect(Redirect {
+            id: 3,
+            target: RedirectTarget::Moved(
+                Word {
+                    trad: "再會".to_string(),
+                    simp: Some("再会".to_string())
+                },
+                5
+            )
+        }))
+    );
+    assert_eq!(
+        parse_line("R4 deleted 2025-06-01"),
+        Ok(DictLine::Redirect(Redirect {
+            id: 4,
+            target: RedirectTarget::Deleted("2025-06-01".to_string())
+        }))
+    );
+    // redirects need a definition id as target and a full date
+    parse_line("R3 -> 再會").unwrap_err();
+    parse_line("R4 deleted 2025-6-1").unwrap_err();
     // Test invalid line
     assert!(parse_line("Z invalid line").is_err());
 }
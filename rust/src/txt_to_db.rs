@@ -3,8 +3,8 @@ use rusqlite::{Connection, Error as SqliteError};
 use crate::config;
 use crate::pinyin;
 use crate::txt_parser::{
-    DefinitionTag, DictLine, LineInfo, Note, ParserIterator, PinyinTagGroup, ReferenceTagGroup,
-    Tag, Tags, Word, WordTagGroup,
+    DefinitionTag, DictLine, LineInfo, Note, ParserIterator, PinyinTagGroup, Redirect,
+    RedirectTarget, ReferenceTagGroup, Tag, Tags, Word, WordTagGroup,
 };
 
 use std::io;
@@ -15,6 +15,20 @@ use std::{fmt, mem};
 
 use crate::common::SqliteId;
 
+/// Line in the text file for each shared entry and tags which were written more than once for an entry, only
+/// kept for the validation of the parsed file
+const SOURCE_LINE_SCHEMA: &str = r#"
+CREATE TEMP TABLE IF NOT EXISTS "dict_source_line" (
+	"shared_id" INTEGER NOT NULL UNIQUE,
+	"line" INTEGER NOT NULL,
+	PRIMARY KEY("shared_id")
+);
+CREATE TEMP TABLE IF NOT EXISTS "dict_duplicate_tag" (
+	"shared_id" INTEGER NOT NULL,
+	"tag_id" INTEGER NOT NULL
+);
+"#;
+
 #[derive(Debug, PartialEq)]
 struct CrossReferenceEntry {
     shared_id: SqliteId,
@@ -26,6 +40,15 @@ struct CrossReferenceEntry {
     err_line_idx: usize,
 }
 
+#[derive(Debug)]
+struct RedirectEntry {
+    redirect_id: SqliteId,
+    word_id: SqliteId,
+    ext_def_id: u32,
+    dst: Option<(Word, u32)>,
+    err_line_idx: usize,
+}
+
 #[derive(Debug)]
 struct NoteReferenceEntry {
     target_shared_id: SqliteId,
@@ -40,6 +63,7 @@ enum DictNode {
     Class(SqliteId),                            // class_id
     Definition((SqliteId, SqliteId, SqliteId)), // shared_id, word_id, definition_id
     CrossReference(SqliteId),                   // shared_id
+    Redirect(SqliteId),                         // shared_id
 }
 
 #[derive(Debug)]
@@ -57,6 +81,7 @@ pub enum TxtToDbError {
     UnknownReferenceType(char),
     ReferenceTargetNotFound(String),
     NoteIdNotFound(u32),
+    RedirectIdInUse(u32),
 }
 
 pub type Result<T> = std::result::Result<T, TxtToDbError>;
@@ -80,6 +105,9 @@ impl fmt::Display for TxtToDbError {
             Self::NoteIdNotFound(id) => {
                 write!(f, "No note with found for id: {id}")
             }
+            Self::RedirectIdInUse(id) => {
+                write!(f, "Redirect for an id which is used by a definition: D{id}")
+            }
         }
     }
 }
@@ -100,6 +128,7 @@ impl std::error::Error for TxtToDbError {
             Self::UnknownReferenceType(_) => None,
             Self::ReferenceTargetNotFound(_) => None,
             Self::NoteIdNotFound(_) => None,
+            Self::RedirectIdInUse(_) => None,
         }
     }
 }
@@ -120,9 +149,11 @@ pub fn txt_to_db(
 pub struct TxtToDb<'a> {
     conn: &'a Connection,
     rank_counter: u64,
+    source_line: u32,
     line_stack: Vec<Vec<DictNode>>,
     cross_references: Vec<CrossReferenceEntry>, // references are added after all entries are in the DB
     note_references: Vec<NoteReferenceEntry>,
+    redirects: Vec<RedirectEntry>, // targets are checked after all entries are in the DB
     new_notes_num: u32,
     pub err_lines: Vec<(String, LineInfo)>, // (word, line_info) keep line info for errors
     pub errors: Vec<TxtToDbErrorLine>,
@@ -131,12 +162,15 @@ pub struct TxtToDb<'a> {
 impl<'a> TxtToDb<'a> {
     pub fn new(conn: &'a Connection) -> Self {
         conn.execute_batch(config::DB_SCHEMA).unwrap();
+        conn.execute_batch(SOURCE_LINE_SCHEMA).unwrap();
         TxtToDb {
             conn,
             rank_counter: 0,
+            source_line: 0,
             line_stack: vec![],
             cross_references: vec![],
             note_references: vec![],
+            redirects: vec![],
             new_notes_num: 0,
             err_lines: vec![],
             errors: vec![],
@@ -192,6 +226,7 @@ impl<'a> TxtToDb<'a> {
         }
         self.complete_cross_reference_entries();
         self.complete_id_reference_entries();
+        self.complete_redirect_entries();
         self.conn.execute("COMMIT", ()).unwrap();
     }
 
@@ -235,10 +270,16 @@ impl<'a> TxtToDb<'a> {
             .prepare_cached("SELECT id FROM dict_tag WHERE tag=?1 AND type=?2")?;
         let tag_id: SqliteId = stmt.query_row((tag_txt, tag_type), |row| row.get(0))?;
 
-        let mut stmt = self
-            .conn
-            .prepare_cached("INSERT INTO dict_shared_tag (for_shared_id, tag_id) VALUES (?1,?2)")?;
-        stmt.execute((shared_id, tag_id))?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO dict_shared_tag (for_shared_id, tag_id) VALUES (?1,?2)",
+        )?;
+        if stmt.execute((shared_id, tag_id))? == 0 {
+            // reported by the check for duplicate tags
+            let mut stmt = self.conn.prepare_cached(
+                "INSERT INTO temp.dict_duplicate_tag (shared_id, tag_id) VALUES (?1,?2)",
+            )?;
+            stmt.execute((shared_id, tag_id))?;
+        }
         Ok(())
     }
 
@@ -261,7 +302,12 @@ impl<'a> TxtToDb<'a> {
             .conn
             .prepare_cached("INSERT INTO dict_shared (rank) VALUES (?1)")?;
         stmt.execute((self.rank_counter,))?;
-        Ok(self.conn.last_insert_rowid())
+        let shared_id = self.conn.last_insert_rowid();
+        let mut stmt = self
+            .conn
+            .prepare_cached("INSERT INTO temp.dict_source_line (shared_id, line) VALUES (?1,?2)")?;
+        stmt.execute((shared_id, self.source_line))?;
+        Ok(shared_id)
     }
 
     fn create_word_entry(&mut self, word: &Word, tags: &Tags) -> Result<DictNode> {
@@ -315,17 +361,19 @@ impl<'a> TxtToDb<'a> {
         word_id: SqliteId,
         definition_tag: &DefinitionTag,
         class: SqliteId,
+        parent_id: Option<SqliteId>,
     ) -> Result<DictNode> {
         let shared_id = self.create_shared_entry()?;
         let mut stmt = self
             .conn
-            .prepare_cached("INSERT INTO dict_definition (shared_id, word_id, definition, ext_def_id, class_id) VALUES (?1,?2,?3,?4,?5)")?;
+            .prepare_cached("INSERT INTO dict_definition (shared_id, word_id, definition, ext_def_id, class_id, parent_id) VALUES (?1,?2,?3,?4,?5,?6)")?;
         stmt.execute((
             shared_id,
             word_id,
             &definition_tag.definition,
             definition_tag.id,
             class,
+            parent_id,
         ))?;
         let definition_id = self.conn.last_insert_rowid();
         let definition_entry = DictNode::Definition((shared_id, word_id, definition_id));
@@ -458,6 +506,91 @@ impl<'a> TxtToDb<'a> {
         }
     }
 
+    fn create_redirect_entry(&mut self, word_id: SqliteId, redirect: Redirect) -> Result<DictNode> {
+        let shared_id = self.create_shared_entry()?;
+        let (dst, date_deleted) = match redirect.target {
+            RedirectTarget::Moved(word, ext_def_id) => (Some((word, ext_def_id)), None),
+            RedirectTarget::Deleted(date) => (None, Some(date)),
+        };
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT INTO dict_redirect (shared_id, word_id, ext_def_id, date_deleted) VALUES (?1,?2,?3,?4)",
+        )?;
+        stmt.execute((shared_id, word_id, redirect.id, date_deleted))?;
+        self.redirects.push(RedirectEntry {
+            redirect_id: self.conn.last_insert_rowid(),
+            word_id,
+            ext_def_id: redirect.id,
+            dst,
+            err_line_idx: self.err_lines.len(),
+        });
+        Ok(DictNode::Redirect(shared_id))
+    }
+
+    fn complete_redirect_entries(&mut self) {
+        for redirect in mem::take(&mut self.redirects) {
+            // the id of a redirect must not be used by a definition of the same word
+            let in_use: bool = self
+                .conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM dict_definition WHERE word_id=?1 AND ext_def_id=?2)",
+                    (redirect.word_id, redirect.ext_def_id),
+                    |row| row.get(0),
+                )
+                .unwrap();
+            if in_use {
+                self.errors.push(TxtToDbErrorLine {
+                    err_line_idx: redirect.err_line_idx,
+                    error: TxtToDbError::RedirectIdInUse(redirect.ext_def_id),
+                });
+                self.delete_redirect_entry(redirect.redirect_id);
+                continue;
+            }
+            let Some((dst_word, dst_ext_def_id)) = redirect.dst else {
+                continue;
+            };
+            // the target is either a definition or another redirect
+            let dst_word_id: Option<SqliteId> = self
+                .conn
+                .query_row(
+                    r"
+                    SELECT w.id FROM dict_word w
+                    WHERE w.trad=?1 AND w.simp=?2 AND (
+                        EXISTS(SELECT 1 FROM dict_definition WHERE word_id=w.id AND ext_def_id=?3) OR
+                        EXISTS(SELECT 1 FROM dict_redirect WHERE word_id=w.id AND ext_def_id=?3))
+                    ",
+                    (
+                        &dst_word.trad,
+                        dst_word.simp.as_ref().unwrap_or(&dst_word.trad),
+                        dst_ext_def_id,
+                    ),
+                    |row| row.get(0),
+                )
+                .ok();
+            let Some(dst_word_id) = dst_word_id else {
+                self.errors.push(TxtToDbErrorLine {
+                    err_line_idx: redirect.err_line_idx,
+                    error: TxtToDbError::ReferenceTargetNotFound(format!(
+                        "{dst_word}#D{dst_ext_def_id}"
+                    )),
+                });
+                self.delete_redirect_entry(redirect.redirect_id);
+                continue;
+            };
+            self.conn
+                .execute(
+                    "UPDATE dict_redirect SET word_id_dst=?1, ext_def_id_dst=?2 WHERE id=?3",
+                    (dst_word_id, dst_ext_def_id, redirect.redirect_id),
+                )
+                .unwrap();
+        }
+    }
+
+    fn delete_redirect_entry(&self, redirect_id: SqliteId) {
+        self.conn
+            .execute("DELETE FROM dict_redirect WHERE id=?1", (redirect_id,))
+            .unwrap();
+    }
+
     fn complete_id_reference_entries(&mut self) {
         for reference in mem::take(&mut self.note_references) {
             let note_id = self.conn.query_row(
@@ -525,6 +658,7 @@ impl<'a> TxtToDb<'a> {
 
     fn add_line_to_db(&mut self, line_info: &LineInfo, line: DictLine) -> (bool, bool) {
         self.line_stack.truncate(line_info.indentation);
+        self.source_line = line_info.source_line_start;
 
         let (line_items, keep_line) = match line {
             DictLine::Word(word_tag_groups) => (self.add_word_line_to_db(word_tag_groups), false),
@@ -539,6 +673,7 @@ impl<'a> TxtToDb<'a> {
                 self.add_cross_reference_line_to_db(reference_tag_groups),
                 true,
             ),
+            DictLine::Redirect(redirect) => (self.add_redirect_line_to_db(redirect), true),
             DictLine::Note(note) => {
                 let is_link = note.is_link;
                 (self.add_note_line_to_db(&note), is_link)
@@ -684,6 +819,17 @@ impl<'a> TxtToDb<'a> {
         Ok(line_items)
     }
 
+    fn add_redirect_line_to_db(&mut self, redirect: Redirect) -> Result<Vec<DictNode>> {
+        // redirects belong directly to the word
+        if self.line_stack.len() == 1
+            && let Some(DictNode::Word((_, word_id))) = self.line_stack[0].first().copied()
+        {
+            Ok(vec![self.create_redirect_entry(word_id, redirect)?])
+        } else {
+            Err(TxtToDbError::NoUsableParentNode)
+        }
+    }
+
     fn add_definition_line_to_db(
         &mut self,
         definition_tag: &DefinitionTag,
@@ -693,8 +839,20 @@ impl<'a> TxtToDb<'a> {
         {
             if let Some(DictNode::Class(class_id)) = self.line_stack.get(2).and_then(|v| v.first())
             {
+                // a definition indented below another definition is a sub-definition
+                let parent_id = if self.line_stack.len() > 3 {
+                    if let Some(DictNode::Definition((_, _, parent_id))) =
+                        self.line_stack.last().and_then(|v| v.first())
+                    {
+                        Some(*parent_id)
+                    } else {
+                        return Err(TxtToDbError::NoUsableParentNode);
+                    }
+                } else {
+                    None
+                };
                 let definition_entry =
-                    self.create_definition_entry(*word_id, definition_tag, *class_id)?;
+                    self.create_definition_entry(*word_id, definition_tag, *class_id, parent_id)?;
                 if let DictNode::Definition((_, _, definition_id)) = definition_entry {
                     // add links between definition and pronunciation
                     let pinyin_entries = self.line_stack.get(1).unwrap().clone();
@@ -747,6 +905,7 @@ const fn get_shared_id_for_dict_node(dict_node: &DictNode) -> Result<SqliteId> {
         }
         DictNode::Definition((shared_id, _, _)) => shared_id,
         DictNode::CrossReference(shared_id) => shared_id,
+        DictNode::Redirect(shared_id) => shared_id,
     };
     Ok(*shared_id)
 }
@@ -0,0 +1,776 @@
+use rusqlite::Connection;
+
+use fmld::db_check::{self, Check};
+use fmld::db_model;
+use fmld::db_to_txt;
+use fmld::txt_to_db;
+
+mod common;
+use common::load_db_from_str;
+
+/// Findings of the stable ids check of the current version compared to the reference
+fn check_stable_ids(reference: &str, current: &str) -> Vec<String> {
+    let reference = db_check::StableIdsReference {
+        entries: db_model::load_word_entries(&load_db_from_str(reference)).unwrap(),
+        min_similarity: 0.5,
+    };
+    db_check::StableIds::new(reference)
+        .run(&load_db_from_str(current))
+        .unwrap()
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+const REFERENCE: &str = "W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+   D2||hi
+    N101 informal
+   D3||how do you do
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N102 also used on the phone
+W||早
+ P||zao3
+  C adj
+   D1||early
+";
+
+#[test]
+fn test_check_stable_ids() {
+    // D1 tombstone, small correction of D2, unchanged notes: no errors
+    assert!(
+        check_stable_ids(
+            REFERENCE,
+            "W||你好
+ P||ni3hao3
+  C phrase
+   D1|X|hello
+   D2||hi!
+    N101 informal
+   D3||how do you do
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N102 also used on the phone
+W||早
+ P||zao3
+  C adj
+   D1||early
+"
+        )
+        .is_empty()
+    );
+
+    let findings = check_stable_ids(
+        REFERENCE,
+        "W||你好
+ P||ni3hao3
+  C phrase
+   D2||hi
+   D3||a kind of bird
+    N101 formal, written language
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+W||早
+ P||zao3
+  C adj
+   D1||early
+    N102 also used on the phone
+W||早上
+ P||zao3shang4
+  C noun
+   D1||early
+",
+    );
+    assert_eq!(
+        findings,
+        vec![
+            "Validation Error: 你好#D1 (line 1): disappeared, keep it with the deleted tag (X) or add a redirect (R) instead (stable-ids)",
+            "Validation Error: 你好#D3 (line 5): changed its meaning: \"how do you do\" -> \"a kind of bird\" (stable-ids)",
+            "Validation Error: note N101 (line 5): changed its meaning: \"informal\" -> \"formal, written language\" (stable-ids)",
+            "Validation Error: note N102 (line 14): moved to a different word: 早 (stable-ids)",
+        ]
+    );
+
+    // word with different characters, but the same definition
+    let findings = check_stable_ids(
+        REFERENCE,
+        "W||您好
+ P||nin2hao3
+  C phrase
+   D1||hello
+   D2||hi
+    N101 informal
+   D3||how do you do
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N102 also used on the phone
+W||早
+ P||zao3
+  C adj
+   D1||early
+",
+    );
+    assert_eq!(findings.len(), 4);
+    assert_eq!(
+        findings[0],
+        "Validation Error: 你好#D1 (line 4): moved to a different word: 您好#D1 (stable-ids)"
+    );
+    assert_eq!(
+        findings[3],
+        "Validation Error: note N101 (line 5): moved to a different word: 您好 (stable-ids)"
+    );
+}
+
+#[test]
+fn test_check_stable_ids_similar_text() {
+    // a similar definition with the same id in another word is not a move
+    let findings = check_stable_ids(
+        "W||丙
+ P||bing3
+  C variant
+   D1||variant of 丁
+   D2||third
+W||戊
+ P||wu4
+  C variant
+   D1||variant of 己
+",
+        "W||丙
+ P||bing3
+  C variant
+   D2||third
+W||戊
+ P||wu4
+  C variant
+   D1||variant of 己
+",
+    );
+    assert_eq!(
+        findings,
+        vec![
+            "Validation Error: 丙#D1 (line 1): disappeared, keep it with the deleted tag (X) or add a redirect (R) instead (stable-ids)",
+        ]
+    );
+}
+
+#[test]
+fn test_check_stable_ids_with_redirects() {
+    // redirects for the moved and the deleted definition
+    let findings = check_stable_ids(
+        REFERENCE,
+        "W||你好
+ R1 -> 您好#D1
+ R3 deleted 2025-06-01
+ P||ni3hao3
+  C phrase
+   D2||hi
+    N101 informal
+W||您好
+ P||nin2hao3
+  C phrase
+   D1||hello
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N102 also used on the phone
+W||早
+ P||zao3
+  C adj
+   D1||early
+",
+    );
+    assert!(findings.is_empty(), "{findings:?}");
+}
+
+#[test]
+fn test_stable_ids_severity_suppression() {
+    let conn = load_db_from_str(
+        "W||你好
+ P||ni3hao3
+  C phrase
+   D2||hi
+    N101 informal
+   D3||a kind of bird
+    # ignore-check: stable-ids
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N102 also used on the phone
+W||早
+ P||zao3
+  C adj
+   D1||early
+",
+    );
+    let checks = db_check::registry_with_options(db_check::CheckOptions {
+        stable_ids: Some(db_check::StableIdsReference {
+            entries: db_model::load_word_entries(&load_db_from_str(REFERENCE)).unwrap(),
+            min_similarity: 0.5,
+        }),
+        ..Default::default()
+    });
+    let mut config = db_check::CheckConfig::default();
+    config
+        .severity
+        .insert("stable-ids".to_owned(), db_check::Severity::Warning);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Validation Warning: 你好#D1 (line 1): disappeared, keep it with the deleted tag (X) or add a redirect (R) instead (stable-ids)",
+        ]
+    );
+}
+
+#[test]
+fn test_run_checks() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||你好／嗎
+ P||ni3hao3
+  C phrase
+   D1||hello
+W|i|乾／干乾
+ P||gan1
+  C adj
+   D1||dry
+W||早上
+ P||zao3
+  C noun
+   D1||morning
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let checks = db_check::registry();
+    let mut config = db_check::CheckConfig::default();
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Validation Error: 你好／嗎 (line 1): different numbers of characters in the traditional and simplified word (trad-simp-length)",
+            "Validation Error: 早上 (line 10): pinyin syllables don't match the number of characters: zao3 (pinyin-syllable-count)",
+        ]
+    );
+
+    config.apply(&[], &["trad-simp-length".to_owned()]);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].check_id, "pinyin-syllable-count");
+
+    // the command line takes precedence over the config file
+    let mut config: db_check::CheckConfig =
+        serde_json::from_str(r#"{"disable": ["trad-simp-length", "pinyin-syllable-count"]}"#)
+            .unwrap();
+    config.apply(&["trad-simp-length".to_owned()], &[]);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].check_id, "trad-simp-length");
+
+    config.apply(&["no-such-check".to_owned()], &[]);
+    assert_eq!(config.unknown_ids(&checks), vec!["no-such-check"]);
+}
+
+#[test]
+fn test_severity_suppression_baseline() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||你好／嗎
+ # ignore-check: trad-simp-length, pinyin-syllable
+ P||ni3hao3
+  C phrase
+   D1||hello
+W||早上
+ P||zao3
+  C noun
+   D1||morning
+W||晚上
+ P||wan3shang4
+  C noun
+   D1||evening
+   D2||evening
+    # ignore-check: duplicate-definition
+W||中國
+ # ignore-check: duplicate-definition
+ P||zhong1gwo2
+  C noun
+   D1||China
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let checks = db_check::registry();
+    let mut config: db_check::CheckConfig =
+        serde_json::from_str(r#"{"severity": {"pinyin-syllable-count": "warning"}}"#).unwrap();
+    config.apply(&[], &["trad-simp-mapping".to_owned()]);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    // a suppression only applies to the checks it names
+    assert_eq!(
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Validation Warning: 早上 (line 7): pinyin syllables don't match the number of characters: zao3 (pinyin-syllable-count)",
+            "Validation Warning: 中國 (line 18): invalid pinyin syllable gwo2 at position 2 of zhong1gwo2, did you mean wo2? (pinyin-syllable)",
+        ]
+    );
+
+    let baseline = db_check::Baseline::from_findings(&findings[..1]);
+    let json = serde_json::to_string(&baseline).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"check":"pinyin-syllable-count","location":"早上","message":"pinyin syllables don't match the number of characters: zao3"}]"#
+    );
+    let baseline: db_check::Baseline = serde_json::from_str(&json).unwrap();
+    assert!(baseline.contains(&findings[0]));
+    assert!(!baseline.contains(&findings[1]));
+    assert_eq!(baseline.num_fixed(&findings[1..]), 1);
+}
+
+#[test]
+fn test_han_characters() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||一,二
+ P||yi1 er4
+  C phrase
+   D1||one, two
+W||\u{F900}有此理／岂有此理
+ P||qi3you3ci3li3
+  C idiom
+   D1||outrageous
+W||卡拉ＯＫ
+ P||ka3la1 O K
+  C noun
+   D1||karaoke
+W||の
+ P||no5
+  C particle
+   D1||of
+W||𱍐𱍐麵／𱍐𱍐面
+ P||biang2mian4
+  C noun
+   D1||biangbiang noodles
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let findings = db_check::run_checks(
+        &conn,
+        &db_check::registry(),
+        &db_check::CheckConfig::default(),
+    )
+    .unwrap();
+    // 𱍐 is in extension H, which was not recognized as Han before
+    assert_eq!(
+        findings
+            .iter()
+            .filter(|f| f.check_id != "trad-simp-mapping")
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "Validation Warning: の (line 13): non-Han character の (U+306E) (non-han-character)",
+            "Validation Warning: \u{F900}有此理／岂有此理 (line 5): compatibility ideograph \u{F900} (U+F900) instead of 豈 (U+8C48) (compatibility-ideograph)",
+            "Validation Warning: 一,二 (line 1): half-width , instead of ， (word-punctuation)",
+            "Validation Warning: 卡拉ＯＫ (line 9): full-width Ｏ instead of O, full-width Ｋ instead of K (word-punctuation)",
+            "Validation Error: 𱍐𱍐麵／𱍐𱍐面 (line 18): pinyin syllables don't match the number of characters: biang2mian4 (pinyin-syllable-count)",
+        ]
+    );
+}
+
+#[test]
+fn test_pinyin_syllable() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||中國／中国
+ P||zhong1zhuong2; zhong1guo2
+  C noun
+   D1||China
+W||過秤／过秤
+ P||ɡuo4cheng4
+  C verb
+   D1||to weigh
+W||哪兒／哪儿
+ P||nar3; na3r5
+ P|i|nia3
+  C pron
+   D1||where
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let findings = db_check::run_checks(
+        &conn,
+        &db_check::registry(),
+        &db_check::CheckConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        findings
+            .iter()
+            .filter(|f| f.check_id == "pinyin-syllable")
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        // the lookalike in ɡuo4 is reported by pinyin-spelling
+        vec![
+            "Validation Warning: 中國／中国 (line 2): invalid pinyin syllable zhuong2 at position 2 of zhong1zhuong2, did you mean zhong2? (pinyin-syllable)",
+        ]
+    );
+}
+
+#[test]
+fn test_trad_simp_mapping() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||中國／中國
+ P||zhong1guo2
+  C noun
+   D1||China
+W||學習／学刁
+ P||xue2xi2
+  C verb
+   D1||to learn
+W||乾杯／干杯
+ P||gan1bei1
+  C verb
+   D1||cheers
+W|i|乾淨／乾净
+ P||gan1jing4
+  C adj
+   D1||clean
+W||甦醒／苏醒
+ P||su1xing3
+  C verb
+   D1||to wake up
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let run = |checks: &[Box<dyn db_check::Check>]| {
+        db_check::run_checks(&conn, checks, &db_check::CheckConfig::default())
+            .unwrap()
+            .iter()
+            .filter(|f| f.check_id == "trad-simp-mapping")
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+    // 甦 and 苏 are not in the bundled table of common characters
+    assert_eq!(
+        run(&db_check::registry()),
+        vec![
+            "Validation Warning: 中國 (line 1): traditional character 國 in the simplified word (simplified: 国) (trad-simp-mapping)",
+            "Validation Warning: 學習／学刁 (line 5): 習 is not simplified as 刁 (trad-simp-mapping)",
+        ]
+    );
+
+    let unihan = "# Unihan_Variants.txt
+U+7526\tkSimplifiedVariant\tU+82CF
+U+82CF\tkTraditionalVariant\tU+8607 U+56CC U+7526
+U+7FD2\tkSimplifiedVariant\tU+4E60
+";
+    let table = db_check::TradSimpTable::read(&mut unihan.as_bytes()).unwrap();
+    let checks = db_check::registry_with_options(db_check::CheckOptions {
+        trad_simp_table: Some(table),
+        ..Default::default()
+    });
+    assert_eq!(
+        run(&checks),
+        vec![
+            "Validation Warning: 學習／学刁 (line 5): 習 is not simplified as 刁 (trad-simp-mapping)",
+        ]
+    );
+}
+
+#[test]
+fn test_tag_rank_conflict() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W|TwC|中國／中国
+ P|x+|zhong1guo2
+  C noun
+   D1|m|China
+W||大
+ P||da4
+  C adj
+   D1||big
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let run = |conn: &Connection| {
+        db_check::run_checks(
+            conn,
+            &db_check::registry(),
+            &db_check::CheckConfig::default(),
+        )
+        .unwrap()
+        .iter()
+        .filter(|f| f.check_id == "tag-rank-conflict")
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        run(&conn),
+        vec![
+            "Validation Warning: 中國／中国 (line 1): several tags of the same rank (country): T, C (tag-rank-conflict)",
+            "Validation Warning: 中國／中国 pronunciation zhong1guo2 (line 2): several tags of the same rank (relevance): x, + (tag-rank-conflict)",
+        ]
+    );
+
+    let checks = db_check::registry();
+    let findings = db_check::run_checks(&conn, &checks, &db_check::CheckConfig::default()).unwrap();
+    let tx = conn.transaction().unwrap();
+    let fixes = db_check::apply_fixes(&tx, &checks, &findings).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(
+        fixes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Fixed: 中國／中国: removed tag C, kept T (tag-rank-conflict)",
+            "Fixed: 中國／中国 pronunciation zhong1guo2: removed tag +, kept x (tag-rank-conflict)",
+        ]
+    );
+    assert!(run(&conn).is_empty());
+}
+
+#[test]
+fn test_fix() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W|ww|女兒／女儿
+ X~||姑娘
+ P||nü3er2
+  C noun
+   D1|-|daughter  (female  child) 
+W||姑娘
+ P||ɡu1niang5
+  C noun
+   D1||girl
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let checks = db_check::registry();
+    let mut config = db_check::CheckConfig::default();
+    // the missing directions are added anyway, the check only reports them
+    config.apply(&["symmetric-reference-missing".to_owned()], &[]);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Validation Warning: 女兒／女儿 pronunciation nü3er2 (line 3): pinyin nü3er2 should be written as nv3er2 (pinyin-spelling)",
+            "Validation Warning: 姑娘 pronunciation ɡu1niang5 (line 7): pinyin ɡu1niang5 should be written as gu1niang5 (pinyin-spelling)",
+            "Validation Warning: 女兒／女儿 (line 1): duplicate tag w (duplicate-tag)",
+            "Validation Warning: 女兒／女儿#D1 (line 5): leading, trailing or repeated whitespace in \"daughter  (female  child) \" (definition-whitespace)",
+            "Validation Warning: 女兒／女儿 reference to 姑娘 (line 2): symmetric reference without the other direction (symmetric-reference-missing)",
+        ]
+    );
+
+    let tx = conn.transaction().unwrap();
+    let fixes = db_check::apply_fixes(&tx, &checks, &findings).unwrap();
+    tx.commit().unwrap();
+    assert_eq!(
+        fixes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Fixed: 女兒／女儿 pronunciation nv3er2: nü3er2 -> nv3er2 (pinyin-spelling)",
+            "Fixed: 姑娘 pronunciation gu1niang5: ɡu1niang5 -> gu1niang5 (pinyin-spelling)",
+            "Fixed: 女兒／女儿: removed duplicate tag w (duplicate-tag)",
+            "Fixed: 女兒／女儿#D1: \"daughter  (female  child) \" -> \"daughter (female child)\" (definition-whitespace)",
+            "Fixed: 姑娘 reference to 女兒／女儿: added (symmetric-reference-missing)",
+        ]
+    );
+    assert!(
+        db_check::run_checks(&conn, &checks, &config)
+            .unwrap()
+            .is_empty()
+    );
+
+    let mut txt_fixed: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_fixed, &conn, false, None).unwrap();
+    assert_eq!(
+        String::from_utf8(txt_fixed).unwrap(),
+        "W|w|女兒／女儿
+ X~||姑娘
+ P||nv3er2
+  C noun
+   D1|-|daughter (female child)
+W||姑娘
+ X~||女兒／女儿
+ P||gu1niang5
+  C noun
+   D1||girl
+"
+    );
+}
+
+#[test]
+fn test_duplicate_definition() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||秤
+ P||cheng4
+  C noun
+   D1||steelyard; balance (for weighing)
+   D2||a scale --- a balance
+  C verb
+   D3||Steelyard, balance
+   D4||(archaic) a balance --- a scale!
+   D5|i|steelyard; balance
+W||稱
+ Xv||秤
+ P||cheng4
+  C noun
+   D1||steelyard or balance
+   D2||to call
+W||漲／涨
+ P||zhang3
+  C verb
+   D1||to rise (of prices)
+   D2||to rise (of water)
+   D3||to go up
+   D4||to go up
+   D5||a unit
+    D6||old
+   D7||a measure
+    D8||old
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let checks = db_check::registry();
+    let mut config = db_check::CheckConfig::default();
+    let run = |config: &db_check::CheckConfig| {
+        db_check::run_checks(&conn, &checks, config)
+            .unwrap()
+            .iter()
+            .filter(|f| f.check_id.starts_with("duplicate-definition"))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        run(&config),
+        vec![
+            "Validation Warning: 秤 (lines 4, 7): duplicate definitions 秤#D1 and 秤#D3 (similarity 1.00) (duplicate-definition)",
+            "Validation Warning: 秤 (lines 5, 8): duplicate definitions 秤#D2 and 秤#D4 (similarity 1.00) (duplicate-definition)",
+            "Validation Warning: 漲／涨 (lines 19, 20): duplicate definitions 漲／涨#D1 and 漲／涨#D2 (similarity 1.00) (duplicate-definition)",
+            "Validation Error: 漲／涨 (lines 21, 22): duplicate definitions 漲／涨#D3 and 漲／涨#D4 (similarity 1.00) (duplicate-definition)",
+        ]
+    );
+
+    config.apply(&["duplicate-definition-variant".to_owned()], &[]);
+    assert_eq!(
+        run(&config)[4..],
+        vec![
+            "Validation Warning: 稱 (lines 14, 4): near-duplicate definitions 稱#D1 and 秤#D1 (similarity 0.85) (duplicate-definition-variant)",
+            "Validation Warning: 稱 (lines 14, 7): near-duplicate definitions 稱#D1 and 秤#D3 (similarity 0.85) (duplicate-definition-variant)",
+        ]
+    );
+}
+
+#[test]
+fn test_reference_graph() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||甲
+ Xv||乙
+ X<||丙
+ P||jia3
+  C noun
+   D1||first
+    X=||甲
+W||乙
+ Xv||丁
+ X<||甲
+ P||yi3
+  C noun
+   D1||second
+W||丙
+ X<||乙
+ P||bing3
+  C noun
+   D1||third
+    XM||丁
+    XM||個／个
+W||丁
+ P||ding1
+  C noun
+   D1||fourth
+W||個／个
+ P||ge4
+  C classifier
+   D1||general measure word
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let findings = db_check::run_checks(
+        &conn,
+        &db_check::registry(),
+        &db_check::CheckConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        findings
+            .iter()
+            .filter(|f| [
+                "self-reference",
+                "variant-chain",
+                "reference-cycle",
+                "reference-target"
+            ]
+            .contains(&f.check_id))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "Validation Warning: 甲#D1 reference to 甲 (line 7): reference to itself (self-reference)",
+            "Validation Warning: 甲 reference to 乙 (line 2): 乙 is itself a variant of 丁, refer to 丁 instead (variant-chain)",
+            "Validation Warning: 甲 (lines 3, 15, 10): cycle of part-of references: 甲 → 丙 → 乙 → 甲 (reference-cycle)",
+            "Validation Warning: 丙#D1 reference to 丁 (line 19): target 丁 of the reference M has no definition with the class classifier (reference-target)",
+        ]
+    );
+}
+
+#[test]
+fn test_symmetric_reference_conflict() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||大
+ X~|T|小
+  N101 big and small
+ P||da4
+  C adj
+   D1||big
+    X~|+|小#D1
+W||小
+ X~|C|大
+  N102 small and big
+ P||xiao3
+  C adj
+   D1||small
+    X~|m|大#D1
+W||多
+ X~|w|少
+ P||duo1
+  C adj
+   D1||many
+W||少
+ X~|m|多
+  N103 only on one side
+ P||shao3
+  C adj
+   D1||few
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let checks = db_check::registry();
+    let mut config = db_check::CheckConfig::default();
+    config.apply(&[], &["pinyin-syllable-count".to_owned()]);
+    let findings = db_check::run_checks(&conn, &checks, &config).unwrap();
+    assert_eq!(
+        findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        vec![
+            "Validation Error: 大 (lines 2, 9): different notes N101 and N102 and conflicting tags T and C on the symmetric references with 小 (symmetric-reference-conflict)",
+        ]
+    );
+}
@@ -0,0 +1,25 @@
+// Helpers shared by the integration tests
+
+use rusqlite::Connection;
+
+use fmld::db_edit;
+use fmld::txt_to_db;
+
+/// DB of a text without errors
+pub fn load_db_from_str(txt: &str) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    conn
+}
+
+/// DB of a text without errors, completed with the symmetric references like by the import
+#[allow(dead_code, reason = "not used by all tests")]
+pub fn load_db_with_symmetric_references_from_str(txt: &str) -> Connection {
+    let mut conn = load_db_from_str(txt);
+    let tx = conn.transaction().unwrap();
+    db_edit::add_missing_symmetric_references(&tx).unwrap();
+    db_edit::add_missing_notes_and_tags_for_symmetric_references(&tx).unwrap();
+    tx.commit().unwrap();
+    conn
+}
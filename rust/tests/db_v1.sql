@@ -0,0 +1,202 @@
+-- dictionary database of schema version 1 (before redirects, sub-definitions and frequency ranks), created
+-- with sqlite3 .dump
+PRAGMA user_version = 1;
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS "dict_definition" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_id" INTEGER NOT NULL,
+	"word_id" INTEGER NOT NULL,
+	"definition" TEXT NOT NULL,
+	-- constant id, used for referencing definitions in the text representation of from external sources
+	"ext_def_id" INTEGER NOT NULL,
+	"class_id" INTEGER NOT NULL,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("word_id") REFERENCES "dict_word"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("class_id") REFERENCES "dict_class"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_definition VALUES(1,4,1,'hello',1,1);
+INSERT INTO dict_definition VALUES(2,5,1,'hi',2,1);
+INSERT INTO dict_definition VALUES(3,9,2,'hello (polite)',1,1);
+CREATE TABLE IF NOT EXISTS "dict_tag" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"tag" TEXT NOT NULL,
+	"type" TEXT NOT NULL,
+	"ascii_symbol" TEXT,
+	PRIMARY KEY("id")
+);
+INSERT INTO dict_tag VALUES(1,'wiktionary','source','w');
+INSERT INTO dict_tag VALUES(2,'lowest-relevance','relevance','x');
+CREATE TABLE IF NOT EXISTS "dict_word" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_id" INTEGER NOT NULL,
+	-- word in traditional characters
+	"trad" TEXT NOT NULL,
+	-- word in simplified characters
+	"simp" TEXT NOT NULL,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_word VALUES(1,1,'你好','你好');
+INSERT INTO dict_word VALUES(2,6,'您好','您好');
+CREATE TABLE IF NOT EXISTS "dict_pron" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"pinyin_num" TEXT NOT NULL,
+	"pinyin_mark" TEXT NOT NULL,
+	PRIMARY KEY("id")
+);
+INSERT INTO dict_pron VALUES(1,'ni3hao3','nǐhǎo');
+INSERT INTO dict_pron VALUES(2,'nin2hao3','nínhǎo');
+CREATE TABLE IF NOT EXISTS "dict_pron_definition" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_pron_id" INTEGER NOT NULL,
+	"definition_id" INTEGER NOT NULL,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("definition_id") REFERENCES "dict_definition"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("shared_pron_id") REFERENCES "dict_shared_pron"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_pron_definition VALUES(1,1,1);
+INSERT INTO dict_pron_definition VALUES(2,1,2);
+INSERT INTO dict_pron_definition VALUES(3,2,3);
+CREATE TABLE IF NOT EXISTS "dict_reference" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_id" INTEGER NOT NULL,
+	"ref_type_id" INTEGER NOT NULL,
+	"word_id_src" INTEGER NOT NULL,
+	"definition_id_src" INTEGER,
+	"word_id_dst" INTEGER NOT NULL,
+	"definition_id_dst" INTEGER,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("word_id_dst") REFERENCES "dict_word"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("word_id_src") REFERENCES "dict_word"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("definition_id_src") REFERENCES "dict_definition"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("definition_id_dst") REFERENCES "dict_definition"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("ref_type_id") REFERENCES "dict_ref_type"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_reference VALUES(1,2,1,1,NULL,2,NULL);
+INSERT INTO dict_reference VALUES(2,7,1,2,NULL,1,NULL);
+CREATE TABLE IF NOT EXISTS "dict_shared" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"rank" INTEGER NOT NULL,
+	"rank_relative" INTEGER,
+	"note_id" INTEGER,
+	"comment_id" INTEGER,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("comment_id") REFERENCES "dict_comment"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("note_id") REFERENCES "dict_note"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_shared VALUES(1,1,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(2,2,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(3,3,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(4,4,NULL,1,NULL);
+INSERT INTO dict_shared VALUES(5,5,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(6,6,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(7,7,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(8,8,NULL,NULL,NULL);
+INSERT INTO dict_shared VALUES(9,9,NULL,NULL,NULL);
+CREATE TABLE IF NOT EXISTS "dict_shared_tag" (
+	"for_shared_id" INTEGER NOT NULL,
+	"tag_id" INTEGER NOT NULL,
+	PRIMARY KEY("for_shared_id", "tag_id"),
+	FOREIGN KEY ("tag_id") REFERENCES "dict_tag"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("for_shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_shared_tag VALUES(1,1);
+INSERT INTO dict_shared_tag VALUES(5,2);
+CREATE TABLE IF NOT EXISTS "dict_note" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"note" TEXT NOT NULL,
+	"ext_note_id" INTEGER NOT NULL,
+	PRIMARY KEY("id")
+);
+INSERT INTO dict_note VALUES(1,'informal',101);
+CREATE TABLE IF NOT EXISTS "dict_comment" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"comment" TEXT NOT NULL,
+	PRIMARY KEY("id")
+);
+CREATE TABLE IF NOT EXISTS "dict_class" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"name" TEXT NOT NULL,
+	PRIMARY KEY("id")
+);
+INSERT INTO dict_class VALUES(1,'phrase');
+CREATE TABLE IF NOT EXISTS "dict_ref_type" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"type" TEXT NOT NULL,
+	"ascii_symbol" TEXT NOT NULL,
+	"is_symmetric" INTEGER NOT NULL,
+	PRIMARY KEY("id")
+);
+INSERT INTO dict_ref_type VALUES(1,'synonym-similar','~',1);
+CREATE TABLE IF NOT EXISTS "dict_shared_pron" (
+	"id" INTEGER NOT NULL UNIQUE,
+	"shared_id" INTEGER NOT NULL,
+	"pron_id" INTEGER NOT NULL,
+	PRIMARY KEY("id"),
+	FOREIGN KEY ("shared_id") REFERENCES "dict_shared"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION,
+	FOREIGN KEY ("pron_id") REFERENCES "dict_pron"("id")
+	ON UPDATE NO ACTION ON DELETE NO ACTION
+);
+INSERT INTO dict_shared_pron VALUES(1,3,1);
+INSERT INTO dict_shared_pron VALUES(2,8,2);
+CREATE VIEW trad_simp_class_pinyin_def AS
+SELECT
+    w.trad,
+    w.simp,
+    c.name AS class_name,
+    GROUP_CONCAT(p.pinyin_mark ORDER BY p_s.rank, p_s.rank_relative),
+    def.ext_def_id,
+    def.definition
+FROM dict_definition def
+JOIN dict_shared s ON def.shared_id = s.id
+JOIN dict_word w ON def.word_id = w.id
+JOIN dict_class c ON def.class_id = c.id
+LEFT JOIN dict_pron_definition pdp ON def.id = pdp.definition_id
+LEFT JOIN dict_shared_pron sp ON pdp.shared_pron_id = sp.id
+LEFT JOIN dict_pron p ON sp.pron_id = p.id
+LEFT JOIN dict_shared p_s ON sp.shared_id = p_s.id
+GROUP BY def.id
+ORDER BY s.rank, s.rank_relative;
+CREATE UNIQUE INDEX "dict_definition_index_0"
+ON "dict_definition" ("word_id", "ext_def_id");
+CREATE UNIQUE INDEX "dict_tag_index_0"
+ON "dict_tag" ("tag", "type");
+CREATE UNIQUE INDEX "dict_word_index_0"
+ON "dict_word" ("trad", "simp");
+CREATE UNIQUE INDEX "dict_pron_index_0"
+ON "dict_pron" ("pinyin_num");
+CREATE INDEX "dict_pron_definition_index_0"
+ON "dict_pron_definition" ("definition_id");
+CREATE INDEX "dict_reference_index_0"
+ON "dict_reference" ("word_id_src", "definition_id_src");
+CREATE INDEX "dict_shared_index_0"
+ON "dict_shared" ("rank", "rank_relative");
+CREATE UNIQUE INDEX "dict_shared_tag_index_0"
+ON "dict_shared_tag" ("for_shared_id", "tag_id");
+CREATE UNIQUE INDEX "dict_note_index_0"
+ON "dict_note" ("ext_note_id");
+CREATE UNIQUE INDEX "dict_class_index_0"
+ON "dict_class" ("name");
+CREATE UNIQUE INDEX "dict_ref_type_index_0"
+ON "dict_ref_type" ("type");
+COMMIT;
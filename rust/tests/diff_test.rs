@@ -0,0 +1,156 @@
+use fmld::changelog;
+use fmld::db_diff::{self, ChangeKind, Entity};
+use fmld::db_model;
+
+mod common;
+use common::load_db_with_symmetric_references_from_str;
+
+fn load_entries_from_str(txt: &str) -> Vec<db_model::WordEntry> {
+    db_model::load_word_entries(&load_db_with_symmetric_references_from_str(txt)).unwrap()
+}
+
+#[test]
+fn test_diff() {
+    let old = load_entries_from_str(
+        "W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+   D2||hi
+    N? a note
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    N? informal
+W||舊
+ P||jiu4
+  C adj
+   D1||old
+",
+    );
+    // moved words, changed order of definitions and renumbered temporary note ids are not reported
+    let new = load_entries_from_str(
+        "W||再見／再见
+ P||zai4jian4; zai4jian5
+  C phrase
+   D1|T|goodbye!
+    N? informal
+   X=||你好#D1
+W||你好
+ P||ni3hao3
+  C interjection
+   D1||hello
+W||早
+ P||zao3
+  C phrase
+   D1||morning
+",
+    );
+    let changes: Vec<String> = db_diff::diff_entries(&old, &new)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            "+ reference 再見／再见: synonym-equal 你好#D1",
+            "~ definition 再見／再见#D1: definition: \"goodbye\" -> \"goodbye!\"",
+            "+ pronunciation 再見／再见#D1: zai4jian5",
+            "+ tag 再見／再见#D1: taiwan-only",
+            "~ definition 你好#D1: class: \"phrase\" -> \"interjection\"",
+            "+ reference 你好#D1: synonym-equal 再見／再见",
+            "- definition 你好#D2: \"hi\"",
+            "+ word 早: \"morning\"",
+            "- word 舊: \"old\"",
+            "- note 你好#D2: N1: \"a note\"",
+        ]
+    );
+
+    assert!(db_diff::diff_entries(&new, &new).is_empty());
+
+    let change = &db_diff::diff_entries(&old, &new)[0];
+    assert_eq!(change.kind, ChangeKind::Added);
+    assert_eq!(change.entity, Entity::Reference);
+    assert_eq!(
+        serde_json::to_string(change).unwrap(),
+        r#"{"kind":"added","entity":"reference","trad":"再見","simp":"再见","key":"synonym-equal 你好#D1"}"#
+    );
+}
+
+#[test]
+fn test_changelog() {
+    let conn_old = load_db_with_symmetric_references_from_str(
+        "W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+   D2||hi
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+W||舊
+ P||jiu4
+  C adj
+   D1||old
+   D2||former
+",
+    );
+    let conn_new = load_db_with_symmetric_references_from_str(
+        "W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+W||再見／再见
+ P||zai4jian4; zai4jian5
+  C phrase
+   D1|X|goodbye
+W|T|早
+ P||zao3
+  C phrase
+   D1||*good* morning
+W||學
+ P||xue2
+  C verb
+   D1||to learn
+W|X|舊
+ P||jiu4
+  C adj
+   D1||old
+   D2||former
+",
+    );
+    let entries_new = db_model::load_word_entries(&conn_new).unwrap();
+    let changes = db_diff::diff_entries(
+        &db_model::load_word_entries(&conn_old).unwrap(),
+        &entries_new,
+    );
+    let counts_old = db_model::count_entries(&conn_old).unwrap();
+    let counts_new = db_model::count_entries(&conn_new).unwrap();
+    assert_eq!(counts_new.num_words, 5);
+
+    let mut out: Vec<u8> = vec![];
+    changelog::write_changelog(
+        &mut out,
+        "Release 1.1",
+        (&counts_old, &counts_new),
+        &changes,
+        &entries_new,
+    )
+    .unwrap();
+    let md = String::from_utf8(out).unwrap();
+
+    assert!(md.starts_with("# Release 1.1\n\n## Summary\n"));
+    assert!(md.contains("| words | 3 | 5 | +2 |\n| definitions | 5 | 6 | +1 |\n"));
+    assert!(md.contains("| definitions | 0 | 1 | 0 |\n| pronunciations | 1 | 0 | 0 |\n"));
+    assert!(md.contains(
+        "## New words\n\n### taiwan-only (1)\n\n- 早 (zǎo): \\*good\\* morning\n\n### without tags (1)\n\n- 學 (xué): to learn\n"
+    ));
+    assert!(md.contains("## Removed definitions\n\n- 你好#D2: hi\n"));
+    // the deleted tag on a word is listed with all of its definitions
+    assert!(md.contains(
+        "## Definitions tagged as deleted\n\n- 再見／再见#D1: goodbye\n- 舊: old; former\n"
+    ));
+    assert!(md.contains("## Changed pronunciations\n\n- 再見／再见#D1: +zai4jian5\n"));
+}
@@ -0,0 +1,458 @@
+use rusqlite::Connection;
+
+use fmld::db_check::{self, Check};
+use fmld::db_edit;
+use fmld::db_model;
+use fmld::db_to_txt;
+use fmld::txt_to_db;
+
+#[test]
+fn test_move_usage_labels() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W||老師／老师
+ P||lao3shi1
+  C noun
+   D1||teacher (chiefly Taiwan)
+   D2|-|master (obsolete)
+   D3||(Taiwan)
+   D4||elder (obsolete, chiefly Taiwan) (of a school)
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let usage_labels = db_edit::UsageLabels::default();
+
+    // dry run
+    let tx = conn.transaction().unwrap();
+    let changes = db_edit::move_usage_labels_to_tags(&tx, &usage_labels).unwrap();
+    drop(tx);
+    assert_eq!(
+        changes,
+        vec![
+            "老師／老师#D1: \"teacher (chiefly Taiwan)\" -> \"teacher\", added t",
+            "老師／老师#D2: kept (obsolete), the tag x conflicts with -",
+            "老師／老师#D3: kept \"(Taiwan)\", nothing would be left of the definition",
+            "老師／老师#D4: \"elder (obsolete, chiefly Taiwan) (of a school)\" -> \"elder (of a school)\", added xt",
+        ]
+    );
+    let mut txt_unchanged: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_unchanged, &conn, false, None).unwrap();
+    assert_eq!(String::from_utf8(txt_unchanged).unwrap(), txt);
+
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::move_usage_labels_to_tags(&tx, &usage_labels).unwrap(),
+        changes
+    );
+    tx.commit().unwrap();
+    let mut txt_moved: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_moved, &conn, false, None).unwrap();
+    assert_eq!(
+        String::from_utf8(txt_moved).unwrap(),
+        "W||老師／老师
+ P||lao3shi1
+  C noun
+   D1|t|teacher
+   D2|-|master (obsolete)
+   D3||(Taiwan)
+   D4|xt|elder (of a school)
+"
+    );
+
+    assert_eq!(
+        db_edit::UsageLabels::new([("dialect", 'q')]).unwrap_err(),
+        "Unknown tag q for the usage label dialect"
+    );
+    assert!(
+        db_edit::UsageLabels::new([("(dialect", 'x')])
+            .unwrap_err()
+            .starts_with("Invalid usage label (dialect")
+    );
+}
+
+#[test]
+fn test_split_definitions() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W||秤
+ P||cheng4
+  C noun
+   D1||a scale --- a balance
+   D2||a steelyard
+   D3||a scale --- a weighing machine
+    N1 my note
+   D4||a unit --- of weight --- old
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let reference = db_check::StableIdsReference {
+        entries: db_model::load_word_entries(&conn).unwrap(),
+        min_similarity: 0.9,
+    };
+
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::split_definition_paths(&tx).unwrap(),
+        vec![
+            "秤#D5: added \"a scale\"",
+            "秤#D1: \"a scale --- a balance\" -> \"a balance\" below D5",
+            "秤#D3: \"a scale --- a weighing machine\" -> \"a weighing machine\" below D5",
+            "秤#D6: added \"a unit\"",
+            "秤#D7: added \"of weight\" below D6",
+            "秤#D4: \"a unit --- of weight --- old\" -> \"old\" below D7",
+        ]
+    );
+    tx.commit().unwrap();
+    let mut txt_split: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_split, &conn, false, None).unwrap();
+    assert_eq!(
+        String::from_utf8(txt_split).unwrap(),
+        "W||秤
+ P||cheng4
+  C noun
+   D5||a scale
+    D1||a balance
+    D3||a weighing machine
+     N1 my note
+   D2||a steelyard
+   D6||a unit
+    D7||of weight
+     D4||old
+"
+    );
+
+    // the split definitions keep their meaning
+    assert!(
+        db_check::StableIds::new(reference)
+            .run(&conn)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_tag_relevance_by_frequency() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+W|-|早
+ P||zao3
+  C adj
+   D1||early
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+W||嗎／吗
+ P||ma5
+  C particle
+   D1||question particle
+W||沒有／没有
+ P||mei2you3
+  C verb
+   D1||not have
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let frequency_list = db_edit::FrequencyList::read(
+        &mut "Word\tWCount\tW/million
+的\t1000\t5.0
+吗\t100\t0.1
+早\t400\t0.4
+你好\t500\t0.5
+再见\t300\t0.3
+"
+        .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(frequency_list.rank("嗎", "吗"), Some(5));
+    assert_eq!(frequency_list.rank("沒有", "没有"), None);
+    let word_list = db_edit::FrequencyList::read(&mut "的\n你好\n".as_bytes()).unwrap();
+    assert_eq!(word_list.rank("你好", "你好"), Some(2));
+
+    let thresholds = db_edit::RelevanceThresholds::new(2, 3, 4).unwrap();
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::tag_relevance_by_frequency(&tx, &frequency_list, thresholds).unwrap(),
+        vec![
+            "你好: rank 2, added +",
+            "再見／再见: rank 4, added -",
+            "嗎／吗: rank 5, added x",
+        ]
+    );
+    tx.commit().unwrap();
+    let ranks: Vec<Option<u32>> = conn
+        .prepare("SELECT frequency_rank FROM dict_word ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(ranks, vec![Some(2), Some(3), Some(4), Some(5), None]);
+    let mut txt_tagged: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_tagged, &conn, false, None).unwrap();
+    let txt_tagged = String::from_utf8(txt_tagged).unwrap();
+    assert!(txt_tagged.starts_with("W|+f|你好\n"));
+    assert!(txt_tagged.contains("W|-|早\n"));
+    assert!(txt_tagged.contains("W|-f|再見／再见\n"));
+    assert!(txt_tagged.contains("W|xf|嗎／吗\n"));
+    assert!(txt_tagged.contains("W||沒有／没有\n"));
+
+    // tags which are set by a human are kept, tags from a previous run are updated
+    let frequency_list =
+        db_edit::FrequencyList::read(&mut "你好 500\n早 400\n再见 300\n".as_bytes()).unwrap();
+    let thresholds = db_edit::RelevanceThresholds::new(3, 3, 4).unwrap();
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::tag_relevance_by_frequency(&tx, &frequency_list, thresholds).unwrap(),
+        vec![
+            "早: kept -, rank 2 would be +",
+            "再見／再见: rank 3, changed - to +",
+            "嗎／吗: no rank, removed x",
+        ]
+    );
+    tx.commit().unwrap();
+    let mut txt_tagged: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_tagged, &conn, false, None).unwrap();
+    let txt_tagged = String::from_utf8(txt_tagged).unwrap();
+    assert!(txt_tagged.contains("W|+f|再見／再见\n"));
+    assert!(txt_tagged.contains("W||嗎／吗\n"));
+
+    assert_eq!(
+        db_edit::RelevanceThresholds::new(3, 2, 4).unwrap_err(),
+        "Relevance thresholds must be in ascending order: 3,2,4"
+    );
+}
+
+#[test]
+fn test_tag_regional_pronunciations() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W||垃圾
+ P||la1ji1
+  C noun
+   D1||garbage
+W||頭髮／头发
+ P||tou2fa5 |C|tou2fa3
+  C noun
+   D1||hair
+W||期
+ P||qi1; qi2
+  C noun
+   D1||period
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let regional_readings = db_edit::RegionalReadings::read(
+        &mut "# word\tTaiwan\tmainland
+垃圾\tle4se4; la1ji1\tla1ji1
+头发\ttou2 fa3\ttou2fa
+期\tqi2\tqi1; qi2
+你好\tni3hao3\tni3hao3
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::tag_regional_pronunciations(&tx, &regional_readings).unwrap(),
+        vec![
+            "垃圾: la1ji1 added c",
+            "垃圾: le4se4 (Taiwan) is missing",
+            "頭髮／头发: tou2fa5 added C",
+            "頭髮／头发: tou2fa3 kept C, the table suggests T",
+            "期: qi1 added C",
+            "期: qi2 added t",
+        ]
+    );
+    tx.commit().unwrap();
+    let mut txt_tagged: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_tagged, &conn, false, None).unwrap();
+    let txt_tagged = String::from_utf8(txt_tagged).unwrap();
+    assert!(txt_tagged.contains(" P|c|la1ji1\n"));
+    assert!(txt_tagged.contains(" P|C|qi1 |t|qi2\n"));
+    assert!(txt_tagged.contains(" P||ni3hao3\n"));
+}
+
+#[test]
+fn test_add_unihan_variant_references() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let txt = "W||回
+ P||hui2
+  C verb
+   D1||to return
+W||囘
+ X=||囬
+ P||hui2
+  C variant
+   D1||variant of 回
+W||囬
+ X=||囘
+ P||hui2
+  C variant
+   D1||variant of 回
+W||迴
+ P||hui2
+  C verb
+   D1||to circle
+W||峯
+ Xv||峰
+ P||feng1
+  C variant
+   D1||variant of 峰
+W||峰
+ P||feng1
+  C noun
+   D1||peak
+W||崶
+ P||feng1
+  C variant
+   D1||variant of 峰
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let unihan_variants = db_edit::UnihanVariants::read(
+        &mut "# Unihan_Variants.txt
+U+56D8\tkSemanticVariant\tU+56DE<kMatthews
+U+56DE\tkSemanticVariant\tU+56D8<kMatthews U+8FF4<kMeyerWempe
+U+56EC\tkZVariant\tU+56DE
+U+5CEF\tkZVariant\tU+5CF0
+U+5D36\tkSemanticVariant\tU+5CEF<kFenn
+U+56DE\tkTraditionalVariant\tU+56DE
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::add_unihan_variant_references(&tx, &unihan_variants).unwrap(),
+        vec![
+            "回, 囘, 迴, 囬: variants (kSemanticVariant, kZVariant), the main form is unknown",
+            "崶: added v 峰 (kSemanticVariant of 峯)",
+        ]
+    );
+    tx.commit().unwrap();
+    let mut txt_added: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_added, &conn, false, None).unwrap();
+    let txt_added = String::from_utf8(txt_added).unwrap();
+    assert!(txt_added.contains("W||峯\n Xv||峰\n P||feng1\n"));
+    assert!(txt_added.contains("W||崶\n Xv|u|峰\n P||feng1\n"));
+    assert!(!txt_added.contains("Xv|u|峯\n"));
+    assert!(txt_added.contains("W||回\n P||hui2\n"));
+
+    // the more frequent word is the main form
+    conn.execute(
+        "UPDATE dict_word SET frequency_rank = 300 WHERE trad = '回'",
+        [],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE dict_word SET frequency_rank = 2000 WHERE trad = '迴'",
+        [],
+    )
+    .unwrap();
+    let tx = conn.transaction().unwrap();
+    assert_eq!(
+        db_edit::add_unihan_variant_references(&tx, &unihan_variants).unwrap(),
+        vec![
+            "囘: added v 回 (kSemanticVariant)",
+            "迴: added v 回 (kSemanticVariant)",
+            "囬: added v 回 (kZVariant)",
+        ]
+    );
+    tx.commit().unwrap();
+    let mut txt_added: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut txt_added, &conn, false, None).unwrap();
+    let txt_added = String::from_utf8(txt_added).unwrap();
+    assert!(txt_added.contains("W||囘\n X=||囬\n Xv|u|回\n P||hui2\n"));
+    assert!(txt_added.contains("W||囬\n X=||囘\n Xv|u|回\n P||hui2\n"));
+    assert!(txt_added.contains("W||迴\n Xv|u|回\n P||hui2\n"));
+}
+
+#[test]
+fn test_add_composition_references() {
+    let txt = "W||電腦遊戲／电脑游戏
+ P||dian4nao3 you2xi4
+  C noun
+   D1||computer game
+W||電腦／电脑
+ P||dian4nao3
+  C noun
+   D1||computer
+W||遊戲／游戏
+ P||you2xi4
+  C noun
+   D1||game
+W||電腦桌／电脑桌
+ X>||電腦／电脑
+ P||dian4nao3zhuo1
+  C noun
+   D1||computer desk
+W||桌
+ P||zhuo1
+  C noun
+   D1||table
+W||機器人／机器人
+ P||ji1qi4ren2
+  C noun
+   D1||robot
+W|-|機器／机器
+ P||ji1qi4
+  C noun
+   D1||machine
+W|X|腦遊／脑游
+ P||nao3you2
+  C noun
+   D1||deleted
+";
+    let add = |options| {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+        assert!(errors.is_empty(), "{errors:?}");
+        let tx = conn.transaction().unwrap();
+        let changes = db_edit::add_composition_references(&tx, options).unwrap();
+        tx.commit().unwrap();
+        let mut txt_added: Vec<u8> = vec![];
+        db_to_txt::db_to_txt(&mut txt_added, &conn, false, None).unwrap();
+        (changes, String::from_utf8(txt_added).unwrap())
+    };
+
+    let (changes, txt_added) = add(db_edit::CompositionOptions::default());
+    assert_eq!(
+        changes,
+        vec![
+            "電腦遊戲／电脑游戏: added > 電腦／电脑",
+            "電腦／电脑: added < 電腦遊戲／电脑游戏",
+            "電腦遊戲／电脑游戏: added > 遊戲／游戏",
+            "遊戲／游戏: added < 電腦遊戲／电脑游戏",
+            "電腦／电脑: added < 電腦桌／电脑桌",
+            "機器人／机器人: added > 機器／机器",
+            "機器／机器: added < 機器人／机器人",
+        ]
+    );
+    assert!(txt_added.contains("W||電腦遊戲／电脑游戏\n X>|s|電腦／电脑;遊戲／游戏\n"));
+    assert!(txt_added.contains("W||電腦／电脑\n X<|s|電腦遊戲／电脑游戏;電腦桌／电脑桌\n"));
+    assert!(txt_added.contains("W||電腦桌／电脑桌\n X>||電腦／电脑\n P||"));
+
+    let (changes, _) = add(db_edit::CompositionOptions {
+        min_length: 1,
+        relevant_only: true,
+    });
+    assert_eq!(
+        changes,
+        vec![
+            "電腦遊戲／电脑游戏: added > 電腦／电脑",
+            "電腦／电脑: added < 電腦遊戲／电脑游戏",
+            "電腦遊戲／电脑游戏: added > 遊戲／游戏",
+            "遊戲／游戏: added < 電腦遊戲／电脑游戏",
+            "電腦／电脑: added < 電腦桌／电脑桌",
+            "電腦桌／电脑桌: added > 桌",
+            "桌: added < 電腦桌／电脑桌",
+        ]
+    );
+}
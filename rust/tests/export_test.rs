@@ -0,0 +1,186 @@
+use std::fs::File;
+
+use rusqlite::Connection;
+
+use fmld::db_edit;
+use fmld::db_model;
+use fmld::export_dsl;
+use fmld::export_ime;
+use fmld::export_latex;
+use fmld::export_rdf;
+use fmld::txt_to_db;
+
+mod common;
+
+fn load_entries_from_str(txt: &str) -> Vec<db_model::WordEntry> {
+    db_model::load_word_entries(&common::load_db_from_str(txt)).unwrap()
+}
+
+fn load_test_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let mut file = File::open("./tests/txt2db2txt_input.txt").unwrap();
+    txt_to_db::txt_to_db(&mut file, &conn, None);
+    let tx = conn.transaction().unwrap();
+    db_edit::add_missing_symmetric_references(&tx).unwrap();
+    db_edit::add_missing_notes_and_tags_for_symmetric_references(&tx).unwrap();
+    tx.commit().unwrap();
+    conn
+}
+
+fn load_test_entries() -> Vec<db_model::WordEntry> {
+    db_model::load_word_entries(&load_test_db()).unwrap()
+}
+
+#[test]
+fn test_export_rdf() {
+    let conn = load_test_db();
+    let entries = db_model::load_word_entries(&conn).unwrap();
+    let redirects = db_model::load_redirects(&conn).unwrap();
+    let mut out: Vec<u8> = vec![];
+    export_rdf::write_rdf(&mut out, &entries, &redirects, "https://example.org/fmld/").unwrap();
+    let ttl = String::from_utf8(out).unwrap();
+
+    assert!(ttl.contains("@prefix ontolex: <http://www.w3.org/ns/lemon/ontolex#> ."));
+    assert!(ttl.contains(
+        "<https://example.org/fmld/entry/Trad1/Simp1#D2> a ontolex:LexicalSense ;\n    dct:identifier \"D2\" ;\n    skos:definition \"my second definition, Taiwan only\"@en ;\n    fmld:tag \"taiwan-only\" ."
+    ));
+    // reference between two definitions
+    assert!(ttl.contains(
+        "[] a vartrans:SenseRelation ;\n    vartrans:source <https://example.org/fmld/entry/Trad1/Simp1#D1> ;\n    vartrans:target <https://example.org/fmld/entry/你好/嗎#D3> ;\n    vartrans:category fmld:wordVariantOf ."
+    ));
+    // moved and deleted definitions
+    assert!(ttl.contains(
+        "<https://example.org/fmld/entry/您好/您好#D1> dct:isReplacedBy <https://example.org/fmld/entry/你好/嗎#D3> ."
+    ));
+    assert!(ttl.contains(
+        "<https://example.org/fmld/entry/您好/您好#D4> fmld:deleted \"2025-06-01\"^^xsd:date ."
+    ));
+    // multiline notes are escaped
+    assert!(ttl.contains("skos:note \"this is actually wrong! \\nLong note this one is!\"@en"));
+    // every statement is terminated
+    for block in ttl.split("\n\n").filter(|b| !b.trim().is_empty()) {
+        assert!(block.trim_end().ends_with('.'), "unterminated block: {block}");
+    }
+}
+
+#[test]
+fn test_export_dsl() {
+    let entries = load_test_entries();
+    let mut out: Vec<u8> = vec![];
+    export_dsl::write_dsl(&mut out, &entries).unwrap();
+
+    assert_eq!(&out[..2], &[0xFF, 0xFE], "missing UTF-16LE byte order mark");
+    let utf16: Vec<u16> = out[2..]
+        .chunks(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    let dsl = String::from_utf16(&utf16).unwrap();
+
+    assert!(dsl.starts_with("#NAME \"Free Mandarin Learner's Dictionary\"\r\n"));
+    assert!(dsl.contains("\r\nTrad2\r\nSimp2\r\n\t[m1][p]character-variant-of[/p] [ref]Trad1[/ref] / [ref]Simp1[/ref][/m]\r\n"));
+    assert!(dsl.contains("\t[m2][i]adj[/i][/m]\r\n\t[m3][b]1.[/b] [trn]my first definition[/trn][/m]\r\n"));
+    // brackets in the definition are escaped
+    assert!(dsl.contains("[trn]variant of \\[Trad1 / Simp1\\][/trn]"));
+    assert!(dsl.contains("\t[m4][com]my note[/com][/m]"));
+}
+
+#[test]
+fn test_export_ime() {
+    let entries = load_entries_from_str(
+        "W|+|你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+ P|x|zai4xian4
+  C phrase
+   D2|X|deleted reading
+ P|x|zai4jian5
+  C phrase
+   D3||rare reading, same toneless pinyin as D1
+W|X|舊詞／旧词
+ P||jiu4ci2
+  C noun
+   D1||old word
+W||一點兒／一点儿
+ P||yi1dian3r5
+  C noun
+   D1||a little
+",
+    );
+    let mut out: Vec<u8> = vec![];
+    export_ime::write_rime(&mut out, &entries, "fmld").unwrap();
+    let rime = String::from_utf8(out).unwrap();
+    assert!(rime.contains("\nname: fmld\n"));
+    let (_, rime_entries) = rime.split_once("...\n\n").unwrap();
+    assert_eq!(
+        rime_entries,
+        "你好\tni hao\t1000
+再見\tzai jian\t100
+再见\tzai jian\t100
+一點兒\tyi dian er\t100
+一点儿\tyi dian er\t100
+"
+    );
+
+    let mut out: Vec<u8> = vec![];
+    export_ime::write_fcitx(&mut out, &entries).unwrap();
+    let fcitx = String::from_utf8(out).unwrap();
+    assert!(fcitx.starts_with("你好 ni'hao 1000\n再見 zai'jian 100\n"));
+}
+
+#[test]
+fn test_export_latex() {
+    let entries = load_entries_from_str(
+        "W||好
+ P||hao3
+  C adj
+   D1||good; 100% fine
+   D2|-|rarely used sense
+   D3|x|obscure sense
+W||阿姨
+ P||a1yi2
+  C noun
+   D1||aunt
+   X~||好#D1
+W|X|舊
+ P||jiu4
+  C adj
+   D1||old
+",
+    );
+    let render = |relevance: Vec<Option<char>>| {
+        let options = export_latex::LatexOptions {
+            order: export_latex::LatexOrder::Pinyin,
+            relevance,
+            radicals: std::collections::HashMap::new(),
+        };
+        let mut out: Vec<u8> = vec![];
+        export_latex::write_latex(&mut out, &entries, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let tex = render(vec![None, Some('-')]);
+    assert!(tex.starts_with("\\documentclass"));
+    assert!(tex.ends_with("\\end{document}\n"));
+    // sections by pinyin, sorted
+    let a = tex.find("\\dictsection{A}").unwrap();
+    let h = tex.find("\\dictsection{H}").unwrap();
+    assert!(a < h);
+    // special characters are escaped
+    assert!(tex.contains("good; 100\\% fine"));
+    // references link to the printed entry or definition
+    assert!(tex.contains("\\taglabel{$\\approx$} \\hyperlink{w1-1}{好~1}"));
+    assert!(tex.contains("\\hypertarget{w1-1}{}"));
+    assert!(tex.contains("\\entry{hǎo}{w1}{好}"));
+    // relevance filter
+    assert!(tex.contains("rarely used sense"));
+    assert!(!tex.contains("obscure sense"));
+    assert!(!tex.contains("舊"));
+
+    let tex = render(vec![None]);
+    assert!(!tex.contains("rarely used sense"));
+}
@@ -0,0 +1,158 @@
+use fmld::txt_merge::merge_txt;
+
+const BASE: &str = "# header
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+   D2||hi
+    N5 a note
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+W||早
+ P||zao3
+  C adj
+   D1||early
+";
+
+#[test]
+fn test_merge_without_conflicts() {
+    let ours = "# header
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello!
+   D2||hi
+    N5 a note
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+W||早
+ P||zao3
+  C adj
+   D1||early
+   D2||morning
+    N? ours new note
+W||學
+ P||xue2
+  C verb
+   D1||to learn
+";
+    // changes to another definition of the same word, the same new definition id and the same new note id
+    let theirs = "# header
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello
+   D2|T|hi there
+    N5 a changed note
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    X=||早#D2
+W||早
+ P||zao3
+  C adj
+   D1||early
+   D2||good morning
+    N? theirs new note
+";
+    let result = merge_txt(BASE, ours, theirs).unwrap();
+    assert_eq!(result.conflicts, 0);
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(
+        result.text,
+        "# header
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello!
+   D2|T|hi there
+    N5 a changed note
+W||再見／再见
+ P||zai4jian4
+  C phrase
+   D1||goodbye
+    X=||早#D3
+W||早
+ P||zao3
+  C adj
+   D1||early
+   D2||morning
+    N1 ours new note
+   D3||good morning
+    N6 theirs new note
+    X=||再見／再见#D1
+W||學
+ P||xue2
+  C verb
+   D1||to learn
+"
+    );
+}
+
+#[test]
+fn test_merge_with_conflicts() {
+    let ours = BASE.replace("D1||hello\n", "D1||hello!\n");
+    let theirs = BASE
+        .replace("D1||hello\n", "D1||hello?\n")
+        .replace("D1||early", "D1||early, soon");
+    let result = merge_txt(BASE, &ours, &theirs).unwrap();
+    assert_eq!(result.conflicts, 1);
+    assert!(result.text.contains(
+        "<<<<<<< ours
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello!
+   D2||hi
+    N5 a note
+=======
+W||你好
+ P||ni3hao3
+  C phrase
+   D1||hello?
+   D2||hi
+    N5 a note
+>>>>>>> theirs
+W||再見／再见"
+    ));
+    assert!(result.text.ends_with("   D1||early, soon\n"));
+
+    // deleted on one side, changed on the other side
+    let ours = BASE.split("W||早").next().unwrap();
+    let theirs = BASE.replace("D1||early", "D1||soon");
+    let result = merge_txt(BASE, ours, &theirs).unwrap();
+    assert_eq!(result.conflicts, 1);
+    assert!(result.text.contains("<<<<<<< ours\n=======\nW||早\n"));
+}
+
+#[test]
+fn test_merge_sub_definitions() {
+    let base = "W||過秤／过秤
+ P||guo4cheng4
+  C verb
+   D1||to weigh
+    D2||to measure the weight
+    D3||to be weighed
+";
+    let ours = base.replace("D2||to measure the weight", "D2||to measure the weight of");
+    let theirs = base.replace("D3||to be weighed", "D3||to be weighed on a scale");
+    let result = merge_txt(base, &ours, &theirs).unwrap();
+    assert_eq!(result.conflicts, 0);
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(
+        result.text,
+        "W||過秤／过秤
+ P||guo4cheng4
+  C verb
+   D1||to weigh
+    D2||to measure the weight of
+    D3||to be weighed on a scale
+"
+    );
+}
@@ -6,6 +6,8 @@ use rusqlite::Connection;
 
 use fmld::db_check;
 use fmld::db_edit;
+use fmld::db_migrate;
+use fmld::db_model::{self, Resolution};
 use fmld::db_to_txt;
 use fmld::txt_to_db;
 
@@ -42,3 +44,153 @@ fn test_txt_to_db_to_txt() {
     }
     assert_eq!(txt_expected, txt_out, "output does not match expected file");
 }
+
+#[test]
+fn test_resolve_definition() {
+    let conn = Connection::open_in_memory().unwrap();
+    let mut file = File::open("./tests/txt2db2txt_input.txt").unwrap();
+    txt_to_db::txt_to_db(&mut file, &conn, None);
+
+    let definition = |trad: &str, simp: &str, ext_def_id| Resolution::Definition {
+        trad: trad.to_owned(),
+        simp: simp.to_owned(),
+        ext_def_id,
+    };
+    assert_eq!(
+        db_model::resolve_definition(&conn, "您好", "您好", 2).unwrap(),
+        definition("您好", "您好", 2)
+    );
+    // redirect to a redirect
+    assert_eq!(
+        db_model::resolve_definition(&conn, "妳好", "妳好", 1).unwrap(),
+        definition("你好", "嗎", 3)
+    );
+    assert_eq!(
+        db_model::resolve_definition(&conn, "您好", "您好", 4).unwrap(),
+        Resolution::Deleted {
+            date: "2025-06-01".to_owned()
+        }
+    );
+    assert_eq!(
+        db_model::resolve_definition(&conn, "您好", "您好", 5).unwrap(),
+        Resolution::NotFound
+    );
+
+    // words with redirects only are loaded without definitions
+    let entries = db_model::load_word_entries(&conn).unwrap();
+    let redirect_only = entries.iter().find(|w| w.trad == "妳好").unwrap();
+    assert!(redirect_only.definitions.is_empty());
+}
+
+#[test]
+fn test_redirect_errors() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||你好
+ R1 -> 您好#D1
+ R2 deleted 2025-06-01
+ P||ni3hao3
+  C phrase
+   D2||hi
+W||您好
+ R2 -> 你好#D3
+ P||nin2hao3
+  C phrase
+   D3||hello
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert_eq!(
+        errors,
+        vec![
+            "Error for 你好 in line 2:",
+            "  R1 -> 您好#D1",
+            "  Reference target not found: 您好#D1",
+            "Error for 你好 in line 3:",
+            "  R2 deleted 2025-06-01",
+            "  Redirect for an id which is used by a definition: D2",
+            "Error for 您好 in line 8:",
+            "  R2 -> 你好#D3",
+            "  Reference target not found: 你好#D3",
+        ]
+    );
+}
+
+#[test]
+fn test_sub_definitions() {
+    let conn = Connection::open_in_memory().unwrap();
+    let txt = "W||過秤／过秤
+ P||guo4cheng4
+  C verb
+   D1||to weigh
+    D2||to measure the weight
+     D3||of an object
+    D4||to be weighed
+   D5||to check
+";
+    let errors = txt_to_db::txt_to_db(&mut txt.as_bytes(), &conn, None);
+    assert!(errors.is_empty(), "{errors:?}");
+    let mut output: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut output, &conn, false, None).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), txt);
+
+    let words = db_model::load_word_entries(&conn).unwrap();
+    let word = &words[0];
+    let definition = |id| {
+        word.definitions
+            .iter()
+            .find(|d| d.ext_def_id == id)
+            .unwrap()
+    };
+    assert_eq!(word.depth(definition(3)), 2);
+    assert_eq!(word.depth(definition(5)), 0);
+    assert_eq!(
+        word.full_definition(definition(3)),
+        "to weigh --- to measure the weight --- of an object"
+    );
+}
+
+#[test]
+fn test_read_db_v1() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(include_str!("db_v1.sql")).unwrap();
+    assert_eq!(db_migrate::migrate(&conn).unwrap(), 1);
+    assert_eq!(
+        conn.pragma_query_value(None, "user_version", |row| row.get::<_, u32>(0))
+            .unwrap(),
+        4
+    );
+    assert!(db_model::load_redirects(&conn).unwrap().is_empty());
+    let ranks: u32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM dict_word WHERE frequency_rank IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(ranks, 2);
+    let words = db_model::load_word_entries(&conn).unwrap();
+    assert!(
+        words[0]
+            .definitions
+            .iter()
+            .all(|d| d.parent_ext_def_id.is_none())
+    );
+
+    let mut output: Vec<u8> = vec![];
+    db_to_txt::db_to_txt(&mut output, &conn, false, None).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "W|w|你好
+ X~||您好
+ P||ni3hao3
+  C phrase
+   D1||hello
+    N101 informal
+   D2|x|hi
+W||您好
+ X~||你好
+ P||nin2hao3
+  C phrase
+   D1||hello (polite)
+"
+    );
+}